@@ -47,6 +47,14 @@ pub const ACP_TYPE_HASH_LINA: H256 =
 pub const ACP_TYPE_HASH_AGGRON: H256 =
     h256!("0x3419a1c09eb2567f6552ee7a8ecffd64155cffe0f1796e6e61ec088d740c1356");
 
+/// OmniLock script mainnet code hash, see:
+/// <https://github.com/nervosnetwork/rfcs/blob/master/rfcs/0042-omnilock/0042-omnilock.md#notes>
+pub const OMNILOCK_TYPE_HASH_LINA: H256 =
+    h256!("0x9b819793a64463aed77c615d6cb226eea5487ccfc0783043a587254cda2b6f26");
+/// OmniLock script testnet code hash
+pub const OMNILOCK_TYPE_HASH_AGGRON: H256 =
+    h256!("0xf329effd1c475a2978453c8600e1eaf0bc2087ee093c3ee64cc96ec6847752cb");
+
 /// cheque withdraw since value
 pub const CHEQUE_CELL_SINCE: u64 = 0xA000000000000006;
 
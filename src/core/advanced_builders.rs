@@ -42,6 +42,21 @@ impl ::std::default::Default for TransactionBuilder {
     }
 }
 
+impl From<&core::TransactionView> for TransactionBuilder {
+    /// Converts a [`TransactionView`](core::TransactionView) back to a builder to modify it.
+    fn from(tx: &core::TransactionView) -> Self {
+        Self {
+            version: tx.version().pack(),
+            cell_deps: tx.cell_deps().into_iter().collect(),
+            header_deps: tx.header_deps().into_iter().collect(),
+            inputs: tx.inputs().into_iter().collect(),
+            outputs: tx.outputs().into_iter().collect(),
+            witnesses: tx.witnesses().into_iter().collect(),
+            outputs_data: tx.outputs_data().into_iter().collect(),
+        }
+    }
+}
+
 macro_rules! def_setter_simple {
     (__add_doc, $prefix:ident, $field:ident, $type:ident, $comment:expr) => {
         #[doc = $comment]
@@ -16,11 +16,11 @@ pub mod test_util;
 #[cfg(test)]
 mod tests;
 
-pub use rpc::{CkbRpcClient, IndexerRpcClient, RpcError};
+pub use rpc::{AsyncCkbRpcClient, AsyncIndexerRpcClient, CkbRpcClient, IndexerRpcClient, RpcError};
 pub use types::{
     Address, AddressPayload, AddressType, CodeHashIndex, HumanCapacity, NetworkInfo, NetworkType,
-    OldAddress, OldAddressFormat, ScriptGroup, ScriptGroupType, ScriptId, Since, SinceType,
-    TransactionWithScriptGroups,
+    OldAddress, OldAddressFormat, PortableTransaction, ScriptGroup, ScriptGroupType, ScriptId,
+    Since, SinceType, TransactionWithScriptGroups,
 };
 
 pub use ckb_crypto::secp::SECP256K1;
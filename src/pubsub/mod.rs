@@ -51,7 +51,7 @@ use tokio_util::codec::Framed;
 
 use stream_codec::StreamCodec;
 
-mod stream_codec;
+pub(crate) mod stream_codec;
 
 /// General rpc subscription client
 pub struct Client<T> {
@@ -0,0 +1,158 @@
+use std::time::Duration;
+
+use anyhow::anyhow;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+
+use super::{RetryPolicy, RpcError};
+
+/// Rpc clients which can be created by [`RpcClientBuilder`], implemented by the
+/// clients generated with `jsonrpc!` and `jsonrpc_async!`.
+pub trait FromRpcClientBuilder: Sized {
+    fn from_builder(builder: &RpcClientBuilder) -> Result<Self, RpcError>;
+}
+
+/// Configure the http transport of the rpc clients, e.g. timeouts, headers and proxy.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use ckb_sdk::rpc::{CkbRpcClient, RpcClientBuilder};
+/// let client: CkbRpcClient = RpcClientBuilder::new("https://testnet.ckb.dev")
+///     .timeout(Duration::from_secs(10))
+///     .bearer_auth("token")
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct RpcClientBuilder {
+    url: String,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    headers: HeaderMap,
+    proxy: Option<reqwest::Proxy>,
+    user_agent: Option<String>,
+    retry_policy: Option<RetryPolicy>,
+    // the first invalid header, reported on build
+    error: Option<String>,
+}
+
+impl RpcClientBuilder {
+    pub fn new(url: &str) -> RpcClientBuilder {
+        RpcClientBuilder {
+            url: url.to_string(),
+            timeout: None,
+            connect_timeout: None,
+            headers: HeaderMap::new(),
+            proxy: None,
+            user_agent: None,
+            retry_policy: None,
+            error: None,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Timeout of a whole request, the blocking clients use reqwest's default 30 seconds when not set.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Add a header sent with every request, e.g. the api key of a hosted node.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        match (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            (Ok(name), Ok(mut value)) => {
+                value.set_sensitive(true);
+                self.headers.insert(name, value);
+            }
+            _ => {
+                self.error
+                    .get_or_insert_with(|| format!("invalid header `{}`", name));
+            }
+        }
+        self
+    }
+
+    /// Send `Authorization: Bearer <token>` with every request.
+    pub fn bearer_auth(self, token: &str) -> Self {
+        self.header(AUTHORIZATION.as_str(), &format!("Bearer {}", token))
+    }
+
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// Retry the failed requests, no retry by default.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    pub fn get_retry_policy(&self) -> Option<&RetryPolicy> {
+        self.retry_policy.as_ref()
+    }
+
+    fn check(&self) -> Result<(), RpcError> {
+        match &self.error {
+            Some(err) => Err(RpcError::Other(anyhow!(err.clone()))),
+            None => Ok(()),
+        }
+    }
+
+    pub fn build_blocking_client(&self) -> Result<reqwest::blocking::Client, RpcError> {
+        self.check()?;
+        let mut builder =
+            reqwest::blocking::Client::builder().default_headers(self.headers.clone());
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(proxy) = self.proxy.clone() {
+            builder = builder.proxy(proxy);
+        }
+        if let Some(user_agent) = self.user_agent.as_ref() {
+            builder = builder.user_agent(user_agent);
+        }
+        Ok(builder.build()?)
+    }
+
+    pub fn build_async_client(&self) -> Result<reqwest::Client, RpcError> {
+        self.check()?;
+        let mut builder = reqwest::Client::builder().default_headers(self.headers.clone());
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(proxy) = self.proxy.clone() {
+            builder = builder.proxy(proxy);
+        }
+        if let Some(user_agent) = self.user_agent.as_ref() {
+            builder = builder.user_agent(user_agent);
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Build any of the rpc clients, e.g. `CkbRpcClient` or `AsyncIndexerRpcClient`.
+    pub fn build<C: FromRpcClientBuilder>(&self) -> Result<C, RpcError> {
+        C::from_builder(self)
+    }
+}
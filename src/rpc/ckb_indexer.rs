@@ -4,8 +4,10 @@ use ckb_jsonrpc_types::{
     BlockNumber, Capacity, CellOutput, JsonBytes, OutPoint, Script, Uint32, Uint64,
 };
 use ckb_types::H256;
+use futures::FutureExt;
 use serde::{Deserialize, Serialize};
 
+use super::{AsyncRpcTransport, PageSize, PaginatedIter, PaginatedStream, RpcTransport};
 use crate::traits::{CellQueryOptions, LiveCell, PrimaryScriptType, ValueRangeOption};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -189,9 +191,71 @@ pub struct Pagination<T> {
     pub last_cursor: JsonBytes,
 }
 
-crate::jsonrpc!(pub struct IndexerRpcClient {
-    pub fn get_indexer_tip(&self) -> Option<Tip>;
-    pub fn get_cells(&self, search_key: SearchKey, order: Order, limit: Uint32, after: Option<JsonBytes>) -> Pagination<Cell>;
-    pub fn get_transactions(&self, search_key: SearchKey, order: Order, limit: Uint32, after: Option<JsonBytes>) -> Pagination<Tx>;
-    pub fn get_cells_capacity(&self, search_key: SearchKey) -> Option<CellsCapacity>;
-});
+// The blocking and async clients share the same method set.
+macro_rules! indexer_rpc_client {
+    ($jsonrpc:ident, $struct_name:ident, $batch_name:ident) => {
+        crate::$jsonrpc!(pub struct $struct_name, batch $batch_name {
+            pub fn get_indexer_tip(&self) -> Option<Tip>;
+            pub fn get_cells(&self, search_key: SearchKey, order: Order, limit: Uint32, after: Option<JsonBytes>) -> Pagination<Cell>;
+            pub fn get_transactions(&self, search_key: SearchKey, order: Order, limit: Uint32, after: Option<JsonBytes>) -> Pagination<Tx>;
+            pub fn get_cells_capacity(&self, search_key: SearchKey) -> Option<CellsCapacity>;
+        });
+    };
+}
+
+indexer_rpc_client!(jsonrpc, IndexerRpcClient, IndexerRpcBatch);
+indexer_rpc_client!(jsonrpc_async, AsyncIndexerRpcClient, AsyncIndexerRpcBatch);
+
+impl<T: RpcTransport> IndexerRpcClient<T> {
+    /// Iterate the cells of all pages, e.g. with `PageSize::default()` or a fixed `u32`.
+    pub fn iter_cells<P: Into<PageSize>>(
+        &self,
+        search_key: SearchKey,
+        order: Order,
+        page_size: P,
+    ) -> PaginatedIter<'_, Cell> {
+        PaginatedIter::new(page_size, move |after, limit| {
+            self.get_cells(search_key.clone(), order.clone(), limit, after)
+        })
+    }
+
+    /// Iterate the transactions of all pages.
+    pub fn iter_transactions<P: Into<PageSize>>(
+        &self,
+        search_key: SearchKey,
+        order: Order,
+        page_size: P,
+    ) -> PaginatedIter<'_, Tx> {
+        PaginatedIter::new(page_size, move |after, limit| {
+            self.get_transactions(search_key.clone(), order.clone(), limit, after)
+        })
+    }
+}
+
+impl<T: AsyncRpcTransport> AsyncIndexerRpcClient<T> {
+    /// Same as `IndexerRpcClient::iter_cells`, but return a `Stream`.
+    pub fn iter_cells<P: Into<PageSize>>(
+        &self,
+        search_key: SearchKey,
+        order: Order,
+        page_size: P,
+    ) -> PaginatedStream<'_, Cell> {
+        PaginatedStream::new(page_size, move |after, limit| {
+            self.get_cells(search_key.clone(), order.clone(), limit, after)
+                .boxed()
+        })
+    }
+
+    /// Same as `IndexerRpcClient::iter_transactions`, but return a `Stream`.
+    pub fn iter_transactions<P: Into<PageSize>>(
+        &self,
+        search_key: SearchKey,
+        order: Order,
+        page_size: P,
+    ) -> PaginatedStream<'_, Tx> {
+        PaginatedStream::new(page_size, move |after, limit| {
+            self.get_transactions(search_key.clone(), order.clone(), limit, after)
+                .boxed()
+        })
+    }
+}
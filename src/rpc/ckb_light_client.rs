@@ -5,7 +5,9 @@ use ckb_jsonrpc_types::{
     RemoteNodeProtocol, Script, Transaction, TransactionView, TxStatus, Uint32, Uint64,
 };
 use ckb_types::H256;
+use futures::FutureExt;
 
+use super::{AsyncRpcTransport, PageSize, PaginatedIter, PaginatedStream, RpcTransport};
 pub use crate::rpc::ckb_indexer::{
     Cell, CellType, CellsCapacity, Order, Pagination, ScriptType, SearchKey, SearchKeyFilter,
 };
@@ -136,34 +138,100 @@ pub struct PeerSyncState {
     pub proved_best_known_header: Option<HeaderView>,
 }
 
-crate::jsonrpc!(pub struct LightClientRpcClient {
-    // BlockFilter
-    pub fn set_scripts(&self, scripts: Vec<ScriptStatus>, command: Option<SetScriptsCommand>) -> ();
-    pub fn get_scripts(&self) -> Vec<ScriptStatus>;
-    pub fn get_cells(&self, search_key: SearchKey, order: Order, limit: Uint32, after: Option<JsonBytes>) -> Pagination<Cell>;
-    pub fn get_transactions(&self, search_key: SearchKey, order: Order, limit: Uint32, after: Option<JsonBytes>) -> Pagination<Tx>;
-    pub fn get_cells_capacity(&self, search_key: SearchKey) -> CellsCapacity;
-
-    // Transaction
-    pub fn send_transaction(&self, tx: Transaction) -> H256;
-
-    // Chain
-    pub fn get_tip_header(&self) -> HeaderView;
-    pub fn get_genesis_block(&self) -> BlockView;
-    pub fn get_header(&self, block_hash: H256) -> Option<HeaderView>;
-    pub fn get_transaction(&self, tx_hash: H256) -> Option<TransactionWithStatus>;
-    pub fn estimate_cycles(&self, tx: Transaction)-> EstimateCycles;
-    /// Fetch a header from remote node. If return status is `not_found` will re-sent fetching request immediately.
-    ///
-    /// Returns: FetchStatus<HeaderView>
-    pub fn fetch_header(&self, block_hash: H256) -> FetchStatus<HeaderView>;
+// The blocking and async clients share the same method set.
+macro_rules! light_client_rpc_client {
+    ($jsonrpc:ident, $struct_name:ident, $batch_name:ident) => {
+        crate::$jsonrpc!(pub struct $struct_name, batch $batch_name {
+            // BlockFilter
+            pub fn set_scripts(&self, scripts: Vec<ScriptStatus>, command: Option<SetScriptsCommand>) -> ();
+            pub fn get_scripts(&self) -> Vec<ScriptStatus>;
+            pub fn get_cells(&self, search_key: SearchKey, order: Order, limit: Uint32, after: Option<JsonBytes>) -> Pagination<Cell>;
+            pub fn get_transactions(&self, search_key: SearchKey, order: Order, limit: Uint32, after: Option<JsonBytes>) -> Pagination<Tx>;
+            pub fn get_cells_capacity(&self, search_key: SearchKey) -> CellsCapacity;
+
+            // Transaction
+            pub fn send_transaction(&self, tx: Transaction) -> H256;
+
+            // Chain
+            pub fn get_tip_header(&self) -> HeaderView;
+            pub fn get_genesis_block(&self) -> BlockView;
+            pub fn get_header(&self, block_hash: H256) -> Option<HeaderView>;
+            pub fn get_transaction(&self, tx_hash: H256) -> Option<TransactionWithStatus>;
+            pub fn estimate_cycles(&self, tx: Transaction)-> EstimateCycles;
+            /// Fetch a header from remote node. If return status is `not_found` will re-sent fetching request immediately.
+            ///
+            /// Returns: FetchStatus<HeaderView>
+            pub fn fetch_header(&self, block_hash: H256) -> FetchStatus<HeaderView>;
+
+            /// Fetch a transaction from remote node. If return status is `not_found` will re-sent fetching request immediately.
+            ///
+            /// Returns: FetchStatus<TransactionWithHeader>
+            pub fn fetch_transaction(&self, tx_hash: H256) -> FetchStatus<TransactionWithStatus>;
+
+            // Net
+            pub fn get_peers(&self) -> Vec<RemoteNode>;
+            pub fn local_node_info(&self) -> LocalNode;
+        });
+    };
+}
 
-    /// Fetch a transaction from remote node. If return status is `not_found` will re-sent fetching request immediately.
-    ///
-    /// Returns: FetchStatus<TransactionWithHeader>
-    pub fn fetch_transaction(&self, tx_hash: H256) -> FetchStatus<TransactionWithStatus>;
+light_client_rpc_client!(jsonrpc, LightClientRpcClient, LightClientRpcBatch);
+light_client_rpc_client!(
+    jsonrpc_async,
+    AsyncLightClientRpcClient,
+    AsyncLightClientRpcBatch
+);
+
+impl<T: RpcTransport> LightClientRpcClient<T> {
+    /// Iterate the cells of all pages, e.g. with `PageSize::default()` or a fixed `u32`.
+    pub fn iter_cells<P: Into<PageSize>>(
+        &self,
+        search_key: SearchKey,
+        order: Order,
+        page_size: P,
+    ) -> PaginatedIter<'_, Cell> {
+        PaginatedIter::new(page_size, move |after, limit| {
+            self.get_cells(search_key.clone(), order.clone(), limit, after)
+        })
+    }
+
+    /// Iterate the transactions of all pages.
+    pub fn iter_transactions<P: Into<PageSize>>(
+        &self,
+        search_key: SearchKey,
+        order: Order,
+        page_size: P,
+    ) -> PaginatedIter<'_, Tx> {
+        PaginatedIter::new(page_size, move |after, limit| {
+            self.get_transactions(search_key.clone(), order.clone(), limit, after)
+        })
+    }
+}
 
-    // Net
-    pub fn get_peers(&self) -> Vec<RemoteNode>;
-    pub fn local_node_info(&self) -> LocalNode;
-});
+impl<T: AsyncRpcTransport> AsyncLightClientRpcClient<T> {
+    /// Same as `LightClientRpcClient::iter_cells`, but return a `Stream`.
+    pub fn iter_cells<P: Into<PageSize>>(
+        &self,
+        search_key: SearchKey,
+        order: Order,
+        page_size: P,
+    ) -> PaginatedStream<'_, Cell> {
+        PaginatedStream::new(page_size, move |after, limit| {
+            self.get_cells(search_key.clone(), order.clone(), limit, after)
+                .boxed()
+        })
+    }
+
+    /// Same as `LightClientRpcClient::iter_transactions`, but return a `Stream`.
+    pub fn iter_transactions<P: Into<PageSize>>(
+        &self,
+        search_key: SearchKey,
+        order: Order,
+        page_size: P,
+    ) -> PaginatedStream<'_, Tx> {
+        PaginatedStream::new(page_size, move |after, limit| {
+            self.get_transactions(search_key.clone(), order.clone(), limit, after)
+                .boxed()
+        })
+    }
+}
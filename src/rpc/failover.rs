@@ -0,0 +1,187 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use parking_lot::Mutex;
+use serde_json::Value;
+
+use super::{CkbRpcClient, HttpTransport, RpcError, RpcTransport};
+
+/// The default max blocks the tip of an endpoint can lag behind the best tip.
+const DEFAULT_MAX_TIP_LAG: u64 = 10;
+/// The default interval to check the tips of the endpoints.
+const DEFAULT_TIP_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+struct EndpointsState {
+    healthy: Vec<bool>,
+    last_check: Option<Instant>,
+}
+
+/// A transport over several endpoints, so every rpc client generated by `jsonrpc!` can fail over.
+///
+/// The requests go to the current endpoint, and are sent to the next endpoint when failed
+/// with a retryable error. The endpoints failed to respond or whose tip lags behind the best
+/// tip are marked unhealthy, and only tried after all healthy ones until the next tip check.
+///
+/// The tips are queried by the ckb rpc `get_tip_block_number`, set the tip check interval to
+/// `None` for the endpoints which are not ckb nodes, e.g. light clients.
+///
+/// ```no_run
+/// # use ckb_sdk::rpc::{CkbRpcClient, FailoverTransport};
+/// let transport =
+///     FailoverTransport::new(&["https://mainnet.ckb.dev", "http://127.0.0.1:8114"]).unwrap();
+/// let client = CkbRpcClient::new_with_transport(transport);
+/// let tip = client.get_tip_header().unwrap();
+/// ```
+pub struct FailoverTransport<T = HttpTransport> {
+    transports: Vec<T>,
+    current: AtomicUsize,
+    max_tip_lag: u64,
+    tip_check_interval: Option<Duration>,
+    state: Mutex<EndpointsState>,
+}
+
+impl FailoverTransport {
+    pub fn new(urls: &[&str]) -> Result<FailoverTransport, RpcError> {
+        let transports = urls
+            .iter()
+            .map(|url| HttpTransport::new(url))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new_with_transports(transports)
+    }
+}
+
+impl<T: RpcTransport> FailoverTransport<T> {
+    /// Use the configured transports, e.g. the `HttpTransport` with a configured http client.
+    pub fn new_with_transports(transports: Vec<T>) -> Result<FailoverTransport<T>, RpcError> {
+        if transports.is_empty() {
+            return Err(RpcError::Other(anyhow!("no rpc endpoint")));
+        }
+        let state = EndpointsState {
+            healthy: vec![true; transports.len()],
+            last_check: None,
+        };
+        Ok(FailoverTransport {
+            transports,
+            current: AtomicUsize::new(0),
+            max_tip_lag: DEFAULT_MAX_TIP_LAG,
+            tip_check_interval: Some(DEFAULT_TIP_CHECK_INTERVAL),
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn transports(&self) -> &[T] {
+        &self.transports
+    }
+
+    pub fn max_tip_lag(&self) -> u64 {
+        self.max_tip_lag
+    }
+    pub fn set_max_tip_lag(&mut self, max_tip_lag: u64) {
+        self.max_tip_lag = max_tip_lag;
+    }
+
+    pub fn tip_check_interval(&self) -> Option<Duration> {
+        self.tip_check_interval
+    }
+    /// Set the interval of the tip check before the requests, `None` to only check by `check_tips`.
+    pub fn set_tip_check_interval(&mut self, interval: Option<Duration>) {
+        self.tip_check_interval = interval;
+    }
+
+    /// The index of the endpoint used by the next request
+    pub fn current(&self) -> usize {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    pub fn is_healthy(&self, index: usize) -> bool {
+        self.state
+            .lock()
+            .healthy
+            .get(index)
+            .cloned()
+            .unwrap_or(false)
+    }
+
+    /// Query the tips of all endpoints, the endpoints failed to respond or lagging more than
+    /// `max_tip_lag` blocks are marked unhealthy. Return the best tip block number.
+    pub fn check_tips(&self) -> Result<u64, RpcError> {
+        let mut last_err = None;
+        let tips: Vec<Option<u64>> = self
+            .transports
+            .iter()
+            .enumerate()
+            .map(|(index, transport)| {
+                match CkbRpcClient::new_with_transport(transport).get_tip_block_number() {
+                    Ok(tip) => Some(tip.value()),
+                    Err(err) => {
+                        log::warn!("failed to get the tip of rpc endpoint {}: {}", index, err);
+                        last_err = Some(err);
+                        None
+                    }
+                }
+            })
+            .collect();
+        let best_tip = tips.iter().flatten().max().cloned();
+
+        let mut state = self.state.lock();
+        state.last_check = Some(Instant::now());
+        for (healthy, tip) in state.healthy.iter_mut().zip(tips) {
+            *healthy = match (tip, best_tip) {
+                (Some(tip), Some(best_tip)) => best_tip - tip <= self.max_tip_lag,
+                _ => false,
+            };
+        }
+        match (best_tip, last_err) {
+            (Some(best_tip), _) => Ok(best_tip),
+            (None, Some(err)) => Err(err),
+            (None, None) => Err(RpcError::Other(anyhow!("no rpc endpoint"))),
+        }
+    }
+
+    fn check_tips_if_expired(&self) {
+        let expired = match (self.tip_check_interval, self.state.lock().last_check) {
+            (Some(_), None) => true,
+            (Some(interval), Some(last_check)) => last_check.elapsed() >= interval,
+            (None, _) => false,
+        };
+        if expired {
+            // the failed endpoints are marked unhealthy, the requests can still try them
+            let _ = self.check_tips();
+        }
+    }
+
+    // The healthy endpoints from the current one, then the unhealthy ones as the last resort.
+    fn candidates(&self) -> Vec<usize> {
+        let current = self.current();
+        let state = self.state.lock();
+        let (mut healthy, unhealthy): (Vec<usize>, Vec<usize>) = (0..self.transports.len())
+            .map(|offset| (current + offset) % self.transports.len())
+            .partition(|index| state.healthy[*index]);
+        healthy.extend(unhealthy);
+        healthy
+    }
+}
+
+impl<T: RpcTransport> RpcTransport for FailoverTransport<T> {
+    /// Send the request to the endpoints in turn until one succeeds or fails with a
+    /// non-retryable error.
+    fn send(&self, request: &Value) -> Result<Value, RpcError> {
+        self.check_tips_if_expired();
+        let mut last_err = None;
+        for index in self.candidates() {
+            match self.transports[index].send(request) {
+                Err(err) if err.is_retryable() => {
+                    log::warn!("rpc endpoint {} failed: {}", index, err);
+                    self.state.lock().healthy[index] = false;
+                    last_err = Some(err);
+                }
+                result => {
+                    self.current.store(index, Ordering::Relaxed);
+                    return result;
+                }
+            }
+        }
+        Err(last_err.expect("at least one endpoint"))
+    }
+}
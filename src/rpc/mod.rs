@@ -1,12 +1,33 @@
+mod builder;
 mod ckb;
 pub mod ckb_indexer;
 pub mod ckb_light_client;
+mod failover;
+mod paginate;
+pub mod retry;
+mod transport;
+
+use std::collections::HashMap;
 
 use anyhow::anyhow;
-pub use ckb::CkbRpcClient;
-pub use ckb_indexer::IndexerRpcClient;
+pub use builder::{FromRpcClientBuilder, RpcClientBuilder};
+pub use ckb::{AsyncCkbRpcBatch, AsyncCkbRpcClient, CkbRpcBatch, CkbRpcClient};
+pub use ckb_indexer::{
+    AsyncIndexerRpcBatch, AsyncIndexerRpcClient, IndexerRpcBatch, IndexerRpcClient,
+};
 use ckb_jsonrpc_types::{JsonBytes, ResponseFormat};
-pub use ckb_light_client::LightClientRpcClient;
+pub use ckb_light_client::{
+    AsyncLightClientRpcBatch, AsyncLightClientRpcClient, LightClientRpcBatch, LightClientRpcClient,
+};
+pub use failover::FailoverTransport;
+pub use paginate::{PageSize, PaginatedIter, PaginatedStream, PaginationCursor};
+pub use retry::RetryPolicy;
+#[cfg(unix)]
+pub use transport::UnixTransport;
+pub use transport::{
+    AsyncHttpTransport, AsyncRpcTransport, HttpTransport, MemoryTransport, RpcTransport,
+    TcpTransport,
+};
 
 use thiserror::Error;
 
@@ -18,12 +39,139 @@ pub enum RpcError {
     Http(#[from] reqwest::Error),
     #[error("jsonrpc error: `{0}`")]
     Rpc(#[from] jsonrpc_core::Error),
+    #[error("invalid url: `{0}`")]
+    InvalidUrl(String),
+    #[error("io error: `{0}`")]
+    Io(#[from] std::io::Error),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
 #[macro_export]
 macro_rules! jsonrpc {
+    (
+        $(#[$struct_attr:meta])*
+        pub struct $struct_name:ident, batch $batch_name:ident {$(
+            $(#[$attr:meta])*
+            pub fn $method:ident(& $selff:ident $(, $arg_name:ident: $arg_ty:ty)*)
+                -> $return_ty:ty;
+        )*}
+    ) => (
+        $crate::jsonrpc!(
+            $(#[$struct_attr])*
+            pub struct $struct_name {$(
+                $(#[$attr])*
+                pub fn $method(&$selff $(, $arg_name: $arg_ty)*) -> $return_ty;
+            )*}
+        );
+
+        #[doc = concat!("JSON-RPC batch request builder of [`", stringify!($struct_name), "`], all requests are sent in one round-trip.")]
+        pub struct $batch_name<'a, R = (), T = $crate::rpc::HttpTransport> {
+            client: &'a $struct_name<T>,
+            requests: Vec<(String, serde_json::Value)>,
+            error: Option<$crate::rpc::RpcError>,
+            results: std::marker::PhantomData<R>,
+        }
+
+        impl<T: $crate::rpc::RpcTransport> $struct_name<T> {
+            /// Start a batch request, the results of `send` are typed in the order of the requests.
+            pub fn batch(&self) -> $batch_name<'_, (), T> {
+                $batch_name {
+                    client: self,
+                    requests: Vec::new(),
+                    error: None,
+                    results: std::marker::PhantomData,
+                }
+            }
+
+            /// Call the same method with a list of parameters in one batch request.
+            pub fn post_batch<PARAM, RET>(&self, method: &str, params: Vec<PARAM>) -> Result<Vec<Result<RET, $crate::rpc::RpcError>>, $crate::rpc::RpcError>
+            where
+                PARAM: serde::ser::Serialize,
+                RET: serde::de::DeserializeOwned,
+            {
+                let requests = params
+                    .into_iter()
+                    .map(|params| serde_json::to_value(params).map(|params| (method.to_owned(), params)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let outputs = self.send_batch(requests)?;
+                Ok(outputs
+                    .into_iter()
+                    .map(|output| output.and_then(|value| serde_json::from_value(value).map_err(Into::into)))
+                    .collect())
+            }
+
+            #[doc(hidden)]
+            pub fn send_batch(&self, requests: Vec<(String, serde_json::Value)>) -> Result<$crate::rpc::BatchOutputs, $crate::rpc::RpcError> {
+                if requests.is_empty() {
+                    return Ok(Vec::new());
+                }
+                let mut ids = Vec::with_capacity(requests.len());
+                let mut req_jsons = Vec::with_capacity(requests.len());
+                for (method, params) in requests {
+                    let id = self.id.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    let mut req_json = serde_json::Map::new();
+                    req_json.insert("id".to_owned(), serde_json::json!(id));
+                    req_json.insert("jsonrpc".to_owned(), serde_json::json!("2.0"));
+                    req_json.insert("method".to_owned(), serde_json::json!(method));
+                    req_json.insert("params".to_owned(), params);
+                    ids.push(id);
+                    req_jsons.push(serde_json::Value::Object(req_json));
+                }
+                let request = serde_json::Value::Array(req_jsons);
+
+                let response = $crate::rpc::retry::with_retry(self.retry_policy.as_ref(), || {
+                    self.transport.send(&request)
+                })?;
+                let response = serde_json::from_value::<jsonrpc_core::response::Response>(response)?;
+                $crate::rpc::batch_outputs(&ids, response)
+            }
+        }
+
+        impl<'a, R: $crate::rpc::BatchResults, T: $crate::rpc::RpcTransport> $batch_name<'a, R, T> {
+            fn push<NEXT>(mut self, method: &str, params: Result<serde_json::Value, $crate::rpc::RpcError>) -> $batch_name<'a, NEXT, T> {
+                match params {
+                    Ok(params) => self.requests.push((method.to_owned(), params)),
+                    Err(err) => {
+                        self.error.get_or_insert(err);
+                    }
+                }
+                $batch_name {
+                    client: self.client,
+                    requests: self.requests,
+                    error: self.error,
+                    results: std::marker::PhantomData,
+                }
+            }
+
+            /// The number of requests in this batch
+            pub fn len(&self) -> usize {
+                self.requests.len()
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.requests.is_empty()
+            }
+
+            /// Send all requests, a transport error fails the whole batch while an error
+            /// returned by the node only fails its own entry.
+            pub fn send(self) -> Result<R, $crate::rpc::RpcError> {
+                if let Some(err) = self.error {
+                    return Err(err);
+                }
+                let outputs = self.client.send_batch(self.requests)?;
+                Ok(R::from_outputs(&mut outputs.into_iter()))
+            }
+
+            $(
+                $(#[$attr])*
+                pub fn $method(self $(, $arg_name: $arg_ty)*) -> $batch_name<'a, (R, Result<$return_ty, $crate::rpc::RpcError>), T> {
+                    let params = serde_json::to_value(($($arg_name,)*)).map_err(Into::into);
+                    self.push(stringify!($method), params)
+                }
+            )*
+        }
+    );
     (
         $(#[$struct_attr:meta])*
         pub struct $struct_name:ident {$(
@@ -33,22 +181,62 @@ macro_rules! jsonrpc {
         )*}
     ) => (
         $(#[$struct_attr])*
-        pub struct $struct_name {
-            pub client: reqwest::blocking::Client,
-            pub url: reqwest::Url,
+        pub struct $struct_name<T = $crate::rpc::HttpTransport> {
+            /// Send the requests, see `RpcTransport`.
+            pub transport: T,
             pub id: std::sync::atomic::AtomicU64,
+            /// Retry the failed requests when set, see `RpcError::is_retryable`.
+            pub retry_policy: Option<$crate::rpc::RetryPolicy>,
         }
 
-        impl Clone for $struct_name {
+        impl<T: Clone> Clone for $struct_name<T> {
             fn clone(&self) -> Self {
-                Self::new(&self.url.to_string())
+                $struct_name {
+                    transport: self.transport.clone(),
+                    id: self.id.load(std::sync::atomic::Ordering::Relaxed).into(),
+                    retry_policy: self.retry_policy.clone(),
+                }
+            }
+        }
+
+        impl $crate::rpc::FromRpcClientBuilder for $struct_name {
+            fn from_builder(builder: &$crate::rpc::RpcClientBuilder) -> Result<Self, $crate::rpc::RpcError> {
+                let mut client = Self::new_with_client(builder.url(), builder.build_blocking_client()?)?;
+                client.retry_policy = builder.get_retry_policy().cloned();
+                Ok(client)
             }
         }
 
         impl $struct_name {
             pub fn new(uri: &str) -> Self {
-                let url = reqwest::Url::parse(uri).expect("ckb uri, e.g. \"http://127.0.0.1:8114\"");
-                $struct_name { url, id: 0.into(), client: reqwest::blocking::Client::new(), }
+                Self::try_new(uri).expect("ckb uri, e.g. \"http://127.0.0.1:8114\"")
+            }
+
+            /// Same as `new`, but return an error for an invalid uri instead of panic.
+            pub fn try_new(uri: &str) -> Result<Self, $crate::rpc::RpcError> {
+                Self::new_with_client(uri, reqwest::blocking::Client::new())
+            }
+
+            /// Create the rpc client with a configured http client, see also `RpcClientBuilder`.
+            pub fn new_with_client(uri: &str, client: reqwest::blocking::Client) -> Result<Self, $crate::rpc::RpcError> {
+                let transport = $crate::rpc::HttpTransport::new_with_client(uri, client)?;
+                Ok(Self::new_with_transport(transport))
+            }
+
+            /// The url of the http endpoint.
+            pub fn url(&self) -> &reqwest::Url {
+                self.transport.url()
+            }
+        }
+
+        impl<T: $crate::rpc::RpcTransport> $struct_name<T> {
+            /// Create the rpc client over another transport, e.g. `TcpTransport`.
+            pub fn new_with_transport(transport: T) -> Self {
+                $struct_name { transport, id: 0.into(), retry_policy: None }
+            }
+
+            pub fn set_retry_policy(&mut self, retry_policy: Option<$crate::rpc::RetryPolicy>) {
+                self.retry_policy = retry_policy;
             }
 
             pub fn post<PARAM, RET>(&self, method:&str, params: PARAM)->Result<RET, $crate::rpc::RpcError>
@@ -57,16 +245,18 @@ macro_rules! jsonrpc {
                 RET: serde::de::DeserializeOwned,
             {
                 let params = serde_json::to_value(params)?;
-                let id = self.id.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                let output = $crate::rpc::retry::with_retry(self.retry_policy.as_ref(), || {
+                    let id = self.id.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
-                let mut req_json = serde_json::Map::new();
-                req_json.insert("id".to_owned(), serde_json::json!(id));
-                req_json.insert("jsonrpc".to_owned(), serde_json::json!("2.0"));
-                req_json.insert("method".to_owned(), serde_json::json!(method));
-                req_json.insert("params".to_owned(), params);
+                    let mut req_json = serde_json::Map::new();
+                    req_json.insert("id".to_owned(), serde_json::json!(id));
+                    req_json.insert("jsonrpc".to_owned(), serde_json::json!("2.0"));
+                    req_json.insert("method".to_owned(), serde_json::json!(method));
+                    req_json.insert("params".to_owned(), params.clone());
 
-                let resp = self.client.post(self.url.clone()).json(&req_json).send()?;
-                let output = resp.json::<jsonrpc_core::response::Output>()?;
+                    let resp = self.transport.send(&serde_json::Value::Object(req_json))?;
+                    serde_json::from_value::<jsonrpc_core::response::Output>(resp).map_err(Into::into)
+                })?;
                 match output {
                     jsonrpc_core::response::Output::Success(success) => {
                         serde_json::from_value(success.result).map_err(Into::into)
@@ -81,38 +271,335 @@ macro_rules! jsonrpc {
             $(
                 $(#[$attr])*
                 pub fn $method(&$selff $(, $arg_name: $arg_ty)*) -> Result<$return_ty, $crate::rpc::RpcError> {
-                    let method = String::from(stringify!($method));
                     let params = $crate::serialize_parameters!($($arg_name,)*);
-                    let id = $selff.id.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    $selff.post(stringify!($method), params)
+                }
+            )*
+        }
+    )
+}
+
+/// Same as `jsonrpc!`, but the generated client is built on an `AsyncRpcTransport`, the async
+/// `reqwest::Client` by default, and every method returns a future, so it can be used inside
+/// a tokio runtime directly.
+#[macro_export]
+macro_rules! jsonrpc_async {
+    (
+        $(#[$struct_attr:meta])*
+        pub struct $struct_name:ident, batch $batch_name:ident {$(
+            $(#[$attr:meta])*
+            pub fn $method:ident(& $selff:ident $(, $arg_name:ident: $arg_ty:ty)*)
+                -> $return_ty:ty;
+        )*}
+    ) => (
+        $crate::jsonrpc_async!(
+            $(#[$struct_attr])*
+            pub struct $struct_name {$(
+                $(#[$attr])*
+                pub fn $method(&$selff $(, $arg_name: $arg_ty)*) -> $return_ty;
+            )*}
+        );
 
+        #[doc = concat!("JSON-RPC batch request builder of [`", stringify!($struct_name), "`], all requests are sent in one round-trip.")]
+        pub struct $batch_name<'a, R = (), T = $crate::rpc::AsyncHttpTransport> {
+            client: &'a $struct_name<T>,
+            requests: Vec<(String, serde_json::Value)>,
+            error: Option<$crate::rpc::RpcError>,
+            results: std::marker::PhantomData<R>,
+        }
+
+        impl<T: $crate::rpc::AsyncRpcTransport> $struct_name<T> {
+            /// Start a batch request, the results of `send` are typed in the order of the requests.
+            pub fn batch(&self) -> $batch_name<'_, (), T> {
+                $batch_name {
+                    client: self,
+                    requests: Vec::new(),
+                    error: None,
+                    results: std::marker::PhantomData,
+                }
+            }
+
+            /// Call the same method with a list of parameters in one batch request.
+            pub async fn post_batch<PARAM, RET>(&self, method: &str, params: Vec<PARAM>) -> Result<Vec<Result<RET, $crate::rpc::RpcError>>, $crate::rpc::RpcError>
+            where
+                PARAM: serde::ser::Serialize,
+                RET: serde::de::DeserializeOwned,
+            {
+                let requests = params
+                    .into_iter()
+                    .map(|params| serde_json::to_value(params).map(|params| (method.to_owned(), params)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let outputs = self.send_batch(requests).await?;
+                Ok(outputs
+                    .into_iter()
+                    .map(|output| output.and_then(|value| serde_json::from_value(value).map_err(Into::into)))
+                    .collect())
+            }
+
+            #[doc(hidden)]
+            pub async fn send_batch(&self, requests: Vec<(String, serde_json::Value)>) -> Result<$crate::rpc::BatchOutputs, $crate::rpc::RpcError> {
+                if requests.is_empty() {
+                    return Ok(Vec::new());
+                }
+                let mut ids = Vec::with_capacity(requests.len());
+                let mut req_jsons = Vec::with_capacity(requests.len());
+                for (method, params) in requests {
+                    let id = self.id.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                     let mut req_json = serde_json::Map::new();
                     req_json.insert("id".to_owned(), serde_json::json!(id));
                     req_json.insert("jsonrpc".to_owned(), serde_json::json!("2.0"));
                     req_json.insert("method".to_owned(), serde_json::json!(method));
                     req_json.insert("params".to_owned(), params);
+                    ids.push(id);
+                    req_jsons.push(serde_json::Value::Object(req_json));
+                }
+                let request = serde_json::Value::Array(req_jsons);
 
-                    let resp = $selff.client.post($selff.url.clone()).json(&req_json).send()?;
-                    let output = resp.json::<jsonrpc_core::response::Output>()?;
-                    match output {
-                        jsonrpc_core::response::Output::Success(success) => {
-                            serde_json::from_value(success.result).map_err(Into::into)
-                        },
-                        jsonrpc_core::response::Output::Failure(failure) => {
-                            Err(failure.error.into())
-                        }
+                let (this, request) = (self, &request);
+                let response = $crate::rpc::retry::with_retry_async(self.retry_policy.as_ref(), move || {
+                    this.transport.send(request)
+                }).await?;
+                let response = serde_json::from_value::<jsonrpc_core::response::Response>(response)?;
+                $crate::rpc::batch_outputs(&ids, response)
+            }
+        }
+
+        impl<'a, R: $crate::rpc::BatchResults, T: $crate::rpc::AsyncRpcTransport> $batch_name<'a, R, T> {
+            fn push<NEXT>(mut self, method: &str, params: Result<serde_json::Value, $crate::rpc::RpcError>) -> $batch_name<'a, NEXT, T> {
+                match params {
+                    Ok(params) => self.requests.push((method.to_owned(), params)),
+                    Err(err) => {
+                        self.error.get_or_insert(err);
+                    }
+                }
+                $batch_name {
+                    client: self.client,
+                    requests: self.requests,
+                    error: self.error,
+                    results: std::marker::PhantomData,
+                }
+            }
+
+            /// The number of requests in this batch
+            pub fn len(&self) -> usize {
+                self.requests.len()
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.requests.is_empty()
+            }
+
+            /// Send all requests, a transport error fails the whole batch while an error
+            /// returned by the node only fails its own entry.
+            pub async fn send(self) -> Result<R, $crate::rpc::RpcError> {
+                if let Some(err) = self.error {
+                    return Err(err);
+                }
+                let outputs = self.client.send_batch(self.requests).await?;
+                Ok(R::from_outputs(&mut outputs.into_iter()))
+            }
+
+            $(
+                $(#[$attr])*
+                pub fn $method(self $(, $arg_name: $arg_ty)*) -> $batch_name<'a, (R, Result<$return_ty, $crate::rpc::RpcError>), T> {
+                    let params = serde_json::to_value(($($arg_name,)*)).map_err(Into::into);
+                    self.push(stringify!($method), params)
+                }
+            )*
+        }
+    );
+    (
+        $(#[$struct_attr:meta])*
+        pub struct $struct_name:ident {$(
+            $(#[$attr:meta])*
+            pub fn $method:ident(& $selff:ident $(, $arg_name:ident: $arg_ty:ty)*)
+                -> $return_ty:ty;
+        )*}
+    ) => (
+        $(#[$struct_attr])*
+        pub struct $struct_name<T = $crate::rpc::AsyncHttpTransport> {
+            /// Send the requests, see `AsyncRpcTransport`.
+            pub transport: T,
+            pub id: std::sync::atomic::AtomicU64,
+            /// Retry the failed requests when set, see `RpcError::is_retryable`.
+            pub retry_policy: Option<$crate::rpc::RetryPolicy>,
+        }
+
+        impl<T: Clone> Clone for $struct_name<T> {
+            fn clone(&self) -> Self {
+                $struct_name {
+                    transport: self.transport.clone(),
+                    id: self.id.load(std::sync::atomic::Ordering::Relaxed).into(),
+                    retry_policy: self.retry_policy.clone(),
+                }
+            }
+        }
+
+        impl $crate::rpc::FromRpcClientBuilder for $struct_name {
+            fn from_builder(builder: &$crate::rpc::RpcClientBuilder) -> Result<Self, $crate::rpc::RpcError> {
+                let mut client = Self::new_with_client(builder.url(), builder.build_async_client()?)?;
+                client.retry_policy = builder.get_retry_policy().cloned();
+                Ok(client)
+            }
+        }
+
+        impl $struct_name {
+            pub fn new(uri: &str) -> Self {
+                Self::try_new(uri).expect("ckb uri, e.g. \"http://127.0.0.1:8114\"")
+            }
+
+            /// Same as `new`, but return an error for an invalid uri instead of panic.
+            pub fn try_new(uri: &str) -> Result<Self, $crate::rpc::RpcError> {
+                Self::new_with_client(uri, reqwest::Client::new())
+            }
+
+            /// Create the rpc client with a configured http client, see also `RpcClientBuilder`.
+            pub fn new_with_client(uri: &str, client: reqwest::Client) -> Result<Self, $crate::rpc::RpcError> {
+                let transport = $crate::rpc::AsyncHttpTransport::new_with_client(uri, client)?;
+                Ok(Self::new_with_transport(transport))
+            }
+
+            /// The url of the http endpoint.
+            pub fn url(&self) -> &reqwest::Url {
+                self.transport.url()
+            }
+        }
+
+        impl<T: $crate::rpc::AsyncRpcTransport> $struct_name<T> {
+            /// Create the rpc client over another transport, e.g. `MemoryTransport`.
+            pub fn new_with_transport(transport: T) -> Self {
+                $struct_name { transport, id: 0.into(), retry_policy: None }
+            }
+
+            pub fn set_retry_policy(&mut self, retry_policy: Option<$crate::rpc::RetryPolicy>) {
+                self.retry_policy = retry_policy;
+            }
+
+            pub async fn post<PARAM, RET>(&self, method:&str, params: PARAM)->Result<RET, $crate::rpc::RpcError>
+            where
+                PARAM:serde::ser::Serialize,
+                RET: serde::de::DeserializeOwned,
+            {
+                let params = &serde_json::to_value(params)?;
+                let this = self;
+                let output = $crate::rpc::retry::with_retry_async(self.retry_policy.as_ref(), move || async move {
+                    let id = this.id.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+                    let mut req_json = serde_json::Map::new();
+                    req_json.insert("id".to_owned(), serde_json::json!(id));
+                    req_json.insert("jsonrpc".to_owned(), serde_json::json!("2.0"));
+                    req_json.insert("method".to_owned(), serde_json::json!(method));
+                    req_json.insert("params".to_owned(), params.clone());
+
+                    let resp = this.transport.send(&serde_json::Value::Object(req_json)).await?;
+                    serde_json::from_value::<jsonrpc_core::response::Output>(resp).map_err(Into::into)
+                }).await?;
+                match output {
+                    jsonrpc_core::response::Output::Success(success) => {
+                        serde_json::from_value(success.result).map_err(Into::into)
+                    },
+                    jsonrpc_core::response::Output::Failure(failure) => {
+                        Err(failure.error.into())
                     }
                 }
+            }
+
+            $(
+                $(#[$attr])*
+                pub async fn $method(&$selff $(, $arg_name: $arg_ty)*) -> Result<$return_ty, $crate::rpc::RpcError> {
+                    let params = $crate::serialize_parameters!($($arg_name,)*);
+                    $selff.post(stringify!($method), params).await
+                }
             )*
         }
     )
 }
 
+/// The raw results of a batch request, in the order of the requests.
+pub type BatchOutputs = Vec<Result<serde_json::Value, RpcError>>;
+
+/// Typed results of a batch request, `()` for no request and `(R, Result<T, RpcError>)`
+/// for the requests of `R` followed by a request returning `T`.
+pub trait BatchResults: Sized {
+    fn from_outputs(outputs: &mut std::vec::IntoIter<Result<serde_json::Value, RpcError>>) -> Self;
+}
+
+impl BatchResults for () {
+    fn from_outputs(_outputs: &mut std::vec::IntoIter<Result<serde_json::Value, RpcError>>) {}
+}
+
+impl<R: BatchResults, T: serde::de::DeserializeOwned> BatchResults for (R, Result<T, RpcError>) {
+    fn from_outputs(outputs: &mut std::vec::IntoIter<Result<serde_json::Value, RpcError>>) -> Self {
+        let results = R::from_outputs(outputs);
+        let result = outputs
+            .next()
+            .unwrap_or_else(|| Err(RpcError::Other(anyhow!("missing batch response"))))
+            .and_then(|value| serde_json::from_value(value).map_err(Into::into));
+        (results, result)
+    }
+}
+
+/// Match the outputs of a batch response to the request ids, the outputs may come in any order.
+#[doc(hidden)]
+pub fn batch_outputs(
+    ids: &[u64],
+    response: jsonrpc_core::response::Response,
+) -> Result<BatchOutputs, RpcError> {
+    use jsonrpc_core::response::{Output, Response};
+
+    let outputs = match response {
+        Response::Batch(outputs) => outputs,
+        // the whole batch is rejected, e.g. a parse error
+        Response::Single(Output::Failure(failure)) => return Err(failure.error.into()),
+        Response::Single(output) => vec![output],
+    };
+    let mut outputs: HashMap<u64, Output> = outputs
+        .into_iter()
+        .filter_map(|output| match output.id() {
+            jsonrpc_core::Id::Num(id) => Some((*id, output)),
+            _ => None,
+        })
+        .collect();
+    Ok(ids
+        .iter()
+        .map(|id| match outputs.remove(id) {
+            Some(Output::Success(success)) => Ok(success.result),
+            Some(Output::Failure(failure)) => Err(failure.error.into()),
+            None => Err(RpcError::Other(anyhow!(
+                "missing response of batch request `{}`",
+                id
+            ))),
+        })
+        .collect())
+}
+
 #[macro_export]
 macro_rules! serialize_parameters {
     () => ( serde_json::Value::Null );
     ($($arg_name:ident,)+) => ( serde_json::to_value(($($arg_name,)+))?)
 }
 
+impl RpcError {
+    /// Whether the request may succeed when sent again: transport errors, timeouts and
+    /// http server errors. The JSON-RPC application errors are never retried.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            RpcError::Http(err) => {
+                err.is_timeout()
+                    || err.is_connect()
+                    || err.is_request()
+                    || err
+                        .status()
+                        .map(|status| status.is_server_error())
+                        .unwrap_or(false)
+            }
+            // the stream transports reconnect on the next request
+            RpcError::Io(_) => true,
+            _ => false,
+        }
+    }
+}
+
 pub trait ResponseFormatGetter<V> {
     fn get_value(self) -> Result<V, crate::rpc::RpcError>;
     fn get_json_bytes(self) -> Result<JsonBytes, crate::rpc::RpcError>;
@@ -0,0 +1,264 @@
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use ckb_jsonrpc_types::{JsonBytes, Uint32};
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::Stream;
+use serde::{Deserialize, Serialize};
+
+use super::{ckb_indexer::Pagination, RpcError};
+
+/// The page size of the paginating iterators, starts from `initial` and is multiplied by
+/// `growth` after every page until `max`.
+///
+/// A `u32` converts to a fixed page size.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PageSize {
+    pub initial: u32,
+    pub max: u32,
+    pub growth: u32,
+}
+
+impl PageSize {
+    pub fn fixed(size: u32) -> PageSize {
+        PageSize {
+            initial: size,
+            max: size,
+            growth: 1,
+        }
+    }
+
+    /// Double the page size after every page until `max`.
+    pub fn adaptive(initial: u32, max: u32) -> PageSize {
+        PageSize {
+            initial,
+            max,
+            growth: 2,
+        }
+    }
+
+    fn next(&self, current: u32) -> u32 {
+        if current >= self.max {
+            current
+        } else {
+            current.saturating_mul(self.growth).min(self.max)
+        }
+    }
+}
+
+impl Default for PageSize {
+    fn default() -> PageSize {
+        PageSize::adaptive(16, 4096)
+    }
+}
+
+impl From<u32> for PageSize {
+    fn from(size: u32) -> PageSize {
+        PageSize::fixed(size)
+    }
+}
+
+/// The position of a paginating iterator: the items after the page cursor `after`, with the
+/// first `skip` of them already consumed. It can be saved to resume a scan later.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PaginationCursor {
+    pub after: Option<JsonBytes>,
+    pub skip: u32,
+}
+
+// The paging state shared by the iterator and the stream.
+struct Pages<T> {
+    page_size: PageSize,
+    limit: u32,
+    cursor: PaginationCursor,
+    items: VecDeque<T>,
+    // the cursor at the end of the buffered page
+    next_after: Option<JsonBytes>,
+    done: bool,
+}
+
+impl<T> Pages<T> {
+    fn new(page_size: PageSize) -> Pages<T> {
+        Pages {
+            limit: page_size.initial.max(1),
+            page_size,
+            cursor: PaginationCursor::default(),
+            items: VecDeque::new(),
+            next_after: None,
+            done: false,
+        }
+    }
+
+    fn resume_from(&mut self, cursor: PaginationCursor) {
+        self.cursor = cursor;
+        self.items.clear();
+        self.next_after = None;
+        self.done = false;
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        let item = self.items.pop_front()?;
+        self.cursor.skip += 1;
+        Some(item)
+    }
+
+    // The cursor and limit of the next page, `None` when all pages are fetched.
+    fn next_request(&mut self) -> Option<(Option<JsonBytes>, Uint32)> {
+        if self.done {
+            return None;
+        }
+        if let Some(after) = self.next_after.take() {
+            self.cursor = PaginationCursor {
+                after: Some(after),
+                skip: 0,
+            };
+        }
+        Some((self.cursor.after.clone(), self.limit.into()))
+    }
+
+    fn on_page(&mut self, page: Result<Pagination<T>, RpcError>) -> Result<(), RpcError> {
+        // stop on error, the scan can be resumed from the cursor
+        let page = match page {
+            Ok(page) => page,
+            Err(err) => {
+                self.done = true;
+                return Err(err);
+            }
+        };
+        let len = page.objects.len() as u32;
+        if len == 0 {
+            self.done = true;
+        } else if len <= self.cursor.skip {
+            // the whole page is consumed before resuming
+            self.cursor = PaginationCursor {
+                after: Some(page.last_cursor),
+                skip: self.cursor.skip - len,
+            };
+        } else {
+            self.items = page
+                .objects
+                .into_iter()
+                .skip(self.cursor.skip as usize)
+                .collect();
+            self.next_after = Some(page.last_cursor);
+        }
+        self.limit = self.page_size.next(self.limit);
+        Ok(())
+    }
+}
+
+type FetchPage<'a, T> =
+    dyn FnMut(Option<JsonBytes>, Uint32) -> Result<Pagination<T>, RpcError> + 'a;
+
+/// Iterate the items of all pages, the next page is fetched when the current one is consumed.
+///
+/// The iteration stops after an error, use `cursor` and `resume_from` to continue.
+pub struct PaginatedIter<'a, T> {
+    pages: Pages<T>,
+    fetch: Box<FetchPage<'a, T>>,
+}
+
+impl<'a, T> PaginatedIter<'a, T> {
+    /// `fetch` gets the page after the cursor with the limit, e.g. `IndexerRpcClient::get_cells`.
+    pub fn new<P, F>(page_size: P, fetch: F) -> PaginatedIter<'a, T>
+    where
+        P: Into<PageSize>,
+        F: FnMut(Option<JsonBytes>, Uint32) -> Result<Pagination<T>, RpcError> + 'a,
+    {
+        PaginatedIter {
+            pages: Pages::new(page_size.into()),
+            fetch: Box::new(fetch),
+        }
+    }
+
+    /// Continue from a cursor saved by `cursor`.
+    pub fn resume_from(mut self, cursor: PaginationCursor) -> Self {
+        self.pages.resume_from(cursor);
+        self
+    }
+
+    /// The position after the last returned item.
+    pub fn cursor(&self) -> PaginationCursor {
+        self.pages.cursor.clone()
+    }
+}
+
+impl<'a, T> Iterator for PaginatedIter<'a, T> {
+    type Item = Result<T, RpcError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.pages.pop() {
+                return Some(Ok(item));
+            }
+            let (after, limit) = self.pages.next_request()?;
+            if let Err(err) = self.pages.on_page((self.fetch)(after, limit)) {
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+type FetchPageAsync<'a, T> = dyn FnMut(Option<JsonBytes>, Uint32) -> BoxFuture<'a, Result<Pagination<T>, RpcError>>
+    + Send
+    + 'a;
+
+/// Same as [`PaginatedIter`], but fetch the pages with the async rpc clients.
+pub struct PaginatedStream<'a, T> {
+    pages: Pages<T>,
+    fetch: Box<FetchPageAsync<'a, T>>,
+    pending: Option<BoxFuture<'a, Result<Pagination<T>, RpcError>>>,
+}
+
+impl<'a, T> PaginatedStream<'a, T> {
+    pub fn new<P, F>(page_size: P, fetch: F) -> PaginatedStream<'a, T>
+    where
+        P: Into<PageSize>,
+        F: FnMut(Option<JsonBytes>, Uint32) -> BoxFuture<'a, Result<Pagination<T>, RpcError>>
+            + Send
+            + 'a,
+    {
+        PaginatedStream {
+            pages: Pages::new(page_size.into()),
+            fetch: Box::new(fetch),
+            pending: None,
+        }
+    }
+
+    /// Continue from a cursor saved by `cursor`.
+    pub fn resume_from(mut self, cursor: PaginationCursor) -> Self {
+        self.pages.resume_from(cursor);
+        self.pending = None;
+        self
+    }
+
+    /// The position after the last returned item.
+    pub fn cursor(&self) -> PaginationCursor {
+        self.pages.cursor.clone()
+    }
+}
+
+impl<'a, T: Unpin> Stream for PaginatedStream<'a, T> {
+    type Item = Result<T, RpcError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(pending) = this.pending.as_mut() {
+                let page = futures::ready!(pending.poll_unpin(cx));
+                this.pending = None;
+                if let Err(err) = this.pages.on_page(page) {
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+            if let Some(item) = this.pages.pop() {
+                return Poll::Ready(Some(Ok(item)));
+            }
+            match this.pages.next_request() {
+                Some((after, limit)) => this.pending = Some((this.fetch)(after, limit)),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
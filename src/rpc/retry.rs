@@ -0,0 +1,89 @@
+use std::future::Future;
+use std::time::Duration;
+
+use super::RpcError;
+
+/// Retry the failed requests with exponential backoff, only the errors of
+/// [`RpcError::is_retryable`] are retried.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The max retries after the first attempt
+    pub max_retries: u32,
+    /// The backoff before the first retry, doubled for every next retry
+    pub initial_backoff: Duration,
+    /// The upper bound of the backoff
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            ..Default::default()
+        }
+    }
+
+    /// The backoff before the retry of `attempt` (starts from 0)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .checked_mul(1u32.checked_shl(attempt).unwrap_or(u32::MAX))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+
+    fn should_retry(&self, attempt: u32, err: &RpcError) -> bool {
+        attempt < self.max_retries && err.is_retryable()
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Run the request until it succeeds or the error can not be retried.
+#[doc(hidden)]
+pub fn with_retry<T, F>(policy: Option<&RetryPolicy>, mut request: F) -> Result<T, RpcError>
+where
+    F: FnMut() -> Result<T, RpcError>,
+{
+    let mut attempt = 0;
+    loop {
+        match (request(), policy) {
+            (Err(err), Some(policy)) if policy.should_retry(attempt, &err) => {
+                log::debug!("retry rpc request after error: {}", err);
+                std::thread::sleep(policy.backoff(attempt));
+                attempt += 1;
+            }
+            (result, _) => return result,
+        }
+    }
+}
+
+/// Same as [`with_retry`] but wait with `tokio::time::sleep`.
+#[doc(hidden)]
+pub async fn with_retry_async<T, F, Fut>(
+    policy: Option<&RetryPolicy>,
+    mut request: F,
+) -> Result<T, RpcError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, RpcError>>,
+{
+    let mut attempt = 0;
+    loop {
+        match (request().await, policy) {
+            (Err(err), Some(policy)) if policy.should_retry(attempt, &err) => {
+                log::debug!("retry rpc request after error: {}", err);
+                tokio::time::sleep(policy.backoff(attempt)).await;
+                attempt += 1;
+            }
+            (result, _) => return result,
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::BytesMut;
+use futures::future::{BoxFuture, FutureExt};
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::RpcError;
+use crate::pubsub::stream_codec::StreamCodec;
+
+/// The transport of the blocking rpc clients generated by `jsonrpc!`.
+///
+/// The request is a serialized JSON-RPC call or batch, the response is returned as it is,
+/// the clients match it to the requests.
+pub trait RpcTransport: Send + Sync {
+    fn send(&self, request: &Value) -> Result<Value, RpcError>;
+}
+
+impl<T: RpcTransport + ?Sized> RpcTransport for &T {
+    fn send(&self, request: &Value) -> Result<Value, RpcError> {
+        (**self).send(request)
+    }
+}
+
+/// The transport of the async rpc clients generated by `jsonrpc_async!`.
+pub trait AsyncRpcTransport: Send + Sync {
+    fn send<'a>(&'a self, request: &'a Value) -> BoxFuture<'a, Result<Value, RpcError>>;
+}
+
+/// JSON-RPC over http, the default transport of the rpc clients.
+#[derive(Clone, Debug)]
+pub struct HttpTransport {
+    client: reqwest::blocking::Client,
+    url: reqwest::Url,
+}
+
+impl HttpTransport {
+    pub fn new(uri: &str) -> Result<HttpTransport, RpcError> {
+        Self::new_with_client(uri, reqwest::blocking::Client::new())
+    }
+
+    pub fn new_with_client(
+        uri: &str,
+        client: reqwest::blocking::Client,
+    ) -> Result<HttpTransport, RpcError> {
+        Ok(HttpTransport {
+            client,
+            url: parse_url(uri)?,
+        })
+    }
+
+    pub fn url(&self) -> &reqwest::Url {
+        &self.url
+    }
+
+    pub fn client(&self) -> &reqwest::blocking::Client {
+        &self.client
+    }
+}
+
+impl RpcTransport for HttpTransport {
+    fn send(&self, request: &Value) -> Result<Value, RpcError> {
+        let resp = self.client.post(self.url.clone()).json(request).send()?;
+        // server errors are reported as http errors, so they can be retried
+        let resp = if resp.status().is_server_error() {
+            resp.error_for_status()?
+        } else {
+            resp
+        };
+        Ok(resp.json()?)
+    }
+}
+
+/// JSON-RPC over http with the async `reqwest::Client`, the default transport of the async
+/// rpc clients.
+#[derive(Clone, Debug)]
+pub struct AsyncHttpTransport {
+    client: reqwest::Client,
+    url: reqwest::Url,
+}
+
+impl AsyncHttpTransport {
+    pub fn new(uri: &str) -> Result<AsyncHttpTransport, RpcError> {
+        Self::new_with_client(uri, reqwest::Client::new())
+    }
+
+    pub fn new_with_client(
+        uri: &str,
+        client: reqwest::Client,
+    ) -> Result<AsyncHttpTransport, RpcError> {
+        Ok(AsyncHttpTransport {
+            client,
+            url: parse_url(uri)?,
+        })
+    }
+
+    pub fn url(&self) -> &reqwest::Url {
+        &self.url
+    }
+
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+}
+
+impl AsyncRpcTransport for AsyncHttpTransport {
+    fn send<'a>(&'a self, request: &'a Value) -> BoxFuture<'a, Result<Value, RpcError>> {
+        async move {
+            let resp = self
+                .client
+                .post(self.url.clone())
+                .json(request)
+                .send()
+                .await?;
+            let resp = if resp.status().is_server_error() {
+                resp.error_for_status()?
+            } else {
+                resp
+            };
+            Ok(resp.json().await?)
+        }
+        .boxed()
+    }
+}
+
+fn parse_url(uri: &str) -> Result<reqwest::Url, RpcError> {
+    reqwest::Url::parse(uri).map_err(|err| RpcError::InvalidUrl(format!("{}: {}", uri, err)))
+}
+
+// A connected stream with the bytes received but not decoded yet.
+struct StreamConnection<S> {
+    stream: S,
+    buf: BytesMut,
+}
+
+impl<S: Read + Write> StreamConnection<S> {
+    fn new(stream: S) -> Self {
+        StreamConnection {
+            stream,
+            buf: BytesMut::new(),
+        }
+    }
+
+    fn request(&mut self, request: &Value) -> Result<Value, RpcError> {
+        let mut codec = StreamCodec::stream_incoming();
+        let mut out = BytesMut::new();
+        codec.encode(serde_json::to_string(request)?, &mut out)?;
+        self.stream.write_all(&out)?;
+        self.stream.flush()?;
+
+        let mut chunk = [0u8; 4096];
+        loop {
+            if let Some(frame) = codec.decode(&mut self.buf)? {
+                return Ok(serde_json::from_slice(&frame)?);
+            }
+            let n = self.stream.read(&mut chunk)?;
+            if n == 0 {
+                return Err(
+                    io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed").into(),
+                );
+            }
+            self.buf.extend_from_slice(&chunk[..n]);
+        }
+    }
+}
+
+// Send the request over the cached connection, which is dropped on any error so the next
+// request reconnects instead of reading the rest of a broken response.
+fn send_over<S, F>(
+    connection: &Mutex<Option<StreamConnection<S>>>,
+    connect: F,
+    request: &Value,
+) -> Result<Value, RpcError>
+where
+    S: Read + Write,
+    F: FnOnce() -> io::Result<S>,
+{
+    let mut connection = connection.lock();
+    if connection.is_none() {
+        *connection = Some(StreamConnection::new(connect()?));
+    }
+    let result = connection
+        .as_mut()
+        .expect("connected stream")
+        .request(request);
+    if result.is_err() {
+        *connection = None;
+    }
+    result
+}
+
+/// Newline delimited JSON-RPC over a raw tcp connection, e.g. the `tcp_listen_address` of
+/// the ckb node.
+///
+/// The connection is established by the first request and reused by the following ones.
+pub struct TcpTransport {
+    addr: String,
+    timeout: Option<Duration>,
+    connection: Mutex<Option<StreamConnection<TcpStream>>>,
+}
+
+impl TcpTransport {
+    /// `addr` is the socket address, e.g. `127.0.0.1:18114`.
+    pub fn new(addr: &str) -> TcpTransport {
+        TcpTransport {
+            addr: addr.to_string(),
+            timeout: None,
+            connection: Mutex::new(None),
+        }
+    }
+
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    /// The read and write timeout of the connection, no timeout by default.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+}
+
+impl Clone for TcpTransport {
+    fn clone(&self) -> Self {
+        // the connection is not shared, the cloned transport connects on its own
+        TcpTransport {
+            addr: self.addr.clone(),
+            timeout: self.timeout,
+            connection: Mutex::new(None),
+        }
+    }
+}
+
+impl RpcTransport for TcpTransport {
+    fn send(&self, request: &Value) -> Result<Value, RpcError> {
+        let connect = || {
+            let stream = TcpStream::connect(&self.addr)?;
+            stream.set_read_timeout(self.timeout)?;
+            stream.set_write_timeout(self.timeout)?;
+            Ok(stream)
+        };
+        send_over(&self.connection, connect, request)
+    }
+}
+
+/// Newline delimited JSON-RPC over a unix domain socket.
+///
+/// The connection is established by the first request and reused by the following ones.
+#[cfg(unix)]
+pub struct UnixTransport {
+    path: PathBuf,
+    timeout: Option<Duration>,
+    connection: Mutex<Option<StreamConnection<UnixStream>>>,
+}
+
+#[cfg(unix)]
+impl UnixTransport {
+    pub fn new<P: AsRef<Path>>(path: P) -> UnixTransport {
+        UnixTransport {
+            path: path.as_ref().to_path_buf(),
+            timeout: None,
+            connection: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The read and write timeout of the connection, no timeout by default.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+}
+
+#[cfg(unix)]
+impl Clone for UnixTransport {
+    fn clone(&self) -> Self {
+        UnixTransport {
+            path: self.path.clone(),
+            timeout: self.timeout,
+            connection: Mutex::new(None),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl RpcTransport for UnixTransport {
+    fn send(&self, request: &Value) -> Result<Value, RpcError> {
+        let connect = || {
+            let stream = UnixStream::connect(&self.path)?;
+            stream.set_read_timeout(self.timeout)?;
+            stream.set_write_timeout(self.timeout)?;
+            Ok(stream)
+        };
+        send_over(&self.connection, connect, request)
+    }
+}
+
+type Handler = dyn Fn(&str, Value) -> Result<Value, jsonrpc_core::Error> + Send + Sync;
+
+/// Serve the requests in process by a handler of the method name and params, mostly for tests.
+///
+/// ```
+/// # use ckb_sdk::rpc::{CkbRpcClient, MemoryTransport};
+/// let transport = MemoryTransport::new(|method, _params| match method {
+///     "get_tip_block_number" => Ok(serde_json::json!("0x64")),
+///     _ => Err(jsonrpc_core::Error::method_not_found()),
+/// });
+/// let client = CkbRpcClient::new_with_transport(transport);
+/// assert_eq!(client.get_tip_block_number().unwrap().value(), 100);
+/// ```
+#[derive(Clone)]
+pub struct MemoryTransport {
+    handler: Arc<Handler>,
+}
+
+impl MemoryTransport {
+    pub fn new<F>(handler: F) -> MemoryTransport
+    where
+        F: Fn(&str, Value) -> Result<Value, jsonrpc_core::Error> + Send + Sync + 'static,
+    {
+        MemoryTransport {
+            handler: Arc::new(handler),
+        }
+    }
+
+    fn handle_call(&self, call: &Value) -> Value {
+        let id = call.get("id").cloned().unwrap_or(Value::Null);
+        let method = call.get("method").and_then(Value::as_str).unwrap_or("");
+        let params = call.get("params").cloned().unwrap_or(Value::Null);
+        match (self.handler)(method, params) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+        }
+    }
+}
+
+impl RpcTransport for MemoryTransport {
+    fn send(&self, request: &Value) -> Result<Value, RpcError> {
+        Ok(match request {
+            Value::Array(calls) => calls.iter().map(|call| self.handle_call(call)).collect(),
+            call => self.handle_call(call),
+        })
+    }
+}
+
+impl AsyncRpcTransport for MemoryTransport {
+    fn send<'a>(&'a self, request: &'a Value) -> BoxFuture<'a, Result<Value, RpcError>> {
+        futures::future::ready(RpcTransport::send(self, request)).boxed()
+    }
+}
@@ -537,7 +537,7 @@ impl CellCollector for LiveCellsContext {
                     self.used_inputs.insert(idx);
                 }
             }
-            if total_capacity >= query.min_total_capacity {
+            if query.is_satisfied(total_capacity, cells.len()) {
                 break;
             }
         }
@@ -553,10 +553,28 @@ impl CellCollector for LiveCellsContext {
     }
     fn apply_tx(
         &mut self,
-        _tx: Transaction,
+        tx: Transaction,
         _tip_block_number: u64,
     ) -> Result<(), CellCollectorError> {
-        unimplemented!()
+        let tx = tx.into_view();
+        for out_point in tx.input_pts_iter() {
+            if let Some(idx) = self
+                .inputs
+                .iter()
+                .position(|item| item.input.previous_output() == out_point)
+            {
+                self.used_inputs.insert(idx);
+            }
+        }
+        for (idx, (output, data)) in tx.outputs_with_data_iter().enumerate() {
+            self.inputs.push(MockInput {
+                input: CellInput::new(OutPoint::new(tx.hash(), idx as u32), 0),
+                output,
+                data,
+                header: None,
+            });
+        }
+        Ok(())
     }
     fn reset(&mut self) {
         self.used_inputs.clear();
@@ -0,0 +1,241 @@
+use ckb_chain_spec::consensus::ConsensusBuilder;
+use ckb_jsonrpc_types as json_types;
+use ckb_types::{
+    bytes::Bytes,
+    core::{EpochNumberWithFraction, HeaderBuilder, TransactionBuilder},
+    h256,
+    packed::{CellInput, CellOutput, OutPoint},
+    prelude::*,
+};
+use httpmock::prelude::*;
+use serde_json::json;
+
+use crate::{
+    constants::ONE_CKB,
+    rpc::ckb_indexer::{Cell, Pagination, Tip},
+    test_util::MockRpcResult,
+    tests::{build_sighash_script, ACCOUNT1_ARG},
+    traits::{
+        AsyncCellCollector, AsyncDefaultCellCollector, AsyncDefaultTransactionDependencyProvider,
+        AsyncTransactionDependencyProvider, CellQueryOptions,
+    },
+};
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+fn indexer_cell(out_point: OutPoint, capacity: u64) -> Cell {
+    Cell {
+        output: CellOutput::new_builder()
+            .capacity(capacity.pack())
+            .lock(build_sighash_script(ACCOUNT1_ARG))
+            .build()
+            .into(),
+        output_data: Some(json_types::JsonBytes::default()),
+        out_point: out_point.into(),
+        block_number: 10.into(),
+        // not a cellbase cell
+        tx_index: 1.into(),
+    }
+}
+
+#[test]
+fn test_async_cell_collector() {
+    let server = MockServer::start();
+    // no cellbase cell is mature at the tip epoch
+    server.mock(|when, then| {
+        when.method(POST).path("/").body_contains("get_consensus");
+        let consensus: json_types::Consensus = ConsensusBuilder::default()
+            .cellbase_maturity(EpochNumberWithFraction::new(4, 0, 1))
+            .build()
+            .into();
+        then.status(200)
+            .body(MockRpcResult::new(consensus).to_json());
+    });
+    server.mock(|when, then| {
+        when.method(POST).path("/").body_contains("get_tip_header");
+        let tip_header: json_types::HeaderView = HeaderBuilder::default()
+            .number(100.pack())
+            .epoch(EpochNumberWithFraction::new(3, 0, 1).full_value().pack())
+            .build()
+            .into();
+        then.status(200)
+            .body(MockRpcResult::new(tip_header).to_json());
+    });
+    server.mock(|when, then| {
+        when.method(POST)
+            .path("/")
+            .body_contains("get_tip_block_number");
+        then.status(200)
+            .body(MockRpcResult::new(json_types::BlockNumber::from(100)).to_json());
+    });
+    server.mock(|when, then| {
+        when.method(POST).path("/").body_contains("get_indexer_tip");
+        let tip = Tip {
+            block_hash: h256!("0x1"),
+            block_number: 100.into(),
+        };
+        then.status(200).body(MockRpcResult::new(tip).to_json());
+    });
+    let cells = vec![
+        indexer_cell(OutPoint::new(h256!("0x2").pack(), 0), 100 * ONE_CKB),
+        indexer_cell(OutPoint::new(h256!("0x2").pack(), 1), 200 * ONE_CKB),
+    ];
+    // the first page without a cursor, and an empty page after it
+    let first_page = server.mock(|when, then| {
+        when.method(POST)
+            .path("/")
+            .body_contains("get_cells")
+            .body_contains("null]");
+        let page = Pagination {
+            objects: cells.clone(),
+            last_cursor: json_types::JsonBytes::from_vec(vec![0xff]),
+        };
+        then.status(200).body(MockRpcResult::new(page).to_json());
+    });
+    let last_page = server.mock(|when, then| {
+        when.method(POST)
+            .path("/")
+            .body_contains("get_cells")
+            .body_contains("\"0xff\"]");
+        let page = Pagination::<Cell> {
+            objects: Vec::new(),
+            last_cursor: json_types::JsonBytes::from_vec(vec![0xff]),
+        };
+        then.status(200).body(MockRpcResult::new(page).to_json());
+    });
+
+    let runtime = runtime();
+    let mut collector = AsyncDefaultCellCollector::new(server.base_url().as_str());
+    let mut query = CellQueryOptions::new_lock(build_sighash_script(ACCOUNT1_ARG));
+    query.min_total_capacity = 150 * ONE_CKB;
+
+    // the collection stops once the capacity is enough
+    let (live_cells, capacity) = runtime
+        .block_on(collector.collect_live_cells(&query, true))
+        .unwrap();
+    assert_eq!(capacity, 300 * ONE_CKB);
+    let mut out_points: Vec<_> = live_cells
+        .iter()
+        .map(|cell| cell.out_point.clone())
+        .collect();
+    out_points.sort_by_key(|out_point| Unpack::<u32>::unpack(&out_point.index()));
+    let expected: Vec<OutPoint> = cells
+        .iter()
+        .map(|cell| cell.out_point.clone().into())
+        .collect();
+    assert_eq!(out_points, expected);
+    first_page.assert();
+    last_page.assert_hits(0);
+
+    // the applied cells are locked, the rest pages are scanned without a result
+    let (live_cells, capacity) = runtime
+        .block_on(collector.collect_live_cells(&query, false))
+        .unwrap();
+    assert!(live_cells.is_empty());
+    assert_eq!(capacity, 0);
+    first_page.assert_hits(2);
+    last_page.assert();
+
+    // the reset collector collects them again
+    collector.reset();
+    let (live_cells, _) = runtime
+        .block_on(collector.collect_live_cells(&query, false))
+        .unwrap();
+    assert_eq!(live_cells.len(), 2);
+}
+
+#[test]
+fn test_async_transaction_dependency_provider() {
+    let output = CellOutput::new_builder()
+        .capacity((100 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT1_ARG))
+        .build();
+    let data = Bytes::from(vec![1u8, 2, 3]);
+    let live_cell = json_types::CellWithStatus {
+        cell: Some(json_types::CellInfo {
+            output: output.clone().into(),
+            data: Some(json_types::CellData {
+                content: json_types::JsonBytes::from_bytes(data.clone()),
+                hash: CellOutput::calc_data_hash(&data).unpack(),
+            }),
+        }),
+        status: "live".to_string(),
+    };
+    let tx = TransactionBuilder::default()
+        .input(CellInput::new(OutPoint::new(h256!("0x2").pack(), 0), 0))
+        .input(CellInput::new(OutPoint::new(h256!("0x3").pack(), 0), 0))
+        .output(output.clone())
+        .output_data(data.pack())
+        .build();
+
+    let server = MockServer::start();
+    let single = server.mock(|when, then| {
+        when.method(POST)
+            .path("/")
+            .body_contains("get_live_cell")
+            .body_contains(format!("{:#x}", h256!("0x2")));
+        then.status(200)
+            .body(MockRpcResult::new(live_cell.clone()).to_json());
+    });
+    // the batch follows the single request, so its request id is 1
+    let batch = server.mock(|when, then| {
+        when.method(POST)
+            .path("/")
+            .body_contains("get_live_cell")
+            .body_contains(format!("{:#x}", h256!("0x3")));
+        then.status(200)
+            .body(json!([{ "jsonrpc": "2.0", "id": 1, "result": live_cell }]).to_string());
+    });
+    let get_transaction = server.mock(|when, then| {
+        when.method(POST).path("/").body_contains("get_transaction");
+        let tx_with_status = json_types::TransactionWithStatusResponse {
+            transaction: Some(json_types::ResponseFormat::json(
+                json_types::TransactionView::from(tx.clone()),
+            )),
+            cycles: None,
+            time_added_to_pool: None,
+            tx_status: json_types::TxStatus::committed(10.into(), h256!("0x1")),
+            fee: None,
+            min_replace_fee: None,
+        };
+        then.status(200)
+            .body(MockRpcResult::new(tx_with_status).to_json());
+    });
+
+    let runtime = runtime();
+    let provider = AsyncDefaultTransactionDependencyProvider::new(server.base_url().as_str(), 10);
+
+    // the cell is fetched once and then served from the cache
+    let first = OutPoint::new(h256!("0x2").pack(), 0);
+    assert_eq!(runtime.block_on(provider.get_cell(&first)).unwrap(), output);
+    assert_eq!(
+        runtime.block_on(provider.get_cell_data(&first)).unwrap(),
+        data
+    );
+    single.assert();
+
+    // the uncached input is prefetched in a batch, the cached one is skipped
+    runtime.block_on(provider.prefetch_inputs(&tx)).unwrap();
+    batch.assert();
+    let second = OutPoint::new(h256!("0x3").pack(), 0);
+    assert_eq!(
+        runtime.block_on(provider.get_cell(&second)).unwrap(),
+        output
+    );
+    batch.assert_hits(1);
+    single.assert_hits(1);
+
+    // the committed transaction is cached too
+    for _ in 0..2 {
+        let fetched = runtime
+            .block_on(provider.get_transaction(&tx.hash()))
+            .unwrap();
+        assert_eq!(fetched.hash(), tx.hash());
+    }
+    get_transaction.assert();
+}
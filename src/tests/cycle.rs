@@ -22,7 +22,7 @@ use crate::{
     ScriptGroup, ScriptId,
 };
 
-const CYCLE_BIN: &[u8] = include_bytes!("../test-data/cycle");
+pub(crate) const CYCLE_BIN: &[u8] = include_bytes!("../test-data/cycle");
 
 pub struct CycleUnlocker {
     loops: u64,
@@ -65,7 +65,7 @@ pub fn build_args(loops: u64) -> Bytes {
     bytes.freeze()
 }
 
-fn build_script(loops: u64) -> Script {
+pub(crate) fn build_script(loops: u64) -> Script {
     let cycle_data_hash = H256::from(blake2b_256(CYCLE_BIN));
     Script::new_builder()
         .code_hash(cycle_data_hash.pack())
@@ -1086,9 +1086,14 @@ fn test_udt_transfer() {
     ctx.verify(tx, FEE_RATE).unwrap();
 }
 
+pub mod async_impls;
 pub mod ckb_indexer_rpc;
 pub mod ckb_rpc;
 pub mod cycle;
 pub mod omni_lock;
 pub mod omni_lock_util;
+pub mod rpc_batch;
+pub mod rpc_client;
+pub mod rpc_paginate;
+pub mod rpc_transport;
 pub mod transaction;
@@ -39,9 +39,9 @@ use ckb_types::{
 };
 use rand::Rng;
 
-const OMNILOCK_BIN: &[u8] = include_bytes!("../test-data/omni_lock");
+pub(crate) const OMNILOCK_BIN: &[u8] = include_bytes!("../test-data/omni_lock");
 
-fn build_omnilock_script(cfg: &OmniLockConfig) -> Script {
+pub(crate) fn build_omnilock_script(cfg: &OmniLockConfig) -> Script {
     let omnilock_data_hash = H256::from(blake2b_256(OMNILOCK_BIN));
     Script::new_builder()
         .code_hash(omnilock_data_hash.pack())
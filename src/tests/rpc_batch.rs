@@ -0,0 +1,191 @@
+use ckb_jsonrpc_types as json_types;
+use ckb_types::{
+    bytes::Bytes,
+    core::TransactionBuilder,
+    h256,
+    packed::{CellInput, CellOutput, OutPoint},
+    prelude::*,
+};
+use httpmock::prelude::*;
+use serde_json::json;
+
+use crate::{
+    constants::ONE_CKB,
+    rpc::{AsyncCkbRpcClient, RpcError},
+    tests::{build_sighash_script, ACCOUNT1_ARG},
+    traits::{DefaultTransactionDependencyProvider, TransactionDependencyProvider},
+    tx_builder::gen_script_groups,
+    CkbRpcClient,
+};
+
+fn mock_tip_and_header(server: &MockServer) -> httpmock::Mock<'_> {
+    // the outputs are not in the order of the requests
+    server.mock(|when, then| {
+        when.method(POST)
+            .path("/")
+            .body_contains("get_tip_block_number")
+            .body_contains("get_header");
+        then.status(200).body(
+            json!([
+                {
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "error": { "code": -32000, "message": "header not found" }
+                },
+                { "jsonrpc": "2.0", "id": 0, "result": "0x64" },
+            ])
+            .to_string(),
+        );
+    })
+}
+
+#[test]
+fn test_batch_requests() {
+    let server = MockServer::start();
+    let mock = mock_tip_and_header(&server);
+
+    let client = CkbRpcClient::new(server.base_url().as_str());
+    let batch = client
+        .batch()
+        .get_tip_block_number()
+        .get_header(h256!("0x1"));
+    assert_eq!(batch.len(), 2);
+    let (((), tip), header) = batch.send().unwrap();
+    assert_eq!(tip.unwrap().value(), 100);
+    assert!(matches!(header, Err(RpcError::Rpc(_))));
+    mock.assert();
+
+    let client = AsyncCkbRpcClient::new(server.base_url().as_str());
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let (((), tip), header) = runtime
+        .block_on(
+            client
+                .batch()
+                .get_tip_block_number()
+                .get_header(h256!("0x1"))
+                .send(),
+        )
+        .unwrap();
+    assert_eq!(tip.unwrap().value(), 100);
+    assert!(header.is_err());
+    mock.assert_hits(2);
+
+    // no request is sent for an empty batch
+    CkbRpcClient::new(server.base_url().as_str())
+        .batch()
+        .send()
+        .unwrap();
+    let results = CkbRpcClient::new(server.base_url().as_str())
+        .post_batch::<(), json_types::BlockNumber>("get_tip_block_number", Vec::new())
+        .unwrap();
+    assert!(results.is_empty());
+    mock.assert_hits(2);
+}
+
+#[test]
+fn test_prefetch_inputs() {
+    let live_out_point = OutPoint::new(h256!("0x2").pack(), 0);
+    let dead_out_point = OutPoint::new(h256!("0x3").pack(), 1);
+    let output = CellOutput::new_builder()
+        .capacity((100 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT1_ARG))
+        .build();
+    let data = Bytes::from(vec![1u8, 2, 3]);
+    let live_cell = json_types::CellWithStatus {
+        cell: Some(json_types::CellInfo {
+            output: output.clone().into(),
+            data: Some(json_types::CellData {
+                content: json_types::JsonBytes::from_bytes(data.clone()),
+                hash: CellOutput::calc_data_hash(&data).unpack(),
+            }),
+        }),
+        status: "live".to_string(),
+    };
+    let dead_cell = json_types::CellWithStatus {
+        cell: None,
+        status: "unknown".to_string(),
+    };
+
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/").body_contains("get_live_cell");
+        then.status(200).body(
+            json!([
+                { "jsonrpc": "2.0", "id": 0, "result": live_cell },
+                { "jsonrpc": "2.0", "id": 1, "result": dead_cell },
+            ])
+            .to_string(),
+        );
+    });
+
+    let tx = TransactionBuilder::default()
+        .input(CellInput::new(live_out_point.clone(), 0))
+        .input(CellInput::new(dead_out_point, 0))
+        .build();
+    let provider = DefaultTransactionDependencyProvider::new(server.base_url().as_str(), 10);
+    provider.prefetch_inputs(&tx).unwrap();
+    mock.assert();
+
+    // the live cell is served from the cache
+    assert_eq!(provider.get_cell(&live_out_point).unwrap(), output);
+    assert_eq!(provider.get_cell_data(&live_out_point).unwrap(), data);
+    // the cached inputs are not fetched again
+    let tx = TransactionBuilder::default()
+        .input(CellInput::new(live_out_point, 0))
+        .build();
+    provider.prefetch_inputs(&tx).unwrap();
+    mock.assert_hits(1);
+}
+
+#[test]
+fn test_prefetch_inputs_of_script_groups() {
+    let out_points: Vec<OutPoint> = (0..2u32)
+        .map(|index| OutPoint::new(h256!("0x2").pack(), index))
+        .collect();
+    let output = CellOutput::new_builder()
+        .capacity((100 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT1_ARG))
+        .build();
+    let live_cell = json_types::CellWithStatus {
+        cell: Some(json_types::CellInfo {
+            output: output.clone().into(),
+            data: Some(json_types::CellData {
+                content: json_types::JsonBytes::default(),
+                hash: CellOutput::calc_data_hash(&[]).unpack(),
+            }),
+        }),
+        status: "live".to_string(),
+    };
+
+    // only the batch response is mocked, the inputs can not be fetched one by one
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/").body_contains("get_live_cell");
+        then.status(200).body(
+            json!([
+                { "jsonrpc": "2.0", "id": 0, "result": live_cell },
+                { "jsonrpc": "2.0", "id": 1, "result": live_cell },
+            ])
+            .to_string(),
+        );
+    });
+
+    let tx = TransactionBuilder::default()
+        .inputs(
+            out_points
+                .into_iter()
+                .map(|out_point| CellInput::new(out_point, 0)),
+        )
+        .build();
+    let provider = DefaultTransactionDependencyProvider::new(server.base_url().as_str(), 10);
+    let script_groups = gen_script_groups(&tx, &provider).unwrap();
+    mock.assert();
+    let lock_group = script_groups
+        .lock_groups
+        .get(&output.calc_lock_hash())
+        .unwrap();
+    assert_eq!(lock_group.input_indices, vec![0, 1]);
+}
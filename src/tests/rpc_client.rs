@@ -0,0 +1,189 @@
+use std::time::Duration;
+
+use httpmock::prelude::*;
+
+use crate::{
+    rpc::{AsyncIndexerRpcClient, FailoverTransport, RetryPolicy, RpcClientBuilder, RpcError},
+    test_util::MockRpcResult,
+    CkbRpcClient,
+};
+
+#[test]
+fn test_invalid_url() {
+    assert!(matches!(
+        CkbRpcClient::try_new("127.0.0.1:8114"),
+        Err(RpcError::InvalidUrl(_))
+    ));
+    assert!(matches!(
+        RpcClientBuilder::new("not a url").build::<CkbRpcClient>(),
+        Err(RpcError::InvalidUrl(_))
+    ));
+    assert!(RpcClientBuilder::new("http://127.0.0.1:8114")
+        .header("x-api-key", "invalid\nvalue")
+        .build::<AsyncIndexerRpcClient>()
+        .is_err());
+}
+
+#[test]
+fn test_client_with_headers() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/")
+            .header("authorization", "Bearer secret")
+            .header("x-api-key", "key")
+            .body_contains("get_tip_block_number");
+        then.status(200)
+            .body(MockRpcResult::new(ckb_jsonrpc_types::Uint64::from(100)).to_json());
+    });
+
+    let client: CkbRpcClient = RpcClientBuilder::new(server.base_url().as_str())
+        .timeout(Duration::from_secs(5))
+        .bearer_auth("secret")
+        .header("x-api-key", "key")
+        .build()
+        .unwrap();
+    assert_eq!(client.get_tip_block_number().unwrap().value(), 100);
+    // the clone keeps the headers
+    assert_eq!(client.clone().get_tip_block_number().unwrap().value(), 100);
+    mock.assert_hits(2);
+
+    // requests without the headers are not matched
+    let client = CkbRpcClient::new(server.base_url().as_str());
+    assert!(client.get_tip_block_number().is_err());
+    mock.assert_hits(2);
+}
+
+fn retry_policy() -> RetryPolicy {
+    RetryPolicy {
+        max_retries: 2,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(10),
+    }
+}
+
+fn mock_tip(server: &MockServer, tip: u64) -> httpmock::Mock<'_> {
+    server.mock(|when, then| {
+        when.method(POST)
+            .path("/")
+            .body_contains("get_tip_block_number");
+        then.status(200)
+            .body(MockRpcResult::new(ckb_jsonrpc_types::Uint64::from(tip)).to_json());
+    })
+}
+
+#[test]
+fn test_retry_policy() {
+    let policy = RetryPolicy::default();
+    assert_eq!(policy.backoff(0), Duration::from_millis(100));
+    assert_eq!(policy.backoff(3), Duration::from_millis(800));
+    assert_eq!(policy.backoff(100), policy.max_backoff);
+
+    // the server errors are retried
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/");
+        then.status(503);
+    });
+    let client: CkbRpcClient = RpcClientBuilder::new(server.base_url().as_str())
+        .retry_policy(retry_policy())
+        .build()
+        .unwrap();
+    let err = client.get_tip_block_number().unwrap_err();
+    assert!(err.is_retryable());
+    mock.assert_hits(3);
+    // the batch requests too
+    assert!(client.batch().get_tip_block_number().send().is_err());
+    mock.assert_hits(6);
+    // no retry by default
+    assert!(CkbRpcClient::new(server.base_url().as_str())
+        .get_tip_block_number()
+        .is_err());
+    mock.assert_hits(7);
+
+    // the application errors are not retried
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/");
+        then.status(200).body(
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "error": { "code": -3, "message": "invalid params" }
+            })
+            .to_string(),
+        );
+    });
+    let mut client = CkbRpcClient::new(server.base_url().as_str());
+    client.set_retry_policy(Some(retry_policy()));
+    let err = client.get_tip_block_number().unwrap_err();
+    assert!(matches!(err, RpcError::Rpc(_)));
+    assert!(!err.is_retryable());
+    mock.assert_hits(1);
+
+    // the connection errors are retried, the async client too
+    let client: AsyncIndexerRpcClient = RpcClientBuilder::new("http://127.0.0.1:1")
+        .retry_policy(retry_policy())
+        .build()
+        .unwrap();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let err = runtime.block_on(client.get_indexer_tip()).unwrap_err();
+    assert!(err.is_retryable());
+}
+
+#[test]
+fn test_failover_transport() {
+    let down = MockServer::start();
+    let down_mock = down.mock(|when, then| {
+        when.method(POST).path("/");
+        then.status(500);
+    });
+    let lagging = MockServer::start();
+    let mut lagging_mock = mock_tip(&lagging, 100);
+    let synced = MockServer::start();
+    let mut synced_mock = mock_tip(&synced, 120);
+
+    let transport = FailoverTransport::new(&[
+        down.base_url().as_str(),
+        lagging.base_url().as_str(),
+        synced.base_url().as_str(),
+    ])
+    .unwrap();
+    let client = CkbRpcClient::new_with_transport(&transport);
+    // the tips are checked before the first request
+    let tip = client.get_tip_block_number().unwrap();
+    assert_eq!(tip.value(), 120);
+    assert!(!transport.is_healthy(0));
+    assert!(!transport.is_healthy(1));
+    assert!(transport.is_healthy(2));
+    assert_eq!(transport.current(), 2);
+    down_mock.assert_hits(1);
+    lagging_mock.assert_hits(1);
+    synced_mock.assert_hits(2);
+
+    // the lagging endpoint is used when the synced one is down
+    synced_mock.delete();
+    let synced_down = synced.mock(|when, then| {
+        when.method(POST).path("/");
+        then.status(502);
+    });
+    let tip = client.get_tip_block_number().unwrap();
+    assert_eq!(tip.value(), 100);
+    assert_eq!(transport.current(), 1);
+    synced_down.assert_hits(1);
+    down_mock.assert_hits(2);
+
+    // all endpoints are down
+    let lagging_down = lagging.mock(|when, then| {
+        when.method(POST).path("/");
+        then.status(500);
+    });
+    lagging_mock.delete();
+    let err = client.get_tip_block_number().unwrap_err();
+    assert!(err.is_retryable());
+    lagging_down.assert_hits(1);
+    assert!(transport.check_tips().is_err());
+}
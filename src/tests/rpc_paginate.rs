@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use ckb_types::H256;
+use futures::stream::StreamExt;
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+
+use crate::{
+    rpc::{
+        ckb_indexer::{Order, SearchKey, Tx},
+        AsyncIndexerRpcClient, IndexerRpcClient, MemoryTransport, PageSize, PaginationCursor,
+    },
+    tests::{build_sighash_script, ACCOUNT1_ARG},
+    traits::{CellQueryOptions, PrimaryScriptType},
+};
+
+const TOTAL_TXS: u32 = 50;
+
+fn parse_hex(value: &Value) -> u32 {
+    u32::from_str_radix(value.as_str().unwrap().trim_start_matches("0x"), 16).unwrap()
+}
+
+// An indexer of `TOTAL_TXS` transactions, the cursor is the index of the next one.
+fn indexer_transport(limits: Arc<Mutex<Vec<u32>>>) -> MemoryTransport {
+    MemoryTransport::new(move |method, params| {
+        assert_eq!(method, "get_transactions");
+        let limit = parse_hex(&params[2]);
+        let start = if params[3].is_null() {
+            0
+        } else {
+            parse_hex(&params[3])
+        };
+        limits.lock().push(limit);
+        let end = (start + limit).min(TOTAL_TXS);
+        let objects: Vec<_> = (start..end)
+            .map(|index| {
+                let mut tx_hash = [0u8; 32];
+                tx_hash[28..].copy_from_slice(&index.to_be_bytes());
+                json!({
+                    "tx_hash": H256(tx_hash),
+                    "block_number": "0x1",
+                    "tx_index": "0x0",
+                    "io_index": "0x0",
+                    "io_type": "output",
+                })
+            })
+            .collect();
+        Ok(json!({ "objects": objects, "last_cursor": format!("0x{:08x}", end) }))
+    })
+}
+
+fn search_key() -> SearchKey {
+    let lock_script = build_sighash_script(ACCOUNT1_ARG);
+    SearchKey::from(CellQueryOptions::new(lock_script, PrimaryScriptType::Lock))
+}
+
+fn tx_index(tx: &Tx) -> u32 {
+    let mut index = [0u8; 4];
+    index.copy_from_slice(&tx.tx_hash().as_bytes()[28..]);
+    u32::from_be_bytes(index)
+}
+
+#[test]
+fn test_iter_transactions() {
+    let limits = Arc::new(Mutex::new(Vec::new()));
+    let client = IndexerRpcClient::new_with_transport(indexer_transport(Arc::clone(&limits)));
+    let txs: Vec<_> = client
+        .iter_transactions(search_key(), Order::Asc, PageSize::adaptive(4, 16))
+        .map(|tx| tx_index(&tx.unwrap()))
+        .collect();
+    assert_eq!(txs, (0..TOTAL_TXS).collect::<Vec<_>>());
+    // the last page is empty
+    assert_eq!(*limits.lock(), vec![4, 8, 16, 16, 16, 16]);
+
+    // stop in the middle of a page and resume from the saved cursor
+    let mut txs = client.iter_transactions(search_key(), Order::Asc, 8);
+    for _ in 0..11 {
+        txs.next().unwrap().unwrap();
+    }
+    let cursor: PaginationCursor =
+        serde_json::from_str(&serde_json::to_string(&txs.cursor()).unwrap()).unwrap();
+    assert_eq!(cursor.skip, 3);
+    let rest: Vec<_> = client
+        .iter_transactions(search_key(), Order::Asc, PageSize::default())
+        .resume_from(cursor)
+        .map(|tx| tx_index(&tx.unwrap()))
+        .collect();
+    assert_eq!(rest, (11..TOTAL_TXS).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_iter_transactions_async() {
+    let limits = Arc::new(Mutex::new(Vec::new()));
+    let client = AsyncIndexerRpcClient::new_with_transport(indexer_transport(Arc::clone(&limits)));
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let txs: Vec<_> = runtime.block_on(
+        client
+            .iter_transactions(search_key(), Order::Asc, 20)
+            .map(|tx| tx_index(&tx.unwrap()))
+            .collect(),
+    );
+    assert_eq!(txs, (0..TOTAL_TXS).collect::<Vec<_>>());
+    assert_eq!(*limits.lock(), vec![20, 20, 20, 20]);
+}
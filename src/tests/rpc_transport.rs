@@ -0,0 +1,106 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::thread;
+
+use ckb_types::h256;
+use serde_json::{json, Value};
+
+use crate::{
+    rpc::{AsyncCkbRpcClient, MemoryTransport, RpcError, TcpTransport},
+    CkbRpcClient,
+};
+
+fn memory_transport() -> MemoryTransport {
+    MemoryTransport::new(|method, params| match method {
+        "get_tip_block_number" => Ok(json!("0x64")),
+        "get_header" if params[0] == json!(h256!("0x1")) => Ok(Value::Null),
+        _ => Err(jsonrpc_core::Error::method_not_found()),
+    })
+}
+
+#[test]
+fn test_memory_transport() {
+    let client = CkbRpcClient::new_with_transport(memory_transport());
+    assert_eq!(client.get_tip_block_number().unwrap().value(), 100);
+    assert!(client.get_header(h256!("0x1")).unwrap().is_none());
+    assert!(matches!(
+        client.get_header(h256!("0x2")),
+        Err(RpcError::Rpc(_))
+    ));
+    let (((), tip), header) = client
+        .batch()
+        .get_tip_block_number()
+        .get_header(h256!("0x1"))
+        .send()
+        .unwrap();
+    assert_eq!(tip.unwrap().value(), 100);
+    assert!(header.unwrap().is_none());
+
+    let client = AsyncCkbRpcClient::new_with_transport(memory_transport());
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let tip = runtime.block_on(client.get_tip_block_number()).unwrap();
+    assert_eq!(tip.value(), 100);
+}
+
+// Answer every request line with the tip, writing each response in two parts.
+fn serve_tip(reader: impl Read, mut writer: impl Write) {
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+    while reader.read_line(&mut line).unwrap() > 0 {
+        let response =
+            |call: &Value| json!({ "jsonrpc": "2.0", "id": call["id"], "result": "0x64" });
+        let response = match serde_json::from_str::<Value>(&line).unwrap() {
+            Value::Array(calls) => calls.iter().map(response).collect(),
+            call => response(&call),
+        };
+        let response = format!("{}\n", response);
+        let (head, tail) = response.split_at(response.len() / 2);
+        writer.write_all(head.as_bytes()).unwrap();
+        writer.flush().unwrap();
+        writer.write_all(tail.as_bytes()).unwrap();
+        line.clear();
+    }
+}
+
+#[test]
+fn test_tcp_transport() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        serve_tip(stream.try_clone().unwrap(), stream);
+    });
+
+    // the requests share one connection
+    let client = CkbRpcClient::new_with_transport(TcpTransport::new(&addr));
+    assert_eq!(client.get_tip_block_number().unwrap().value(), 100);
+    let (((), tip0), tip1) = client
+        .batch()
+        .get_tip_block_number()
+        .get_tip_block_number()
+        .send()
+        .unwrap();
+    assert_eq!(tip0.unwrap().value(), 100);
+    assert_eq!(tip1.unwrap().value(), 100);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unix_transport() {
+    use crate::rpc::UnixTransport;
+
+    let path = std::env::temp_dir().join(format!("ckb-sdk-ipc-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        serve_tip(stream.try_clone().unwrap(), stream);
+    });
+
+    let client = CkbRpcClient::new_with_transport(UnixTransport::new(&path));
+    assert_eq!(client.get_tip_block_number().unwrap().value(), 100);
+    assert_eq!(client.get_tip_block_number().unwrap().value(), 100);
+    std::fs::remove_file(&path).unwrap();
+}
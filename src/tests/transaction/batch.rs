@@ -0,0 +1,208 @@
+use std::collections::HashSet;
+
+use ckb_types::{prelude::*, H160};
+
+use crate::{
+    constants::ONE_CKB,
+    tests::{build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT1_KEY, FEE_RATE},
+    transaction::{
+        builder::{
+            BatchFeeMode, BatchPaymentBuilder, ChangeDecision, FeeCalculator, RecipientPayment,
+            SmallChangePolicy,
+        },
+        input::InputIterator,
+        signer::{SignContexts, TransactionSigner},
+        TransactionBuilderConfiguration,
+    },
+    tx_builder::TxBuilderError,
+    Address, AddressPayload, HumanCapacity, NetworkInfo, NetworkType,
+};
+
+fn recipient(index: u8) -> Address {
+    Address::new(
+        NetworkType::Testnet,
+        AddressPayload::from_pubkey_hash(H160([index; 20])),
+        true,
+    )
+}
+
+fn new_builder(ctx: &crate::test_util::Context) -> BatchPaymentBuilder {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(NetworkInfo::testnet()).unwrap();
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    BatchPaymentBuilder::new(configuration, iterator)
+}
+
+#[test]
+fn test_batch_payment_split_by_output_count() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(300 * ONE_CKB)),
+            (sender.clone(), Some(300 * ONE_CKB)),
+            (sender.clone(), Some(300 * ONE_CKB)),
+        ],
+    );
+    let mut builder = new_builder(&ctx);
+    for index in 0..5u8 {
+        builder
+            .add_recipient(
+                &recipient(index),
+                HumanCapacity((100 + index as u64) * ONE_CKB),
+            )
+            .unwrap();
+    }
+    builder.set_max_outputs_per_tx(Some(2));
+    let batches = builder.build_batches(&Default::default()).unwrap();
+
+    assert_eq!(batches.len(), 3);
+    let mut spent = HashSet::new();
+    let mut recipient_index = 0;
+    for mut batch in batches {
+        for (output_index, payment) in batch.payments.iter().enumerate() {
+            let capacity = (100 + recipient_index as u64) * ONE_CKB;
+            assert_eq!(
+                payment,
+                &RecipientPayment {
+                    recipient_index,
+                    output_index,
+                    capacity,
+                    deducted_fee: 0,
+                }
+            );
+            recipient_index += 1;
+        }
+        TransactionSigner::new(&NetworkInfo::testnet())
+            .sign_transaction(
+                &mut batch.tx_with_groups,
+                &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+            )
+            .unwrap();
+        let tx = batch.tx_with_groups.get_tx_view().clone();
+        for out_point in tx.input_pts_iter() {
+            assert!(spent.insert(out_point), "the input is spent twice");
+        }
+        ctx.verify(tx, FEE_RATE).unwrap();
+    }
+    assert_eq!(recipient_index, 5);
+}
+
+#[test]
+fn test_batch_payment_deduct_fee_from_recipients() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(Vec::new(), vec![(sender.clone(), Some(1000 * ONE_CKB))]);
+    let mut builder = new_builder(&ctx);
+    builder
+        .add_recipient(&recipient(1), HumanCapacity(100 * ONE_CKB))
+        .unwrap();
+    builder
+        .add_recipient(&recipient(2), HumanCapacity(300 * ONE_CKB))
+        .unwrap();
+    builder.set_fee_mode(BatchFeeMode::DeductedProportionally);
+    let mut batches = builder.build_batches(&Default::default()).unwrap();
+    assert_eq!(batches.len(), 1);
+    let batch = &mut batches[0];
+
+    TransactionSigner::new(&NetworkInfo::testnet())
+        .sign_transaction(
+            &mut batch.tx_with_groups,
+            &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+        )
+        .unwrap();
+    let tx = batch.tx_with_groups.get_tx_view().clone();
+    let tx_size = tx.data().as_reader().serialized_size_in_block() as u64;
+    let fee = FeeCalculator::new(FEE_RATE).fee(tx_size);
+
+    // the recipients pay the fee by their amounts
+    let payments = &batch.payments;
+    assert_eq!(payments[0].output_index, 0);
+    assert_eq!(payments[1].output_index, 1);
+    assert_eq!(payments[0].deducted_fee + payments[1].deducted_fee, fee);
+    assert_eq!(payments[1].deducted_fee, fee * 3 / 4);
+    assert_eq!(
+        payments[0].capacity,
+        100 * ONE_CKB - payments[0].deducted_fee
+    );
+    assert_eq!(
+        payments[1].capacity,
+        300 * ONE_CKB - payments[1].deducted_fee
+    );
+
+    // the sender pays nothing but the amounts
+    let change_capacity: u64 = tx.output(2).unwrap().capacity().unpack();
+    assert_eq!(change_capacity, (1000 - 400) * ONE_CKB);
+    assert_eq!(
+        batch.tx_with_groups.get_change_decision(),
+        Some(&ChangeDecision::ChangeOutput {
+            output_index: 2,
+            capacity: change_capacity,
+        })
+    );
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_batch_payment_deduct_fee_with_small_change() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    // the change is less than the occupied capacity of a change output
+    let ctx = init_context(Vec::new(), vec![(sender.clone(), Some(450 * ONE_CKB))]);
+    let build = |fee_mode: BatchFeeMode| {
+        let mut configuration =
+            TransactionBuilderConfiguration::new_with_network(NetworkInfo::testnet()).unwrap();
+        configuration.small_change_policy = SmallChangePolicy::AddToOutput { output_index: 0 };
+        let iterator = InputIterator::new_with_cell_collector(
+            vec![sender.clone()],
+            Box::new(ctx.to_live_cells_context()) as Box<_>,
+        );
+        let mut builder = BatchPaymentBuilder::new(configuration, iterator);
+        builder
+            .add_recipient(&recipient(1), HumanCapacity(100 * ONE_CKB))
+            .unwrap();
+        builder
+            .add_recipient(&recipient(2), HumanCapacity(300 * ONE_CKB))
+            .unwrap();
+        builder.set_fee_mode(fee_mode);
+        builder.build_batches(&Default::default())
+    };
+
+    // the small change goes to the first recipient when the sender pays the fee
+    let batches = build(BatchFeeMode::PaidBySender).unwrap();
+    let batch = &batches[0];
+    let Some(&ChangeDecision::AddedToOutput {
+        output_index: 0,
+        capacity,
+    }) = batch.tx_with_groups.get_change_decision()
+    else {
+        panic!("the small change should be added to the first recipient");
+    };
+    assert_eq!(batch.payments[0].capacity, 100 * ONE_CKB + capacity);
+    assert_eq!(batch.payments[1].output_index, 1);
+
+    // the fee deducted from the first recipient can not be returned to itself
+    let Err(err) = build(BatchFeeMode::DeductedEqually) else {
+        panic!("the fee can not be returned to a recipient");
+    };
+    assert!(
+        matches!(err, TxBuilderError::InvalidParameter(_)),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_batch_payment_minimal_capacity() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(Vec::new(), vec![(sender, Some(1000 * ONE_CKB))]);
+    let mut builder = new_builder(&ctx);
+    assert!(builder
+        .add_recipient(&recipient(1), HumanCapacity(60 * ONE_CKB))
+        .is_err());
+    assert!(builder
+        .add_recipient(&recipient(1), HumanCapacity(61 * ONE_CKB))
+        .is_ok());
+}
@@ -0,0 +1,195 @@
+use std::{
+    sync::{Arc, Barrier},
+    thread,
+};
+
+use ckb_types::{
+    core::TransactionView,
+    packed::{CellOutput, OutPoint},
+    prelude::*,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::Context,
+    tests::{build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT2_ARG},
+    traits::WalletCellCache,
+    transaction::{
+        builder::{CkbTransactionBuilder, SimpleTransactionBuilder},
+        input::{InputIterator, LargestFirstSelector},
+        TransactionBuilderConfiguration,
+    },
+    NetworkInfo,
+};
+
+fn build_transfer(cell_cache: &WalletCellCache, capacity: u64) -> TransactionView {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let output = CellOutput::new_builder()
+        .capacity(capacity.pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(NetworkInfo::testnet()).unwrap();
+    let iterator = InputIterator::new_with_cell_cache(vec![sender], cell_cache);
+    let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+    builder.add_output_and_data(output, ckb_types::packed::Bytes::default());
+    builder
+        .build(&Default::default())
+        .expect("build failed")
+        .get_tx_view()
+        .clone()
+}
+
+fn init_cell_cache() -> (Context, WalletCellCache) {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(300 * ONE_CKB)),
+            (sender, Some(500 * ONE_CKB)),
+        ],
+    );
+    let cell_cache = WalletCellCache::new(Box::new(ctx.to_live_cells_context()));
+    (ctx, cell_cache)
+}
+
+#[test]
+fn test_chain_pending_transactions() {
+    let (ctx, cell_cache) = init_cell_cache();
+    let first_cell = ctx.inputs[0].input.previous_output();
+
+    // the built transaction is added to the cache as a pending transaction
+    let tx1 = build_transfer(&cell_cache, 70 * ONE_CKB);
+    assert_eq!(
+        tx1.input_pts_iter().collect::<Vec<_>>(),
+        vec![first_cell.clone()]
+    );
+    assert_eq!(cell_cache.pending_tx_hashes(), vec![tx1.hash()]);
+    assert!(cell_cache.is_locked(&first_cell));
+    assert!(!cell_cache.is_reserved(&first_cell));
+    let change = OutPoint::new(tx1.hash(), 1);
+    assert_eq!(
+        cell_cache
+            .pending_outputs()
+            .into_iter()
+            .map(|cell| cell.out_point)
+            .collect::<Vec<_>>(),
+        vec![OutPoint::new(tx1.hash(), 0), change.clone()]
+    );
+
+    // the change of the pending transaction is spent first, and the locked cell is skipped
+    let tx2 = build_transfer(&cell_cache, 70 * ONE_CKB);
+    assert_eq!(
+        tx2.input_pts_iter().collect::<Vec<_>>(),
+        vec![change.clone()]
+    );
+    assert!(cell_cache.is_locked(&change));
+
+    // the rejection of the parent rejects the child, all the cells are unlocked
+    cell_cache.reject_tx(&tx1.hash());
+    assert!(cell_cache.pending_tx_hashes().is_empty());
+    assert!(cell_cache.pending_outputs().is_empty());
+    assert!(!cell_cache.is_locked(&first_cell));
+    let tx3 = build_transfer(&cell_cache, 70 * ONE_CKB);
+    assert_eq!(tx3.input_pts_iter().collect::<Vec<_>>(), vec![first_cell]);
+}
+
+#[test]
+fn test_commit_pending_transaction() {
+    let (ctx, cell_cache) = init_cell_cache();
+    let first_cell = ctx.inputs[0].input.previous_output();
+
+    let tx1 = build_transfer(&cell_cache, 70 * ONE_CKB);
+    let tx2 = build_transfer(&cell_cache, 70 * ONE_CKB);
+
+    // the cache is shared by the clones across threads
+    let watcher = cell_cache.clone();
+    let tx1_hash = tx1.hash();
+    thread::spawn(move || watcher.commit_tx(&tx1_hash))
+        .join()
+        .unwrap();
+    assert_eq!(cell_cache.pending_tx_hashes(), vec![tx2.hash()]);
+    assert!(!cell_cache.is_locked(&first_cell));
+    assert!(cell_cache.is_locked(&OutPoint::new(tx1.hash(), 1)));
+
+    cell_cache.commit_tx(&tx2.hash());
+    assert!(cell_cache.pending_tx_hashes().is_empty());
+    assert!(!cell_cache.is_locked(&OutPoint::new(tx1.hash(), 1)));
+}
+
+#[test]
+fn test_collect_cells_concurrently() {
+    let (ctx, cell_cache) = init_cell_cache();
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+
+    // both iterators hold their first input until the other one collects
+    let barrier = Arc::new(Barrier::new(2));
+    let handles = (0..2)
+        .map(|_| {
+            let cell_cache = cell_cache.clone();
+            let sender = sender.clone();
+            let barrier = Arc::clone(&barrier);
+            thread::spawn(move || {
+                let mut iterator = InputIterator::new_with_cell_cache(vec![sender], &cell_cache);
+                let input = iterator.next().unwrap().unwrap();
+                barrier.wait();
+                input.live_cell.out_point
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut collected = handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect::<Vec<_>>();
+    collected.sort_by_key(|out_point| out_point.as_bytes());
+    collected.dedup();
+    assert_eq!(collected.len(), 2);
+
+    // the reservations are released with the iterators
+    let first_cell = ctx.inputs[0].input.previous_output();
+    assert!(!cell_cache.is_reserved(&first_cell));
+    let mut iterator = InputIterator::new_with_cell_cache(vec![sender], &cell_cache);
+    let input = iterator.next().unwrap().unwrap();
+    assert_eq!(input.live_cell.out_point, first_cell);
+    assert!(cell_cache.is_reserved(&first_cell));
+    drop(iterator);
+    assert!(!cell_cache.is_reserved(&first_cell));
+}
+
+#[test]
+fn test_reserve_selected_cells() {
+    let (ctx, cell_cache) = init_cell_cache();
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let small_cell = ctx.inputs[0].input.previous_output();
+    let large_cell = ctx.inputs[1].input.previous_output();
+
+    // the candidates are reserved while they are collected
+    let mut iterator = InputIterator::new_with_cell_cache(vec![sender.clone()], &cell_cache);
+    let candidates = iterator.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(candidates.len(), 2);
+    assert!(cell_cache.is_reserved(&small_cell));
+    assert!(cell_cache.is_reserved(&large_cell));
+    drop(iterator);
+
+    // only the selected cell is spent, the other candidate is released
+    let output = CellOutput::new_builder()
+        .capacity((70 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(NetworkInfo::testnet()).unwrap();
+    let iterator = InputIterator::new_with_cell_cache(vec![sender], &cell_cache);
+    let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+    builder.add_output_and_data(output, ckb_types::packed::Bytes::default());
+    builder.set_input_selector(Box::new(LargestFirstSelector));
+    let tx_with_groups = builder.build(&Default::default()).expect("build failed");
+    let tx = tx_with_groups.get_tx_view();
+    assert_eq!(
+        tx.input_pts_iter().collect::<Vec<_>>(),
+        vec![large_cell.clone()]
+    );
+    assert!(cell_cache.is_locked(&large_cell));
+    assert!(!cell_cache.is_locked(&small_cell));
+    assert!(!cell_cache.is_reserved(&small_cell));
+    assert!(!cell_cache.is_reserved(&large_cell));
+}
@@ -0,0 +1,178 @@
+use ckb_types::{
+    bytes::Bytes,
+    packed::{CellInput, OutPoint},
+    prelude::*,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    tests::{build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT1_KEY, FEE_RATE},
+    transaction::{
+        builder::{ChangeDecision, ConsolidationBuilder, FeeCalculator},
+        signer::{SignContexts, TransactionSigner},
+        TransactionBuilderConfiguration,
+    },
+    NetworkInfo,
+};
+
+#[test]
+fn test_consolidate_until_target() {
+    let owner = build_sighash_script(ACCOUNT1_ARG);
+    let mut ctx = init_context(
+        Vec::new(),
+        (0..10)
+            .map(|index| (owner.clone(), Some((70 + index) * ONE_CKB)))
+            .collect(),
+    );
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(NetworkInfo::testnet()).unwrap();
+    let mut builder = ConsolidationBuilder::new_with_cell_collector(
+        configuration,
+        vec![owner.clone()],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    builder.set_max_inputs(4);
+    builder.set_target_cell_count(Some(3));
+
+    let mut rounds = Vec::new();
+    while let Some(mut tx_with_groups) = builder.build_next(&Default::default()).unwrap() {
+        let tx = tx_with_groups.get_tx_view().clone();
+        let inputs_capacity: u64 = tx
+            .input_pts_iter()
+            .map(|out_point| {
+                let (output, _) = ctx.get_input(&out_point).unwrap();
+                Unpack::<u64>::unpack(&output.capacity())
+            })
+            .sum();
+        assert_eq!(tx.outputs().len(), 1);
+        assert_eq!(tx.output(0).unwrap().lock(), owner);
+        assert!(matches!(
+            tx_with_groups.get_change_decision(),
+            Some(ChangeDecision::ChangeOutput {
+                output_index: 0,
+                ..
+            })
+        ));
+
+        TransactionSigner::new(&NetworkInfo::testnet())
+            .sign_transaction(
+                &mut tx_with_groups,
+                &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+            )
+            .unwrap();
+        let tx = tx_with_groups.get_tx_view().clone();
+        let tx_size = tx.data().as_reader().serialized_size_in_block() as u64;
+        let output_capacity: u64 = tx.output(0).unwrap().capacity().unpack();
+        assert_eq!(
+            inputs_capacity - output_capacity,
+            FeeCalculator::new(FEE_RATE).fee(tx_size)
+        );
+        ctx.verify(tx.clone(), FEE_RATE).unwrap();
+
+        // the merged output can be consolidated in the next rounds
+        ctx.add_live_cell(
+            CellInput::new(OutPoint::new(tx.hash(), 0), 0),
+            tx.output(0).unwrap(),
+            Bytes::new(),
+            None,
+        );
+        rounds.push(tx.inputs().len());
+    }
+    // 10 cells -> 7 cells -> 4 cells -> 3 cells
+    assert_eq!(rounds, vec![4, 4, 2]);
+    assert_eq!(builder.collect_cells().unwrap().len(), 3);
+}
+
+#[test]
+fn test_consolidate_into_multiple_outputs() {
+    let owner = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        (0..6)
+            .map(|_| (owner.clone(), Some(100 * ONE_CKB)))
+            .collect(),
+    );
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(NetworkInfo::testnet()).unwrap();
+    let mut builder = ConsolidationBuilder::new_with_cell_collector(
+        configuration,
+        vec![owner],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    builder.set_output_count(2);
+
+    let mut tx_with_groups = builder.build_next(&Default::default()).unwrap().unwrap();
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(tx.inputs().len(), 6);
+    assert_eq!(tx.outputs().len(), 2);
+    let first_capacity: u64 = tx.output(0).unwrap().capacity().unpack();
+    assert_eq!(first_capacity, 300 * ONE_CKB);
+    TransactionSigner::new(&NetworkInfo::testnet())
+        .sign_transaction(
+            &mut tx_with_groups,
+            &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+        )
+        .unwrap();
+    ctx.verify(tx_with_groups.get_tx_view().clone(), FEE_RATE)
+        .unwrap();
+
+    // the cell count is not more than the output count now
+    assert!(builder.build_next(&Default::default()).unwrap().is_none());
+
+    builder.set_output_count(1);
+    builder.set_max_tx_size(100);
+    builder.set_target_cell_count(Some(1));
+    assert!(builder.build_next(&Default::default()).is_err());
+}
+
+#[test]
+fn test_consolidate_with_limited_cells() {
+    let owner = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        (0..10)
+            .map(|_| (owner.clone(), Some(70 * ONE_CKB)))
+            .collect(),
+    );
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(NetworkInfo::testnet()).unwrap();
+    let mut builder = ConsolidationBuilder::new_with_cell_collector(
+        configuration,
+        vec![owner],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    // only the cells to fill a transaction are collected
+    builder.set_max_inputs(5);
+    assert_eq!(builder.collect_cells().unwrap().len(), 6);
+
+    // 350 CKB can not be split into 4 outputs of 87.5 CKB, which is less than the occupied capacity
+    // 161 CKB of the output lock, the capacity is split into 2 outputs
+    let output_lock = build_sighash_script(ACCOUNT1_ARG)
+        .as_builder()
+        .args(Bytes::from(vec![0u8; 100]).pack())
+        .build();
+    builder.set_output_lock(output_lock.clone());
+    builder.set_output_count(4);
+    builder.set_target_cell_count(Some(9));
+    let mut tx_with_groups = builder.build_next(&Default::default()).unwrap().unwrap();
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(tx.inputs().len(), 5);
+    assert_eq!(tx.outputs().len(), 2);
+    assert_eq!(tx.output(0).unwrap().lock(), output_lock);
+    let first_capacity: u64 = tx.output(0).unwrap().capacity().unpack();
+    assert_eq!(first_capacity, 175 * ONE_CKB);
+    TransactionSigner::new(&NetworkInfo::testnet())
+        .sign_transaction(
+            &mut tx_with_groups,
+            &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+        )
+        .unwrap();
+    ctx.verify(tx_with_groups.get_tx_view().clone(), FEE_RATE)
+        .unwrap();
+
+    // the cells are not enough for an output
+    builder.set_output_count(1);
+    builder.set_max_inputs(2);
+    builder.set_target_cell_count(Some(1));
+    assert!(builder.build_next(&Default::default()).is_err());
+}
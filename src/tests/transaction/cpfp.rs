@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use ckb_types::{
+    packed::{CellInput, CellOutput, OutPoint},
+    prelude::*,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    tests::{
+        build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, FEE_RATE,
+    },
+    transaction::{
+        builder::{
+            CkbTransactionBuilder, CpfpTransactionBuilder, FeeCalculator, SimpleTransactionBuilder,
+        },
+        environment::BuildEnvironment,
+        input::InputIterator,
+        signer::{SignContexts, TransactionSigner},
+        TransactionBuilderConfiguration,
+    },
+    NetworkInfo,
+};
+
+#[test]
+fn test_cpfp_spend_pending_output() {
+    let sender = build_sighash_script(ACCOUNT2_ARG);
+    let receiver = build_sighash_script(ACCOUNT1_ARG);
+    let mut ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(200 * ONE_CKB)),
+            (receiver.clone(), Some(100 * ONE_CKB)),
+        ],
+    );
+    let network_info = NetworkInfo::testnet();
+
+    // the incoming payment with the minimal fee rate
+    let output = CellOutput::new_builder()
+        .capacity((100 * ONE_CKB).pack())
+        .lock(receiver.clone())
+        .build();
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender.clone()],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(network_info.clone()).unwrap();
+    let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+    builder.add_output_and_data(output.clone(), ckb_types::packed::Bytes::default());
+    let parent_tx = builder
+        .build(&Default::default())
+        .expect("build failed")
+        .get_tx_view()
+        .clone();
+    let parent_size = parent_tx.data().as_reader().serialized_size_in_block() as u64;
+    let parent_fee =
+        100 * ONE_CKB - Unpack::<u64>::unpack(&parent_tx.output(1).unwrap().capacity());
+
+    // the child spends the payment with a higher fee rate
+    let fee_rate = 5000;
+    let mut configuration =
+        TransactionBuilderConfiguration::new_with_network(network_info.clone()).unwrap();
+    configuration.fee_rate = fee_rate;
+    configuration.set_environment(BuildEnvironment::new(
+        Arc::new(ctx.clone()),
+        Arc::new(ctx.clone()),
+        Arc::new(ctx.clone()),
+    ));
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![receiver.clone()],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let builder = CpfpTransactionBuilder::new(configuration, iterator, parent_tx.clone());
+    let mut tx_with_groups = builder.build(&Default::default()).expect("build failed");
+    TransactionSigner::new(&network_info)
+        .sign_transaction(
+            &mut tx_with_groups,
+            &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+        )
+        .unwrap();
+
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(tx.inputs().len(), 1);
+    let parent_out_point = OutPoint::new(parent_tx.hash(), 0);
+    assert_eq!(
+        tx.inputs().get(0).unwrap().previous_output(),
+        parent_out_point
+    );
+    assert_eq!(tx.outputs().len(), 1);
+    assert_eq!(tx.output(0).unwrap().lock(), receiver);
+
+    // the package of the parent and the child reaches the fee rate
+    let tx_size = tx.data().as_reader().serialized_size_in_block() as u64;
+    let fee = 100 * ONE_CKB - Unpack::<u64>::unpack(&tx.output(0).unwrap().capacity());
+    let fee_calculator = FeeCalculator::new(fee_rate);
+    assert_eq!(fee, fee_calculator.fee(parent_size + tx_size) - parent_fee);
+    assert!(fee > fee_calculator.fee(tx_size));
+
+    ctx.add_live_cell(
+        CellInput::new(parent_out_point, 0),
+        output,
+        Default::default(),
+        None,
+    );
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
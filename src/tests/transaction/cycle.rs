@@ -0,0 +1,217 @@
+use std::sync::Arc;
+
+use ckb_types::{
+    packed::{CellDep, CellOutput, Script},
+    prelude::*,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    core::TransactionBuilder,
+    tests::{
+        build_sighash_script,
+        cycle::{build_script, CYCLE_BIN},
+        init_context, ACCOUNT0_ARG, ACCOUNT0_KEY, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG,
+        ACCOUNT2_KEY, FEE_RATE,
+    },
+    traits::CellDepResolver,
+    transaction::{
+        builder::{CkbTransactionBuilder, FeeCalculator, SimpleTransactionBuilder},
+        environment::BuildEnvironment,
+        handler::{HandlerContext, HandlerContexts, ScriptHandler},
+        input::InputIterator,
+        signer::{SignContexts, TransactionSigner},
+        TransactionBuilderConfiguration,
+    },
+    tx_builder::{bytes_per_cycle, TxBuilderError},
+    unlock::MultisigConfig,
+    NetworkInfo, ScriptGroup, TransactionWithScriptGroups,
+};
+
+/// The cycle script loops `args` times, the witness must be the same loop count.
+struct CycleScriptHandler {
+    loops: u64,
+    cell_dep: CellDep,
+}
+
+impl ScriptHandler for CycleScriptHandler {
+    fn build_transaction(
+        &self,
+        tx_builder: &mut TransactionBuilder,
+        script_group: &mut ScriptGroup,
+        _context: &dyn HandlerContext,
+    ) -> Result<bool, TxBuilderError> {
+        if script_group.script != build_script(self.loops) {
+            return Ok(false);
+        }
+        tx_builder.dedup_cell_deps(vec![self.cell_dep.clone()]);
+        let index = script_group.input_indices[0];
+        tx_builder.set_witness(index, self.loops.to_le_bytes().pack());
+        Ok(true)
+    }
+
+    fn init(&mut self, _network: &NetworkInfo) -> Result<(), TxBuilderError> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_transfer_with_cycle_fee() {
+    let loops = 512 * 1024;
+    let sender = build_script(loops);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(
+        vec![(CYCLE_BIN, true)],
+        vec![(sender.clone(), Some(200 * ONE_CKB))],
+    );
+
+    let network_info = NetworkInfo::testnet();
+    let output = CellOutput::new_builder()
+        .capacity((140 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+    let mut configuration =
+        TransactionBuilderConfiguration::new_with_network(network_info).unwrap();
+    configuration.register_script_handler(Box::new(CycleScriptHandler {
+        loops,
+        cell_dep: ctx.resolve(&sender).unwrap(),
+    }));
+    configuration.set_environment(BuildEnvironment::new(
+        Arc::new(ctx.clone()),
+        Arc::new(ctx.clone()),
+        Arc::new(ctx.clone()),
+    ));
+    configuration.check_cycle_fee = true;
+
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender.clone()],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+    builder.add_output_and_data(output.clone(), ckb_types::packed::Bytes::default());
+    let tx_with_groups = builder.build(&Default::default()).expect("build failed");
+
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(tx.inputs().len(), 1);
+    assert_eq!(tx.outputs().len(), 2);
+    assert_eq!(tx.output(0).unwrap(), output);
+    assert_eq!(tx.output(1).unwrap().lock(), sender);
+
+    let cycles = ctx.verify(tx.clone(), FEE_RATE).unwrap();
+    let tx_size = tx.data().as_reader().serialized_size_in_block() as u64;
+    // the fee is paid by cycles rather than by size
+    assert!((cycles as f64 * bytes_per_cycle()) as u64 > tx_size);
+    let change_capacity: u64 = tx.output(1).unwrap().capacity().unpack();
+    let fee = (200 - 140) * ONE_CKB - change_capacity;
+    assert!(fee >= FeeCalculator::new(FEE_RATE).fee_with_cycle(tx_size, cycles));
+}
+
+#[test]
+fn test_transfer_from_multisig_with_cycle_fee() {
+    let cfg = MultisigConfig::new_with(
+        vec![
+            ACCOUNT0_ARG.clone(),
+            ACCOUNT1_ARG.clone(),
+            ACCOUNT2_ARG.clone(),
+        ],
+        0,
+        3,
+    )
+    .unwrap();
+    let sender = Script::from(&cfg);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(Vec::new(), vec![(sender.clone(), Some(300 * ONE_CKB))]);
+
+    let network_info = NetworkInfo::testnet();
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+    let build = |check_cycle_fee: bool| {
+        let mut configuration =
+            TransactionBuilderConfiguration::new_with_network(network_info.clone()).unwrap();
+        configuration.set_environment(BuildEnvironment::new(
+            Arc::new(ctx.clone()),
+            Arc::new(ctx.clone()),
+            Arc::new(ctx.clone()),
+        ));
+        configuration.check_cycle_fee = check_cycle_fee;
+        let mut iterator = InputIterator::new_with_cell_collector(
+            Vec::new(),
+            Box::new(ctx.to_live_cells_context()) as Box<_>,
+        );
+        iterator.add_multisig_lock(&cfg, None);
+        let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+        builder.add_output_and_data(output.clone(), ckb_types::packed::Bytes::default());
+        builder
+            .build(&HandlerContexts::new_multisig(cfg.clone()))
+            .expect("build failed")
+    };
+    let fee = |tx_with_groups: &TransactionWithScriptGroups| {
+        let change_capacity: u64 = tx_with_groups
+            .get_tx_view()
+            .output(1)
+            .unwrap()
+            .capacity()
+            .unpack();
+        (300 - 120) * ONE_CKB - change_capacity
+    };
+
+    // the multisig lock fails with the placeholder signatures, the cycles are estimated by the
+    // handler, and the fee of the heavy lock is paid by cycles rather than by size
+    let size_fee = fee(&build(false));
+    let mut tx_with_groups = build(true);
+    assert!(fee(&tx_with_groups) > size_fee);
+
+    let signer = TransactionSigner::new(&network_info);
+    for key in [&ACCOUNT0_KEY, &ACCOUNT1_KEY, &ACCOUNT2_KEY] {
+        signer
+            .sign_transaction(
+                &mut tx_with_groups,
+                &SignContexts::new_multisig_h256(key, cfg.clone()).unwrap(),
+            )
+            .unwrap();
+    }
+    let tx = tx_with_groups.get_tx_view().clone();
+    let cycles = ctx.verify(tx.clone(), FEE_RATE).unwrap();
+    let tx_size = tx.data().as_reader().serialized_size_in_block() as u64;
+    assert!(fee(&tx_with_groups) >= FeeCalculator::new(FEE_RATE).fee_with_cycle(tx_size, cycles));
+}
+
+#[test]
+fn test_cycle_fee_without_estimation() {
+    let loops = 1024;
+    let sender = build_script(loops);
+    let ctx = init_context(
+        vec![(CYCLE_BIN, true)],
+        vec![(sender.clone(), Some(200 * ONE_CKB))],
+    );
+
+    let output = CellOutput::new_builder()
+        .capacity((140 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    let mut configuration =
+        TransactionBuilderConfiguration::new_with_network(NetworkInfo::testnet()).unwrap();
+    configuration.set_environment(BuildEnvironment::new(
+        Arc::new(ctx.clone()),
+        Arc::new(ctx.clone()),
+        Arc::new(ctx.clone()),
+    ));
+    configuration.check_cycle_fee = true;
+
+    // no handler fills the witness of the cycle script, so it fails and can not be estimated
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+    builder.add_output_and_data(output, ckb_types::packed::Bytes::default());
+    let err = match builder.build(&Default::default()) {
+        Ok(_) => panic!("the cycles must be estimated"),
+        Err(err) => err,
+    };
+    assert!(err
+        .to_string()
+        .contains("can not estimate the cycles of the script group"));
+}
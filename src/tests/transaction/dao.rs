@@ -0,0 +1,354 @@
+use std::sync::Arc;
+
+use ckb_dao_utils::pack_dao_data;
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, EpochNumberWithFraction, HeaderBuilder},
+    packed::{CellInput, CellOutput, WitnessArgs},
+    prelude::*,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::random_out_point,
+    tests::{
+        build_dao_script, build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT1_KEY, FEE_RATE,
+    },
+    transaction::{
+        builder::{CkbTransactionBuilder, DaoTransactionBuilder},
+        environment::BuildEnvironment,
+        handler::{
+            dao::{DaoDepositContext, DaoPrepareContext, DaoWithdrawContext},
+            HandlerContexts,
+        },
+        input::InputIterator,
+        signer::{SignContexts, TransactionSigner},
+        TransactionBuilderConfiguration,
+    },
+    tx_builder::dao::{DaoDepositReceiver, DaoPrepareItem},
+    util::{calculate_dao_maximum_withdraw4, minimal_unlock_point},
+    NetworkInfo, Since, SinceType,
+};
+
+#[test]
+fn test_dao_deposit() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+            (sender.clone(), Some(300 * ONE_CKB)),
+        ],
+    );
+
+    let network_info = NetworkInfo::testnet();
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(network_info.clone()).unwrap();
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender.clone()],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let builder = DaoTransactionBuilder::new(configuration, iterator);
+
+    let mut contexts = HandlerContexts::default();
+    contexts.add_context(Box::new(DaoDepositContext::new(vec![
+        DaoDepositReceiver::new(sender.clone(), 120 * ONE_CKB),
+    ])));
+    let mut tx_with_groups = builder.build(&contexts).expect("build failed");
+
+    TransactionSigner::new(&network_info)
+        .sign_transaction(
+            &mut tx_with_groups,
+            &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+        )
+        .unwrap();
+
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(tx.header_deps().len(), 0);
+    assert_eq!(tx.cell_deps().len(), 2);
+    assert_eq!(tx.inputs().len(), 2);
+    for out_point in tx.input_pts_iter() {
+        assert_eq!(ctx.get_input(&out_point).unwrap().0.lock(), sender);
+    }
+    assert_eq!(tx.outputs().len(), 2);
+    let deposit_output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(sender.clone())
+        .type_(Some(build_dao_script()).pack())
+        .build();
+    assert_eq!(tx.output(0).unwrap(), deposit_output);
+    assert_eq!(tx.output(1).unwrap().lock(), sender);
+    let outputs_data = tx
+        .outputs_data()
+        .into_iter()
+        .map(|d| d.raw_data())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        outputs_data,
+        vec![Bytes::from(vec![0u8; 8]), Bytes::default()]
+    );
+
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_dao_prepare() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let mut ctx = init_context(Vec::new(), vec![(sender.clone(), Some(100 * ONE_CKB))]);
+
+    let deposit_point = (5, 5, 1000);
+    let deposit_number = deposit_point.0 * deposit_point.2 + deposit_point.1;
+    let deposit_point =
+        EpochNumberWithFraction::new(deposit_point.0, deposit_point.1, deposit_point.2);
+    let deposit_input = CellInput::new(random_out_point(), 0);
+    let deposit_output = CellOutput::new_builder()
+        .capacity((220 * ONE_CKB).pack())
+        .lock(sender.clone())
+        .type_(Some(build_dao_script()).pack())
+        .build();
+    let deposit_header = HeaderBuilder::default()
+        .epoch(deposit_point.full_value().pack())
+        .number(deposit_number.pack())
+        .build();
+    let deposit_block_hash = deposit_header.hash();
+    ctx.add_live_cell(
+        deposit_input.clone(),
+        deposit_output.clone(),
+        Bytes::from(vec![0u8; 8]),
+        Some(deposit_block_hash.clone()),
+    );
+    ctx.add_header(deposit_header);
+
+    let network_info = NetworkInfo::testnet();
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(network_info.clone()).unwrap();
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender.clone()],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let builder = DaoTransactionBuilder::new(configuration, iterator);
+
+    let mut contexts = HandlerContexts::default();
+    contexts.add_context(Box::new(DaoPrepareContext::new_with_resolvers(
+        vec![DaoPrepareItem::from(deposit_input.clone())],
+        Box::new(ctx.clone()),
+        Box::new(ctx.clone()),
+    )));
+    let mut tx_with_groups = builder.build(&contexts).expect("build failed");
+
+    TransactionSigner::new(&network_info)
+        .sign_transaction(
+            &mut tx_with_groups,
+            &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+        )
+        .unwrap();
+
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(
+        tx.header_deps().into_iter().collect::<Vec<_>>(),
+        vec![deposit_block_hash]
+    );
+    assert_eq!(tx.cell_deps().len(), 2);
+    assert_eq!(tx.inputs().len(), 2);
+    assert_eq!(tx.inputs().get(0).unwrap(), deposit_input);
+    assert_eq!(tx.outputs().len(), 2);
+    assert_eq!(tx.output(0).unwrap(), deposit_output);
+    assert_eq!(tx.output(1).unwrap().lock(), sender);
+    let outputs_data = tx
+        .outputs_data()
+        .into_iter()
+        .map(|d| d.raw_data())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        outputs_data,
+        vec![
+            Bytes::from(deposit_number.to_le_bytes().to_vec()),
+            Bytes::default()
+        ]
+    );
+
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_dao_prepare_with_environment() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let mut ctx = init_context(Vec::new(), vec![(sender.clone(), Some(100 * ONE_CKB))]);
+
+    let deposit_number = 5 * 1000 + 5;
+    let deposit_input = CellInput::new(random_out_point(), 0);
+    let deposit_output = CellOutput::new_builder()
+        .capacity((220 * ONE_CKB).pack())
+        .lock(sender.clone())
+        .type_(Some(build_dao_script()).pack())
+        .build();
+    let deposit_header = HeaderBuilder::default()
+        .epoch(EpochNumberWithFraction::new(5, 5, 1000).full_value().pack())
+        .number(deposit_number.pack())
+        .build();
+    let deposit_block_hash = deposit_header.hash();
+    ctx.add_live_cell(
+        deposit_input.clone(),
+        deposit_output.clone(),
+        Bytes::from(vec![0u8; 8]),
+        Some(deposit_block_hash.clone()),
+    );
+    ctx.add_header(deposit_header);
+
+    let network_info = NetworkInfo::testnet();
+    let mut configuration =
+        TransactionBuilderConfiguration::new_with_network(network_info.clone()).unwrap();
+    // the handler resolves the deposit cell and header with the environment
+    configuration.set_environment(BuildEnvironment::new(
+        Arc::new(ctx.clone()),
+        Arc::new(ctx.clone()),
+        Arc::new(ctx.clone()),
+    ));
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender.clone()],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let builder = DaoTransactionBuilder::new(configuration, iterator);
+
+    let mut contexts = HandlerContexts::default();
+    contexts.add_context(Box::new(DaoPrepareContext::new_with_environment(vec![
+        DaoPrepareItem::from(deposit_input.clone()),
+    ])));
+    let mut tx_with_groups = builder.build(&contexts).expect("build failed");
+
+    TransactionSigner::new(&network_info)
+        .sign_transaction(
+            &mut tx_with_groups,
+            &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+        )
+        .unwrap();
+
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(
+        tx.header_deps().into_iter().collect::<Vec<_>>(),
+        vec![deposit_block_hash]
+    );
+    assert_eq!(tx.inputs().get(0).unwrap(), deposit_input);
+    assert_eq!(tx.output(0).unwrap(), deposit_output);
+
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_dao_withdraw() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let mut ctx = init_context(Vec::new(), vec![(sender.clone(), Some(100 * ONE_CKB))]);
+
+    let (deposit_point, prepare_point) = ((5, 5, 1000), (184, 4, 1000));
+    let deposit_number = deposit_point.0 * deposit_point.2 + deposit_point.1;
+    let prepare_number = prepare_point.0 * prepare_point.2 + prepare_point.1;
+    let deposit_point =
+        EpochNumberWithFraction::new(deposit_point.0, deposit_point.1, deposit_point.2);
+    let prepare_point =
+        EpochNumberWithFraction::new(prepare_point.0, prepare_point.1, prepare_point.2);
+    let deposit_header = HeaderBuilder::default()
+        .epoch(deposit_point.full_value().pack())
+        .number(deposit_number.pack())
+        .dao(pack_dao_data(
+            10_000_000_000_123_456,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        ))
+        .build();
+    let prepare_header = HeaderBuilder::default()
+        .epoch(prepare_point.full_value().pack())
+        .number(prepare_number.pack())
+        .dao(pack_dao_data(
+            10_000_000_001_123_456,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        ))
+        .build();
+    let deposit_block_hash = deposit_header.hash();
+    let prepare_block_hash = prepare_header.hash();
+
+    let unlock_point = minimal_unlock_point(&deposit_header, &prepare_header);
+    let since = Since::new(
+        SinceType::EpochNumberWithFraction,
+        unlock_point.full_value(),
+        false,
+    );
+    let prepare_out_point = random_out_point();
+    let prepare_input = CellInput::new(prepare_out_point.clone(), since.value());
+    let prepare_output = CellOutput::new_builder()
+        .capacity((220 * ONE_CKB).pack())
+        .lock(sender.clone())
+        .type_(Some(build_dao_script()).pack())
+        .build();
+    ctx.add_live_cell(
+        prepare_input.clone(),
+        prepare_output.clone(),
+        Bytes::from(deposit_number.to_le_bytes().to_vec()),
+        Some(prepare_block_hash.clone()),
+    );
+    ctx.add_header(deposit_header.clone());
+    ctx.add_header(prepare_header.clone());
+
+    let network_info = NetworkInfo::testnet();
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(network_info.clone()).unwrap();
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender.clone()],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let builder = DaoTransactionBuilder::new(configuration, iterator);
+
+    let mut contexts = HandlerContexts::default();
+    contexts.add_context(Box::new(DaoWithdrawContext::new_with_resolvers(
+        vec![prepare_out_point.clone()],
+        Box::new(ctx.clone()),
+        Box::new(ctx.clone()),
+    )));
+    let mut tx_with_groups = builder.build(&contexts).expect("build failed");
+
+    TransactionSigner::new(&network_info)
+        .sign_transaction(
+            &mut tx_with_groups,
+            &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+        )
+        .unwrap();
+
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(
+        tx.header_deps().into_iter().collect::<Vec<_>>(),
+        vec![deposit_block_hash, prepare_block_hash]
+    );
+    assert_eq!(tx.cell_deps().len(), 2);
+    assert_eq!(tx.inputs().len(), 1);
+    assert_eq!(tx.inputs().get(0).unwrap(), prepare_input);
+
+    // the DAO compensation goes to the change output
+    assert_eq!(tx.outputs().len(), 1);
+    assert_eq!(tx.output(0).unwrap().lock(), sender);
+    let occupied_capacity = prepare_output
+        .occupied_capacity(Capacity::bytes(8).unwrap())
+        .unwrap()
+        .as_u64();
+    let maximum_withdraw = calculate_dao_maximum_withdraw4(
+        &deposit_header,
+        &prepare_header,
+        &prepare_output,
+        occupied_capacity,
+    );
+    let change_capacity: u64 = tx.output(0).unwrap().capacity().unpack();
+    let fee = maximum_withdraw - change_capacity;
+    assert_eq!(tx.data().as_reader().serialized_size_in_block() as u64, fee);
+
+    let witness =
+        WitnessArgs::from_slice(tx.witnesses().get(0).unwrap().raw_data().as_ref()).unwrap();
+    assert_eq!(
+        witness.input_type().to_opt().unwrap().raw_data(),
+        Bytes::from(0u64.to_le_bytes().to_vec())
+    );
+    assert_eq!(witness.lock().to_opt().unwrap().raw_data().len(), 65);
+
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
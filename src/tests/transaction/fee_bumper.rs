@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use ckb_types::{packed::CellOutput, prelude::*};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::Context,
+    tests::{
+        build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, FEE_RATE,
+    },
+    transaction::{
+        builder::{ChangeDecision, CkbTransactionBuilder, SimpleTransactionBuilder},
+        environment::BuildEnvironment,
+        fee_bumper::{FeeBumper, DEFAULT_MIN_RBF_RATE},
+        input::InputIterator,
+        signer::{SignContexts, TransactionSigner},
+        TransactionBuilderConfiguration,
+    },
+    NetworkInfo, TransactionWithScriptGroups,
+};
+
+fn build_configuration(ctx: &Context) -> TransactionBuilderConfiguration {
+    let mut configuration =
+        TransactionBuilderConfiguration::new_with_network(NetworkInfo::testnet()).unwrap();
+    configuration.set_environment(BuildEnvironment::new(
+        Arc::new(ctx.clone()),
+        Arc::new(ctx.clone()),
+        Arc::new(ctx.clone()),
+    ));
+    configuration
+}
+
+fn build_signed_transfer(ctx: &Context, capacity: u64) -> TransactionWithScriptGroups {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let output = CellOutput::new_builder()
+        .capacity(capacity.pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let mut builder = SimpleTransactionBuilder::new(build_configuration(ctx), iterator);
+    builder.add_output_and_data(output, ckb_types::packed::Bytes::default());
+    let mut tx_with_groups = builder.build(&Default::default()).expect("build failed");
+    sign(&mut tx_with_groups);
+    tx_with_groups
+}
+
+fn sign(tx_with_groups: &mut TransactionWithScriptGroups) {
+    TransactionSigner::new(&NetworkInfo::testnet())
+        .sign_transaction(
+            tx_with_groups,
+            &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+        )
+        .unwrap();
+}
+
+fn tx_fee(ctx: &Context, tx_with_groups: &TransactionWithScriptGroups) -> u64 {
+    let tx = tx_with_groups.get_tx_view();
+    let inputs_capacity: u64 = tx
+        .input_pts_iter()
+        .map(|out_point| Unpack::<u64>::unpack(&ctx.get_input(&out_point).unwrap().0.capacity()))
+        .sum();
+    let outputs_capacity: u64 = tx
+        .outputs()
+        .into_iter()
+        .map(|output| Unpack::<u64>::unpack(&output.capacity()))
+        .sum();
+    inputs_capacity - outputs_capacity
+}
+
+#[test]
+fn test_bump_fee_from_change() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+        ],
+    );
+    let original = build_signed_transfer(&ctx, 120 * ONE_CKB);
+    let original_fee = tx_fee(&ctx, &original);
+
+    // the default offline environment of the configuration is enough
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(NetworkInfo::testnet()).unwrap();
+    let mut bumped = FeeBumper::new(original.clone(), configuration, Arc::new(ctx.clone()))
+        .build(&Default::default())
+        .expect("bump failed");
+    sign(&mut bumped);
+
+    let original_tx = original.get_tx_view();
+    let tx = bumped.get_tx_view().clone();
+    assert_ne!(tx.hash(), original_tx.hash());
+    assert_eq!(tx.inputs().as_bytes(), original_tx.inputs().as_bytes());
+    assert_eq!(tx.outputs().len(), 2);
+    assert_eq!(tx.output(0), original_tx.output(0));
+    assert_eq!(tx.output(1).unwrap().lock(), sender);
+
+    let tx_size = tx.data().as_reader().serialized_size_in_block() as u64;
+    let fee = tx_fee(&ctx, &bumped);
+    assert_eq!(fee, original_fee + tx_size * DEFAULT_MIN_RBF_RATE / 1000);
+    assert_eq!(
+        bumped.get_change_decision(),
+        Some(&ChangeDecision::ChangeOutput {
+            output_index: 1,
+            capacity: tx.output(1).unwrap().capacity().unpack(),
+        })
+    );
+
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_bump_fee_with_more_inputs() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+        ],
+    );
+    // the change is about 61.5 CKB, just enough for the change output
+    let original = build_signed_transfer(&ctx, 385 * ONE_CKB / 10);
+    assert_eq!(original.get_tx_view().inputs().len(), 1);
+
+    let mut fee_bumper = FeeBumper::new(
+        original.clone(),
+        build_configuration(&ctx),
+        Arc::new(ctx.clone()),
+    );
+    // the RBF increment is more than 1 CKB, the change output can not afford it
+    fee_bumper.set_min_rbf_rate(2 * ONE_CKB);
+    fee_bumper.set_input_iterator(InputIterator::new_with_cell_collector(
+        vec![sender.clone()],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    ));
+    let mut bumped = fee_bumper.build(&Default::default()).expect("bump failed");
+    sign(&mut bumped);
+
+    let original_tx = original.get_tx_view();
+    let tx = bumped.get_tx_view().clone();
+    assert_eq!(tx.inputs().len(), 2);
+    assert_eq!(tx.inputs().get(0), original_tx.inputs().get(0));
+    assert_eq!(tx.output(0), original_tx.output(0));
+    assert_eq!(bumped.get_script_groups().len(), 1);
+    assert_eq!(bumped.get_script_groups()[0].input_indices, vec![0, 1]);
+
+    let tx_size = tx.data().as_reader().serialized_size_in_block() as u64;
+    let fee = tx_fee(&ctx, &bumped);
+    assert_eq!(fee, tx_fee(&ctx, &original) + tx_size * 2 * ONE_CKB / 1000);
+
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
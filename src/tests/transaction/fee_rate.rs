@@ -0,0 +1,100 @@
+use ckb_jsonrpc_types::FeeRateStatistics;
+use ckb_types::{packed::CellOutput, prelude::*};
+use httpmock::prelude::*;
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::MockRpcResult,
+    tests::{build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT2_ARG},
+    transaction::{
+        builder::{CkbTransactionBuilder, SimpleTransactionBuilder},
+        fee_rate::{
+            BoundedFeeRatePolicy, FeeRatePolicy, FeeRateStatistic, FixedFeeRatePolicy,
+            StatisticsFeeRatePolicy,
+        },
+        input::InputIterator,
+        TransactionBuilderConfiguration,
+    },
+    NetworkInfo,
+};
+
+#[test]
+fn test_statistics_fee_rate_policy() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST)
+            .path("/")
+            .body_contains("get_fee_rate_statistics");
+        then.status(200).body(
+            MockRpcResult::new(Some(FeeRateStatistics {
+                mean: 3000.into(),
+                median: 2000.into(),
+            }))
+            .to_json(),
+        );
+    });
+
+    let mut policy = StatisticsFeeRatePolicy::new(server.base_url().as_str());
+    policy.set_target(Some(10));
+    assert_eq!(policy.fee_rate().unwrap(), 2000);
+    policy.set_statistic(FeeRateStatistic::Mean);
+    assert_eq!(policy.fee_rate().unwrap(), 3000);
+
+    let bounded = BoundedFeeRatePolicy::new(Box::new(policy), 1000, 2500).unwrap();
+    assert_eq!(bounded.fee_rate().unwrap(), 2500);
+}
+
+#[test]
+fn test_statistics_fee_rate_policy_fallback() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST)
+            .path("/")
+            .body_contains("get_fee_rate_statistics");
+        then.status(200)
+            .body(MockRpcResult::new(None::<FeeRateStatistics>).to_json());
+    });
+
+    let mut policy = StatisticsFeeRatePolicy::new(server.base_url().as_str());
+    policy.set_fallback_fee_rate(1500);
+    assert_eq!(policy.fee_rate().unwrap(), 1500);
+}
+
+#[test]
+fn test_bounded_fee_rate_policy() {
+    let floor = BoundedFeeRatePolicy::new(Box::new(FixedFeeRatePolicy::new(500)), 1000, 5000);
+    assert_eq!(floor.unwrap().fee_rate().unwrap(), 1000);
+    assert!(BoundedFeeRatePolicy::new(Box::new(FixedFeeRatePolicy::new(500)), 2000, 1000).is_err());
+}
+
+#[test]
+fn test_transfer_with_fee_rate_policy() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(Vec::new(), vec![(sender.clone(), Some(200 * ONE_CKB))]);
+
+    let output = CellOutput::new_builder()
+        .capacity((50 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+    let mut configuration =
+        TransactionBuilderConfiguration::new_with_network(NetworkInfo::testnet()).unwrap();
+    configuration.set_fee_rate_policy(Box::new(
+        BoundedFeeRatePolicy::new(Box::new(FixedFeeRatePolicy::new(10_000)), 1000, 3000).unwrap(),
+    ));
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender.clone()],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+    builder.add_output_and_data(output, ckb_types::packed::Bytes::default());
+    let tx_with_groups = builder.build(&Default::default()).expect("build failed");
+
+    assert_eq!(tx_with_groups.get_fee_rate(), Some(3000));
+    let tx = tx_with_groups.get_tx_view().clone();
+    let change_capacity: u64 = tx.output(1).unwrap().capacity().unpack();
+    let fee = (200 - 50) * ONE_CKB - change_capacity;
+    let tx_size = tx.data().as_reader().serialized_size_in_block() as u64;
+    // the placeholder witness is filled, so the size is the same after signing
+    assert_eq!(fee, tx_size * 3000 / 1000);
+}
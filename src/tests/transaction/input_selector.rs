@@ -0,0 +1,225 @@
+use ckb_types::{core::TransactionView, packed::CellOutput, prelude::*};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::Context,
+    tests::{
+        build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, FEE_RATE,
+    },
+    transaction::{
+        builder::{ChangeDecision, CkbTransactionBuilder, SimpleTransactionBuilder},
+        input::{
+            BranchAndBoundSelector, InputIterator, InputSelector, LargestFirstSelector,
+            MaxInputCountSelector, RandomImproveSelector, SmallestFirstSelector, TransactionInput,
+        },
+        signer::{SignContexts, TransactionSigner},
+        TransactionBuilderConfiguration,
+    },
+    NetworkInfo,
+};
+
+const CAPACITIES: [u64; 5] = [300, 100, 62, 500, 150];
+
+fn collect_candidates() -> Vec<TransactionInput> {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        CAPACITIES
+            .iter()
+            .map(|capacity| (sender.clone(), Some(capacity * ONE_CKB)))
+            .collect(),
+    );
+    InputIterator::new_with_cell_collector(
+        vec![sender],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    )
+    .collect::<Result<Vec<_>, _>>()
+    .unwrap()
+}
+
+fn capacities_in_ckb(inputs: &[TransactionInput]) -> Vec<u64> {
+    inputs
+        .iter()
+        .map(|input| Unpack::<u64>::unpack(&input.previous_output().capacity()) / ONE_CKB)
+        .collect()
+}
+
+#[test]
+fn test_largest_and_smallest_first() {
+    let candidates = collect_candidates();
+    assert_eq!(capacities_in_ckb(&candidates), CAPACITIES.to_vec());
+
+    let selected = LargestFirstSelector.select(candidates.clone(), 120 * ONE_CKB);
+    assert_eq!(capacities_in_ckb(&selected), vec![500, 300, 150, 100, 62]);
+
+    let selected = SmallestFirstSelector.select(candidates, 120 * ONE_CKB);
+    assert_eq!(capacities_in_ckb(&selected), vec![62, 100, 150, 300, 500]);
+}
+
+#[test]
+fn test_branch_and_bound() {
+    let candidates = collect_candidates();
+
+    // 150 + 62 = 212
+    let selected = BranchAndBoundSelector::new(ONE_CKB).select(candidates.clone(), 212 * ONE_CKB);
+    assert_eq!(capacities_in_ckb(&selected), vec![150, 62, 500, 300, 100]);
+
+    // 500 + 100 = 600 is the only match in [599, 600]
+    let selected = BranchAndBoundSelector::new(ONE_CKB).select(candidates.clone(), 599 * ONE_CKB);
+    assert_eq!(capacities_in_ckb(&selected), vec![500, 100, 300, 150, 62]);
+
+    // no match, fallback to largest first
+    let selected = BranchAndBoundSelector::new(0).select(candidates, ONE_CKB);
+    assert_eq!(capacities_in_ckb(&selected), vec![500, 300, 150, 100, 62]);
+}
+
+#[test]
+fn test_random_improve() {
+    let candidates = collect_candidates();
+    let mut sorted_capacities = CAPACITIES.to_vec();
+    sorted_capacities.sort_unstable();
+    for seed in 0..32 {
+        let selected =
+            RandomImproveSelector::new_with_seed(seed).select(candidates.clone(), 200 * ONE_CKB);
+        let capacities = capacities_in_ckb(&selected);
+        let mut selected_capacities = capacities.clone();
+        selected_capacities.sort_unstable();
+        assert_eq!(selected_capacities, sorted_capacities);
+
+        // the selection is deterministic with the same seed
+        let selected_again =
+            RandomImproveSelector::new_with_seed(seed).select(candidates.clone(), 200 * ONE_CKB);
+        assert_eq!(capacities_in_ckb(&selected_again), capacities);
+    }
+}
+
+#[test]
+fn test_max_input_count() {
+    let candidates = collect_candidates();
+    let mut selector = MaxInputCountSelector::new(Box::new(SmallestFirstSelector), 2);
+    let selected = selector.select(candidates, 120 * ONE_CKB);
+    assert_eq!(capacities_in_ckb(&selected), vec![62, 100]);
+}
+
+#[test]
+fn test_transfer_with_input_selector() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        CAPACITIES
+            .iter()
+            .map(|capacity| (sender.clone(), Some(capacity * ONE_CKB)))
+            .collect(),
+    );
+    let network_info = NetworkInfo::testnet();
+    let output = CellOutput::new_builder()
+        .capacity((400 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+
+    let build = |selector: Box<dyn InputSelector>| {
+        let configuration =
+            TransactionBuilderConfiguration::new_with_network(network_info.clone()).unwrap();
+        let iterator = InputIterator::new_with_cell_collector(
+            vec![sender.clone()],
+            Box::new(ctx.to_live_cells_context()) as Box<_>,
+        );
+        let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+        builder.add_output_and_data(output.clone(), ckb_types::packed::Bytes::default());
+        builder.set_input_selector(selector);
+        builder.build(&Default::default())
+    };
+
+    let mut tx_with_groups = build(Box::new(LargestFirstSelector)).expect("build failed");
+    TransactionSigner::new(&network_info)
+        .sign_transaction(
+            &mut tx_with_groups,
+            &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+        )
+        .unwrap();
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(tx.inputs().len(), 1);
+    assert_eq!(
+        ctx.get_input(&tx.inputs().get(0).unwrap().previous_output())
+            .unwrap()
+            .0
+            .capacity(),
+        (500 * ONE_CKB).pack()
+    );
+    assert_eq!(tx.outputs().len(), 2);
+    assert_eq!(tx.output(0).unwrap(), output);
+    ctx.verify(tx, FEE_RATE).unwrap();
+
+    // 62 + 100 + 150 can not pay for the output
+    let selector = MaxInputCountSelector::new(Box::new(SmallestFirstSelector), 3);
+    assert!(build(Box::new(selector)).is_err());
+
+    // only 300 and 100 are collected as the candidates, they can not pay for the fee
+    let mut selector = BranchAndBoundSelector::new(ONE_CKB);
+    selector.max_candidates = Some(2);
+    assert!(build(Box::new(selector)).is_err());
+
+    // 300 + 100 can not pay for the fee without change, so the candidates are not matched and
+    // the largest one pays with a change output, the last cell 150 is not collected
+    selector.max_candidates = Some(4);
+    let tx_with_groups = build(Box::new(selector)).expect("build failed");
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(input_capacities(&ctx, &tx), vec![500]);
+    assert_eq!(tx.outputs().len(), 2);
+}
+
+#[test]
+fn test_transfer_with_exact_match() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        CAPACITIES
+            .iter()
+            .map(|capacity| (sender.clone(), Some(capacity * ONE_CKB)))
+            .collect(),
+    );
+    let network_info = NetworkInfo::testnet();
+    let output = CellOutput::new_builder()
+        .capacity((399 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(network_info.clone()).unwrap();
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+    builder.add_output_and_data(output.clone(), ckb_types::packed::Bytes::default());
+    builder.set_input_selector(Box::new(BranchAndBoundSelector::new(ONE_CKB)));
+    let mut tx_with_groups = builder.build(&Default::default()).expect("build failed");
+
+    // 300 + 100 pays for the output and the fee within the tolerance, the rest is merged into the fee
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(input_capacities(&ctx, &tx), vec![300, 100]);
+    assert_eq!(tx.outputs().len(), 1);
+    assert_eq!(tx.output(0).unwrap(), output);
+    let extra_fee = match tx_with_groups.get_change_decision() {
+        Some(ChangeDecision::MergedIntoFee { capacity }) => *capacity,
+        decision => panic!("unexpected change decision: {:?}", decision),
+    };
+    assert!(extra_fee < ONE_CKB);
+
+    TransactionSigner::new(&network_info)
+        .sign_transaction(
+            &mut tx_with_groups,
+            &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+        )
+        .unwrap();
+    ctx.verify(tx_with_groups.get_tx_view().clone(), FEE_RATE)
+        .unwrap();
+}
+
+fn input_capacities(ctx: &Context, tx: &TransactionView) -> Vec<u64> {
+    tx.input_pts_iter()
+        .map(|out_point| Unpack::<u64>::unpack(&ctx.get_input(&out_point).unwrap().0.capacity()))
+        .map(|capacity| capacity / ONE_CKB)
+        .collect()
+}
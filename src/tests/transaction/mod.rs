@@ -1,2 +1,17 @@
+pub mod batch;
+pub mod cell_cache;
+pub mod consolidation;
+pub mod cpfp;
+pub mod cycle;
+pub mod dao;
+pub mod fee_bumper;
+pub mod fee_rate;
+pub mod input_selector;
+pub mod multisig;
+pub mod omnilock;
+pub mod portable;
+pub mod script_registry;
 pub mod sighash;
+pub mod since;
+pub mod sudt;
 pub mod typeid;
@@ -0,0 +1,148 @@
+use ckb_types::{
+    bytes::Bytes,
+    core::TransactionView,
+    packed::{CellOutput, Script},
+    prelude::*,
+    H160, H256,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    tests::{
+        build_sighash_script, init_context, ACCOUNT0_ARG, ACCOUNT0_KEY, ACCOUNT1_ARG, ACCOUNT1_KEY,
+        ACCOUNT2_ARG, ACCOUNT2_KEY, FEE_RATE,
+    },
+    traits::{SecpCkbRawKeySigner, Signer},
+    transaction::{
+        builder::{CkbTransactionBuilder, SimpleTransactionBuilder},
+        handler::HandlerContexts,
+        input::InputIterator,
+        signer::{
+            multisig::{
+                add_signature, get_signatures, is_threshold_reached, multisig_message,
+                normalize_signatures,
+            },
+            SignContexts, TransactionSigner,
+        },
+        TransactionBuilderConfiguration,
+    },
+    unlock::MultisigConfig,
+    NetworkInfo, ScriptGroup, TransactionWithScriptGroups,
+};
+
+fn sign(tx: &TransactionView, message: &[u8], key: &H256, arg: &H160) -> Bytes {
+    let key = secp256k1::SecretKey::from_slice(key.as_bytes()).unwrap();
+    SecpCkbRawKeySigner::new_with_secret_keys(vec![key])
+        .sign(arg.as_bytes(), message, true, tx)
+        .unwrap()
+}
+
+#[test]
+fn test_aggregate_multisig_signatures() {
+    // the first cosigner is required
+    let cfg = MultisigConfig::new_with(
+        vec![
+            ACCOUNT0_ARG.clone(),
+            ACCOUNT1_ARG.clone(),
+            ACCOUNT2_ARG.clone(),
+        ],
+        1,
+        2,
+    )
+    .unwrap();
+    let sender = Script::from(&cfg);
+    let ctx = init_context(Vec::new(), vec![(sender.clone(), Some(200 * ONE_CKB))]);
+
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(NetworkInfo::testnet()).unwrap();
+    let mut iterator = InputIterator::new_with_cell_collector(
+        Vec::new(),
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    iterator.add_multisig_lock(&cfg, None);
+    let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    builder.add_output_and_data(output, ckb_types::packed::Bytes::default());
+    let tx_with_groups: TransactionWithScriptGroups = builder
+        .build(&HandlerContexts::new_multisig(cfg.clone()))
+        .unwrap();
+    let script_group: ScriptGroup = tx_with_groups
+        .get_script_groups()
+        .iter()
+        .find(|group| group.script == sender)
+        .cloned()
+        .unwrap();
+    let unsigned_tx = tx_with_groups.get_tx_view().clone();
+    let message = multisig_message(&unsigned_tx, &script_group, &cfg).unwrap();
+    let signatures = [
+        sign(&unsigned_tx, &message, &ACCOUNT0_KEY, &ACCOUNT0_ARG),
+        sign(&unsigned_tx, &message, &ACCOUNT1_KEY, &ACCOUNT1_ARG),
+        sign(&unsigned_tx, &message, &ACCOUNT2_KEY, &ACCOUNT2_ARG),
+    ];
+
+    // the signatures are collected from the cosigners in any order
+    let mut tx = unsigned_tx.clone();
+    for index in [2, 1] {
+        tx = add_signature(&tx, &script_group, &cfg, signatures[index].clone()).unwrap();
+        assert!(!is_threshold_reached(&tx, &script_group, &cfg).unwrap());
+    }
+    assert_eq!(
+        get_signatures(&tx, &script_group, &cfg).unwrap(),
+        vec![signatures[1].clone(), signatures[2].clone()]
+    );
+    // the required first cosigner replaces the last one
+    tx = add_signature(&tx, &script_group, &cfg, signatures[0].clone()).unwrap();
+    assert!(is_threshold_reached(&tx, &script_group, &cfg).unwrap());
+    assert_eq!(
+        get_signatures(&tx, &script_group, &cfg).unwrap(),
+        vec![signatures[0].clone(), signatures[1].clone()]
+    );
+    ctx.verify(tx, FEE_RATE).unwrap();
+
+    let invalid = Bytes::from(vec![1u8; 65]);
+    assert!(add_signature(&unsigned_tx, &script_group, &cfg, invalid).is_err());
+
+    // the signatures put by the signer are normalized in the cosigners' order
+    let mut signed = tx_with_groups.clone();
+    let signer = TransactionSigner::new(&NetworkInfo::testnet());
+    for key in [&ACCOUNT1_KEY, &ACCOUNT0_KEY] {
+        signer
+            .sign_transaction(
+                &mut signed,
+                &SignContexts::new_multisig_h256(key, cfg.clone()).unwrap(),
+            )
+            .unwrap();
+    }
+    let tx = signed.get_tx_view().clone();
+    assert_eq!(
+        get_signatures(&tx, &script_group, &cfg).unwrap(),
+        vec![signatures[1].clone(), signatures[0].clone()]
+    );
+    let tx = normalize_signatures(&tx, &script_group, &cfg).unwrap();
+    assert_eq!(
+        get_signatures(&tx, &script_group, &cfg).unwrap(),
+        vec![signatures[0].clone(), signatures[1].clone()]
+    );
+    ctx.verify(tx, FEE_RATE).unwrap();
+
+    // a signature from the signer and one added later
+    let mut signed = tx_with_groups;
+    signer
+        .sign_transaction(
+            &mut signed,
+            &SignContexts::new_multisig_h256(&ACCOUNT2_KEY, cfg.clone()).unwrap(),
+        )
+        .unwrap();
+    let tx = add_signature(
+        signed.get_tx_view(),
+        &script_group,
+        &cfg,
+        signatures[0].clone(),
+    )
+    .unwrap();
+    assert!(is_threshold_reached(&tx, &script_group, &cfg).unwrap());
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
@@ -0,0 +1,239 @@
+use ckb_crypto::secp::SECP256K1;
+use ckb_types::{
+    packed::{CellOutput, WitnessArgs},
+    prelude::*,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::random_out_point,
+    tests::{
+        build_sighash_script, init_context,
+        omni_lock::{build_omnilock_script, OMNILOCK_BIN},
+        ACCOUNT0_ARG, ACCOUNT0_KEY, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, FEE_RATE,
+    },
+    traits::CellDepResolver,
+    transaction::{
+        builder::{CkbTransactionBuilder, SimpleTransactionBuilder},
+        handler::{
+            omnilock::{OmniLockScriptContext, OmniLockScriptHandler},
+            HandlerContexts,
+        },
+        input::InputIterator,
+        signer::{merge::merge_transaction, SignContexts, TransactionSigner},
+        TransactionBuilderConfiguration,
+    },
+    tx_builder::TxBuilderError,
+    types::omni_lock::OmniLockWitnessLock,
+    unlock::{MultisigConfig, OmniLockConfig, OmniUnlockMode},
+    util::blake160,
+    NetworkInfo, ScriptId, TransactionWithScriptGroups,
+};
+
+#[test]
+fn test_omnilock_transfer_from_sighash() {
+    let sender_key = secp256k1::SecretKey::from_slice(ACCOUNT0_KEY.as_bytes()).unwrap();
+    let pubkey = secp256k1::PublicKey::from_secret_key(&SECP256K1, &sender_key);
+    let cfg = OmniLockConfig::new_pubkey_hash(blake160(&pubkey.serialize()));
+    let unlock_mode = OmniUnlockMode::Normal;
+    let sender = build_omnilock_script(&cfg);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(
+        vec![(OMNILOCK_BIN, true)],
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+            (sender.clone(), Some(300 * ONE_CKB)),
+        ],
+    );
+
+    let network_info = NetworkInfo::testnet();
+    let mut configuration =
+        TransactionBuilderConfiguration::new_with_network(network_info).unwrap();
+    // the test OmniLock script is deployed by data hash in a dep group
+    let omnilock_cell_dep = ctx.resolve(&sender).unwrap();
+    configuration.register_script_handler(Box::new(OmniLockScriptHandler::new_with_cell_deps(
+        vec![omnilock_cell_dep],
+        ScriptId::from(&sender),
+    )));
+    // the signer of the custom OmniLock handler is derived from the configuration
+    let signer = TransactionSigner::new_with_configuration(&configuration);
+
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender.clone()],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+    builder.add_output_and_data(output.clone(), ckb_types::packed::Bytes::default());
+
+    let mut contexts = HandlerContexts::default();
+    contexts.add_context(Box::new(OmniLockScriptContext::new(
+        cfg.clone(),
+        unlock_mode,
+    )));
+    let mut tx_with_groups = builder.build(&contexts).expect("build failed");
+
+    let signed_groups = signer
+        .sign_transaction(
+            &mut tx_with_groups,
+            &SignContexts::new_omnilock(vec![sender_key], cfg, unlock_mode),
+        )
+        .unwrap();
+    assert_eq!(signed_groups, vec![0]);
+
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(tx.header_deps().len(), 0);
+    assert_eq!(tx.cell_deps().len(), 1);
+    assert_eq!(tx.inputs().len(), 2);
+    for out_point in tx.input_pts_iter() {
+        assert_eq!(ctx.get_input(&out_point).unwrap().0.lock(), sender);
+    }
+    assert_eq!(tx.outputs().len(), 2);
+    assert_eq!(tx.output(0).unwrap(), output);
+    assert_eq!(tx.output(1).unwrap().lock(), sender);
+    let witnesses = tx
+        .witnesses()
+        .into_iter()
+        .map(|w| w.raw_data())
+        .collect::<Vec<_>>();
+    assert_eq!(witnesses.len(), 2);
+    assert!(WitnessArgs::from_slice(witnesses[0].as_ref())
+        .unwrap()
+        .lock()
+        .is_some());
+    assert!(witnesses[1].is_empty());
+
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_omnilock_merge_multisig_signatures() {
+    let multisig_config = MultisigConfig::new_with(
+        vec![
+            ACCOUNT0_ARG.clone(),
+            ACCOUNT1_ARG.clone(),
+            ACCOUNT2_ARG.clone(),
+        ],
+        0,
+        2,
+    )
+    .unwrap();
+    let cfg = OmniLockConfig::new_multisig(multisig_config.clone());
+    let unlock_mode = OmniUnlockMode::Normal;
+    let sender = build_omnilock_script(&cfg);
+    let ctx = init_context(
+        vec![(OMNILOCK_BIN, true)],
+        vec![(sender.clone(), Some(300 * ONE_CKB))],
+    );
+
+    let mut configuration =
+        TransactionBuilderConfiguration::new_with_network(NetworkInfo::testnet()).unwrap();
+    configuration.register_script_handler(Box::new(OmniLockScriptHandler::new_with_cell_deps(
+        vec![ctx.resolve(&sender).unwrap()],
+        ScriptId::from(&sender),
+    )));
+    let signer = TransactionSigner::new_with_configuration(&configuration);
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender.clone()],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    builder.add_output_and_data(output, ckb_types::packed::Bytes::default());
+    let mut contexts = HandlerContexts::default();
+    contexts.add_context(Box::new(OmniLockScriptContext::new(
+        cfg.clone(),
+        unlock_mode,
+    )));
+    let unsigned = builder.build(&contexts).expect("build failed");
+
+    // the second cosigner signs first
+    let mut copies = Vec::new();
+    for key in [&ACCOUNT1_KEY, &ACCOUNT0_KEY] {
+        let key = secp256k1::SecretKey::from_slice(key.as_bytes()).unwrap();
+        let mut copy = unsigned.clone();
+        signer
+            .sign_transaction(
+                &mut copy,
+                &SignContexts::new_omnilock(vec![key], cfg.clone(), unlock_mode),
+            )
+            .unwrap();
+        copies.push(copy);
+    }
+    let mut merged = copies[0].clone();
+    merge_transaction(&mut merged, &copies[1]).unwrap();
+
+    // the signatures are sorted by the cosigners' order in the multisig config
+    let config_len = multisig_config.to_witness_data().len();
+    let signatures = |tx_with_groups: &TransactionWithScriptGroups| {
+        let witness = tx_with_groups.get_tx_view().witnesses().get(0).unwrap();
+        let lock = WitnessArgs::from_slice(&witness.raw_data())
+            .unwrap()
+            .lock()
+            .to_opt()
+            .unwrap()
+            .raw_data();
+        let signature = OmniLockWitnessLock::from_slice(&lock)
+            .unwrap()
+            .signature()
+            .to_opt()
+            .unwrap()
+            .raw_data();
+        signature.slice(config_len..)
+    };
+    let mut expected = signatures(&copies[1]).slice(..65).to_vec();
+    expected.extend_from_slice(&signatures(&copies[0])[..65]);
+    assert_eq!(signatures(&merged).to_vec(), expected);
+
+    ctx.verify(merged.get_tx_view().clone(), FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_omnilock_rce_cells_without_admin_flag() {
+    let sender_key = secp256k1::SecretKey::from_slice(ACCOUNT0_KEY.as_bytes()).unwrap();
+    let pubkey = secp256k1::PublicKey::from_secret_key(&SECP256K1, &sender_key);
+    let cfg = OmniLockConfig::new_pubkey_hash(blake160(&pubkey.serialize()));
+    let sender = build_omnilock_script(&cfg);
+    let ctx = init_context(
+        vec![(OMNILOCK_BIN, true)],
+        vec![(sender.clone(), Some(300 * ONE_CKB))],
+    );
+
+    let mut configuration =
+        TransactionBuilderConfiguration::new_with_network(NetworkInfo::testnet()).unwrap();
+    configuration.register_script_handler(Box::new(OmniLockScriptHandler::new_with_cell_deps(
+        vec![ctx.resolve(&sender).unwrap()],
+        ScriptId::from(&sender),
+    )));
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender.clone()],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(build_sighash_script(ACCOUNT2_ARG))
+        .build();
+    builder.add_output_and_data(output, ckb_types::packed::Bytes::default());
+
+    // the RCE cells are rejected rather than added as cell deps of a non-admin OmniLock
+    let mut context = OmniLockScriptContext::new(cfg, OmniUnlockMode::Normal);
+    context.set_rce_cells(vec![random_out_point()]);
+    let mut contexts = HandlerContexts::default();
+    contexts.add_context(Box::new(context));
+    let Err(err) = builder.build(&contexts) else {
+        panic!("the RCE cells should be rejected");
+    };
+    assert!(
+        matches!(err, TxBuilderError::InvalidParameter(_)),
+        "{}",
+        err
+    );
+}
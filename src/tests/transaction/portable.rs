@@ -0,0 +1,186 @@
+use ckb_types::{
+    packed::{CellOutput, Script},
+    prelude::*,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    tests::{
+        build_sighash_script, init_context, ACCOUNT0_ARG, ACCOUNT0_KEY, ACCOUNT1_ARG, ACCOUNT1_KEY,
+        ACCOUNT2_ARG, ACCOUNT2_KEY, FEE_RATE,
+    },
+    transaction::{
+        builder::{CkbTransactionBuilder, SimpleTransactionBuilder},
+        handler::HandlerContexts,
+        input::InputIterator,
+        signer::{
+            merge::{merge_portable_transaction, TransactionMergeError},
+            multisig::get_signatures,
+            SignContexts, TransactionSigner,
+        },
+        TransactionBuilderConfiguration,
+    },
+    types::{portable_transaction::PORTABLE_TX_VERSION, PortableTxError},
+    unlock::MultisigConfig,
+    NetworkInfo, PortableTransaction,
+};
+
+fn build_multisig_tx() -> (
+    crate::test_util::Context,
+    MultisigConfig,
+    PortableTransaction,
+) {
+    let cfg = MultisigConfig::new_with(
+        vec![
+            ACCOUNT0_ARG.clone(),
+            ACCOUNT1_ARG.clone(),
+            ACCOUNT2_ARG.clone(),
+        ],
+        0,
+        2,
+    )
+    .unwrap();
+    let sender = Script::from(&cfg.to_address_payload(None));
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender, Some(200 * ONE_CKB)),
+        ],
+    );
+
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(NetworkInfo::testnet()).unwrap();
+    let mut iterator = InputIterator::new_with_cell_collector(
+        Vec::new(),
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    iterator.add_multisig_lock(&cfg, None);
+    let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+    builder.add_output_and_data(output, ckb_types::packed::Bytes::default());
+    let tx_with_groups = builder
+        .build(&HandlerContexts::new_multisig(cfg.clone()))
+        .unwrap();
+    let portable = PortableTransaction::new(tx_with_groups, &ctx).unwrap();
+    (ctx, cfg, portable)
+}
+
+#[test]
+fn test_portable_tx_round_trip() {
+    let (_, _, portable) = build_multisig_tx();
+    let tx = portable.tx_with_groups().get_tx_view().clone();
+    assert_eq!(portable.inputs().len(), tx.inputs().len());
+    // the secp256k1 dep group and its members
+    assert!(portable.cell_deps().len() > tx.cell_deps().len());
+
+    let json = portable.to_json().unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["version"], PORTABLE_TX_VERSION);
+    let from_json = PortableTransaction::from_json(&json).unwrap();
+    let from_molecule =
+        PortableTransaction::from_molecule_bytes(&portable.to_molecule_bytes()).unwrap();
+    for decoded in [&from_json, &from_molecule] {
+        assert_eq!(
+            decoded.tx_with_groups().get_tx_view().data().as_bytes(),
+            tx.data().as_bytes()
+        );
+        assert_eq!(
+            decoded.tx_with_groups().get_script_groups(),
+            portable.tx_with_groups().get_script_groups()
+        );
+        assert_eq!(decoded.inputs(), portable.inputs());
+        assert_eq!(decoded.cell_deps(), portable.cell_deps());
+    }
+    assert_eq!(
+        from_molecule.to_mock_tx().mock_info.cell_deps.len(),
+        portable.cell_deps().len()
+    );
+
+    let unsupported = json.replacen(
+        &format!("\"version\": {}", PORTABLE_TX_VERSION),
+        "\"version\": 99",
+        1,
+    );
+    assert!(matches!(
+        PortableTransaction::from_json(&unsupported),
+        Err(PortableTxError::Json(_))
+    ));
+}
+
+#[test]
+fn test_merge_multisig_signatures() {
+    let (ctx, cfg, portable) = build_multisig_tx();
+    let signer = TransactionSigner::new(&NetworkInfo::testnet());
+
+    // the cosigners sign their own copies received as json and molecule, the second cosigner
+    // signs first
+    let mut copies = Vec::new();
+    for (key, molecule) in [(&ACCOUNT1_KEY, false), (&ACCOUNT0_KEY, true)] {
+        let mut copy = if molecule {
+            PortableTransaction::from_molecule_bytes(&portable.to_molecule_bytes()).unwrap()
+        } else {
+            PortableTransaction::from_json(&portable.to_json().unwrap()).unwrap()
+        };
+        signer
+            .sign_transaction(
+                copy.tx_with_groups_mut(),
+                &SignContexts::new_multisig_h256(key, cfg.clone()).unwrap(),
+            )
+            .unwrap();
+        // one signature is not enough
+        assert!(ctx
+            .verify(copy.tx_with_groups().get_tx_view().clone(), FEE_RATE)
+            .is_err());
+        copies.push(copy);
+    }
+
+    let mut merged = portable.clone();
+    for copy in &copies {
+        merge_portable_transaction(&mut merged, copy).unwrap();
+    }
+    // merging the same signatures again changes nothing
+    merge_portable_transaction(&mut merged, &copies[0]).unwrap();
+    // the signatures are sorted by the cosigners' order in the multisig config
+    let script_group = &merged.tx_with_groups().get_script_groups()[0];
+    let signatures = |portable: &PortableTransaction| {
+        get_signatures(portable.tx_with_groups().get_tx_view(), script_group, &cfg).unwrap()
+    };
+    assert_eq!(
+        signatures(&merged),
+        vec![
+            signatures(&copies[1])[0].clone(),
+            signatures(&copies[0])[0].clone()
+        ]
+    );
+    ctx.verify(merged.tx_with_groups().get_tx_view().clone(), FEE_RATE)
+        .unwrap();
+
+    // the third signature exceeds the threshold
+    let mut third = portable.clone();
+    signer
+        .sign_transaction(
+            third.tx_with_groups_mut(),
+            &SignContexts::new_multisig_h256(&ACCOUNT2_KEY, cfg).unwrap(),
+        )
+        .unwrap();
+    let err = merge_portable_transaction(&mut merged, &third).unwrap_err();
+    assert!(matches!(err, TransactionMergeError::ConflictWitness(0)));
+
+    let (_, _, mut other) = build_multisig_tx();
+    let other_tx = other
+        .tx_with_groups()
+        .get_tx_view()
+        .as_advanced_builder()
+        .version(1u32.pack())
+        .build();
+    other.tx_with_groups_mut().set_tx_view(other_tx);
+    assert!(matches!(
+        merge_portable_transaction(&mut merged, &other).unwrap_err(),
+        TransactionMergeError::DifferentTransaction(..)
+    ));
+}
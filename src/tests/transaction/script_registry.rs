@@ -0,0 +1,96 @@
+use ckb_jsonrpc_types as json_types;
+use ckb_types::{core::BlockView, packed::CellOutput, prelude::*};
+
+use crate::{
+    constants::ONE_CKB,
+    tests::{
+        build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, FEE_RATE,
+        GENESIS_JSON,
+    },
+    traits::DefaultCellDepResolver,
+    transaction::{
+        builder::{CkbTransactionBuilder, SimpleTransactionBuilder},
+        input::InputIterator,
+        script_registry::ScriptRegistry,
+        signer::{SignContexts, TransactionSigner},
+        TransactionBuilderConfiguration,
+    },
+    NetworkInfo, NetworkType,
+};
+
+fn genesis_block() -> BlockView {
+    let genesis_block: json_types::BlockView = serde_json::from_str(GENESIS_JSON).unwrap();
+    genesis_block.into()
+}
+
+#[test]
+fn test_script_registry_from_genesis() {
+    let genesis_block = genesis_block();
+    let resolver = DefaultCellDepResolver::from_genesis(&genesis_block).unwrap();
+    let registry = ScriptRegistry::from_genesis(&genesis_block).unwrap();
+    assert_eq!(
+        registry.sighash_cell_deps,
+        Some(vec![resolver.sighash_dep().unwrap().0.clone().into()])
+    );
+    assert_eq!(
+        registry.multisig_cell_deps,
+        Some(vec![resolver.multisig_dep().unwrap().0.clone().into()])
+    );
+    assert_eq!(
+        registry.dao_cell_deps,
+        Some(vec![resolver.dao_dep().unwrap().0.clone().into()])
+    );
+    assert!(registry.sudt.is_none());
+    assert!(registry.omnilock.is_none());
+
+    let json = serde_json::to_string(&registry).unwrap();
+    assert_eq!(ScriptRegistry::from_json(&json).unwrap(), registry);
+    assert_eq!(ScriptRegistry::from_json("{}").unwrap(), Default::default());
+}
+
+#[test]
+fn test_transfer_from_sighash_on_dev_chain() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+        ],
+    );
+
+    let network_info = NetworkInfo::new(NetworkType::Dev, "http://127.0.0.1:8114".to_string());
+    assert!(TransactionBuilderConfiguration::new_with_network(network_info.clone()).is_err());
+    let configuration =
+        TransactionBuilderConfiguration::new_with_genesis(network_info.clone(), &genesis_block())
+            .unwrap();
+
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender.clone()],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+    builder.add_output_and_data(output.clone(), ckb_types::packed::Bytes::default());
+    let mut tx_with_groups = builder.build(&Default::default()).expect("build failed");
+
+    TransactionSigner::new(&network_info)
+        .sign_transaction(
+            &mut tx_with_groups,
+            &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+        )
+        .unwrap();
+
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(tx.cell_deps().len(), 1);
+    assert_eq!(tx.inputs().len(), 2);
+    assert_eq!(tx.outputs().len(), 2);
+    assert_eq!(tx.output(0).unwrap(), output);
+    assert_eq!(tx.output(1).unwrap().lock(), sender);
+
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
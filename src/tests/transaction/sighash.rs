@@ -1,4 +1,8 @@
-use ckb_types::{packed::CellOutput, prelude::*};
+use ckb_types::{
+    core::{EpochNumberWithFraction, HeaderBuilder},
+    packed::CellOutput,
+    prelude::*,
+};
 
 use crate::{
     constants::ONE_CKB,
@@ -6,12 +10,16 @@ use crate::{
         build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, FEE_RATE,
     },
     transaction::{
-        builder::{CkbTransactionBuilder, SimpleTransactionBuilder},
-        input::InputIterator,
+        builder::{
+            ChangeDecision, CkbTransactionBuilder, PinnedInputs, SimpleTransactionBuilder,
+            SmallChangePolicy,
+        },
+        input::{InputIterator, TransactionInput},
         signer::{SignContexts, TransactionSigner},
         TransactionBuilderConfiguration,
     },
-    NetworkInfo,
+    tx_builder::TxBuilderError,
+    NetworkInfo, TransactionWithScriptGroups,
 };
 
 #[test]
@@ -76,3 +84,212 @@ fn test_transfer_from_sighash() {
 
     ctx.verify(tx, FEE_RATE).unwrap();
 }
+
+#[test]
+fn test_transfer_from_sighash_with_pinned_input() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let mut ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+            (sender.clone(), Some(300 * ONE_CKB)),
+        ],
+    );
+    let pinned_out_point = ctx.inputs[2].input.previous_output();
+
+    // the block number of the pinned input is resolved with the header of its transaction
+    let header = HeaderBuilder::default()
+        .number(100.pack())
+        .epoch(
+            EpochNumberWithFraction::new(0, 100, 1000)
+                .full_value()
+                .pack(),
+        )
+        .build();
+    ctx.add_header(header.clone());
+    let mock_input = ctx.inputs[2].clone();
+    ctx.add_live_cell(
+        mock_input.input,
+        mock_input.output,
+        mock_input.data,
+        Some(header.hash()),
+    );
+    let pinned_input =
+        TransactionInput::new_with_out_point(pinned_out_point.clone(), 0, &ctx, &ctx).unwrap();
+    assert_eq!(pinned_input.live_cell.block_number, 100);
+
+    let network_info = NetworkInfo::testnet();
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(network_info.clone()).unwrap();
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender.clone()],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+    builder.add_output_and_data(output.clone(), ckb_types::packed::Bytes::default());
+    builder
+        .add_input_out_point(pinned_out_point.clone(), 0, &ctx, &ctx)
+        .unwrap();
+    let mut tx_with_groups = builder.build(&Default::default()).expect("build failed");
+
+    TransactionSigner::new(&network_info)
+        .sign_transaction(
+            &mut tx_with_groups,
+            &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+        )
+        .unwrap();
+
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(tx_with_groups.script_groups.len(), 1);
+    // the pinned input alone balances the transaction, and it's not collected again
+    assert_eq!(tx.inputs().len(), 1);
+    assert_eq!(
+        tx.inputs().get(0).unwrap().previous_output(),
+        pinned_out_point
+    );
+    assert_eq!(tx.outputs().len(), 2);
+    assert_eq!(tx.output(0).unwrap(), output);
+    let change_capacity: u64 = tx.output(1).unwrap().capacity().unpack();
+    assert!(change_capacity < (300 - 120) * ONE_CKB);
+
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_transfer_from_sighash_with_precise_fee() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(
+        Vec::new(),
+        vec![
+            (sender.clone(), Some(100 * ONE_CKB)),
+            (sender.clone(), Some(200 * ONE_CKB)),
+        ],
+    );
+
+    let network_info = NetworkInfo::testnet();
+    // only 10000 shannons are left for the fee after the 61 CKB change output,
+    // it's enough for the real transaction but not for the fixed `estimate_tx_size`
+    let output = CellOutput::new_builder()
+        .capacity((39 * ONE_CKB - 10_000).pack())
+        .lock(receiver)
+        .build();
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(network_info.clone()).unwrap();
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender.clone()],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+    builder.add_output_and_data(output.clone(), ckb_types::packed::Bytes::default());
+    let mut tx_with_groups = builder.build(&Default::default()).expect("build failed");
+
+    TransactionSigner::new(&network_info)
+        .sign_transaction(
+            &mut tx_with_groups,
+            &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+        )
+        .unwrap();
+
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(tx.inputs().len(), 1);
+    assert_eq!(tx.outputs().len(), 2);
+    assert_eq!(tx.output(0).unwrap(), output);
+    let change_capacity: u64 = tx.output(1).unwrap().capacity().unpack();
+    let fee = 100 * ONE_CKB - 39 * ONE_CKB + 10_000 - change_capacity;
+    assert_eq!(tx.data().as_reader().serialized_size_in_block() as u64, fee);
+    assert_eq!(
+        tx_with_groups.get_change_decision(),
+        Some(&ChangeDecision::ChangeOutput {
+            output_index: 1,
+            capacity: change_capacity
+        })
+    );
+
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_transfer_from_sighash_with_small_change() {
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let ctx = init_context(Vec::new(), vec![(sender.clone(), Some(100 * ONE_CKB))]);
+    let network_info = NetworkInfo::testnet();
+
+    let build = |policy: SmallChangePolicy, output_capacity: u64| {
+        let mut configuration =
+            TransactionBuilderConfiguration::new_with_network(network_info.clone()).unwrap();
+        configuration.small_change_policy = policy;
+        let iterator = InputIterator::new_with_cell_collector(
+            vec![sender.clone()],
+            Box::new(ctx.to_live_cells_context()) as Box<_>,
+        );
+        let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+        let output = CellOutput::new_builder()
+            .capacity(output_capacity.pack())
+            .lock(receiver.clone())
+            .build();
+        builder.add_output_and_data(output, ckb_types::packed::Bytes::default());
+        builder.build(&Default::default())
+    };
+    let sign = |mut tx_with_groups: TransactionWithScriptGroups| {
+        TransactionSigner::new(&network_info)
+            .sign_transaction(
+                &mut tx_with_groups,
+                &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+            )
+            .unwrap();
+        tx_with_groups
+    };
+
+    // the 30 CKB change is less than the 61 CKB occupied capacity of the change output
+    assert!(build(SmallChangePolicy::CollectMoreInputs, 70 * ONE_CKB).is_err());
+    let policy = SmallChangePolicy::MergeIntoFee {
+        max_extra_fee: ONE_CKB,
+    };
+    assert!(build(policy, 70 * ONE_CKB).is_err());
+
+    // merge the dust into the fee
+    let tx_with_groups = sign(build(policy, 100 * ONE_CKB - 10_000).unwrap());
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(tx.outputs().len(), 1);
+    let fee = tx.data().as_reader().serialized_size_in_block() as u64;
+    assert_eq!(
+        tx_with_groups.get_change_decision(),
+        Some(&ChangeDecision::MergedIntoFee {
+            capacity: 10_000 - fee
+        })
+    );
+    ctx.verify(tx, FEE_RATE).unwrap();
+
+    // add the small change to the first output
+    let policy = SmallChangePolicy::AddToOutput { output_index: 0 };
+    let tx_with_groups = sign(build(policy, 70 * ONE_CKB).unwrap());
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(tx.outputs().len(), 1);
+    let fee = tx.data().as_reader().serialized_size_in_block() as u64;
+    assert_eq!(
+        tx.output(0).unwrap().capacity(),
+        (100 * ONE_CKB - fee).pack()
+    );
+    assert_eq!(
+        tx_with_groups.get_change_decision(),
+        Some(&ChangeDecision::AddedToOutput {
+            output_index: 0,
+            capacity: 30 * ONE_CKB - fee
+        })
+    );
+    ctx.verify(tx, FEE_RATE).unwrap();
+
+    let policy = SmallChangePolicy::AddToOutput { output_index: 1 };
+    assert!(matches!(
+        build(policy, 70 * ONE_CKB),
+        Err(TxBuilderError::NoOutputForSmallChange)
+    ));
+}
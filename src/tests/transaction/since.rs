@@ -0,0 +1,168 @@
+use std::sync::Arc;
+
+use ckb_types::{
+    bytes::Bytes,
+    core::{EpochNumberWithFraction, HeaderBuilder},
+    packed::{CellInput, CellOutput, Script},
+    prelude::*,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::{random_out_point, Context},
+    tests::{
+        build_sighash_script, init_context, ACCOUNT0_ARG, ACCOUNT0_KEY, ACCOUNT1_ARG, ACCOUNT1_KEY,
+        ACCOUNT2_ARG, FEE_RATE,
+    },
+    traits::LiveCell,
+    transaction::{
+        builder::{CkbTransactionBuilder, SimpleTransactionBuilder},
+        handler::HandlerContexts,
+        input::{InputIterator, SinceChecker},
+        signer::{SignContexts, TransactionSigner},
+        TransactionBuilderConfiguration,
+    },
+    unlock::MultisigConfig,
+    NetworkInfo, Since, SinceType,
+};
+
+fn build_tip_checker(epoch_number: u64) -> SinceChecker {
+    let tip_header = HeaderBuilder::default()
+        .number((epoch_number * 1000 + 1).pack())
+        .epoch(
+            EpochNumberWithFraction::new(epoch_number, 1, 1000)
+                .full_value()
+                .pack(),
+        )
+        .build();
+    SinceChecker::new(tip_header)
+}
+
+#[test]
+fn test_transfer_from_time_locked_multisig() {
+    let cfg = MultisigConfig::new_with(
+        vec![
+            ACCOUNT0_ARG.clone(),
+            ACCOUNT1_ARG.clone(),
+            ACCOUNT2_ARG.clone(),
+        ],
+        0,
+        2,
+    )
+    .unwrap();
+    let since_epoch = 10;
+    let sender = Script::from(&cfg.to_address_payload(Some(since_epoch)));
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    // the inputs are unlocked with the since of the lock args
+    let since = Since::new_absolute_epoch(since_epoch).value();
+    let mut ctx = init_context(Vec::new(), Vec::new());
+    for capacity in [100 * ONE_CKB, 200 * ONE_CKB] {
+        let output = CellOutput::new_builder()
+            .capacity(capacity.pack())
+            .lock(sender.clone())
+            .build();
+        ctx.add_live_cell(
+            CellInput::new(random_out_point(), since),
+            output,
+            Bytes::default(),
+            None,
+        );
+    }
+
+    let network_info = NetworkInfo::testnet();
+    let output = CellOutput::new_builder()
+        .capacity((120 * ONE_CKB).pack())
+        .lock(receiver)
+        .build();
+    let build = |tip_epoch: u64| {
+        let configuration =
+            TransactionBuilderConfiguration::new_with_network(network_info.clone()).unwrap();
+        let mut iterator = InputIterator::new_with_cell_collector(
+            Vec::new(),
+            Box::new(ctx.to_live_cells_context()) as Box<_>,
+        );
+        assert_eq!(iterator.add_multisig_lock(&cfg, Some(since_epoch)), sender);
+        iterator.set_since_checker(Some(build_tip_checker(tip_epoch)));
+        let mut builder = SimpleTransactionBuilder::new(configuration, iterator);
+        builder.add_output_and_data(output.clone(), ckb_types::packed::Bytes::default());
+        builder.build(&HandlerContexts::new_multisig(cfg.clone()))
+    };
+
+    // all the cells are time-locked before the epoch
+    let Err(err) = build(since_epoch - 1) else {
+        panic!("the time-locked cells should not be collected");
+    };
+    assert!(err.to_string().contains("time-locked"), "{}", err);
+
+    let mut tx_with_groups = build(since_epoch).expect("build failed");
+    let signer = TransactionSigner::new(&network_info);
+    for key in [&ACCOUNT0_KEY, &ACCOUNT1_KEY] {
+        signer
+            .sign_transaction(
+                &mut tx_with_groups,
+                &SignContexts::new_multisig_h256(key, cfg.clone()).unwrap(),
+            )
+            .unwrap();
+    }
+
+    let tx = tx_with_groups.get_tx_view().clone();
+    assert_eq!(tx.inputs().len(), 2);
+    for input in tx.inputs() {
+        assert_eq!(Unpack::<u64>::unpack(&input.since()), since);
+    }
+    assert_eq!(tx.output(0).unwrap(), output);
+    assert_eq!(tx.output(1).unwrap().lock(), sender);
+
+    ctx.verify(tx, FEE_RATE).unwrap();
+}
+
+#[test]
+fn test_since_checker_median_time() {
+    // a block every 10 seconds, the tip timestamp is far ahead of the median time
+    let mut ctx = Context::default();
+    let header = |number: u64, timestamp: u64| {
+        HeaderBuilder::default()
+            .number(number.pack())
+            .epoch(
+                EpochNumberWithFraction::new(0, number, 1000)
+                    .full_value()
+                    .pack(),
+            )
+            .timestamp(timestamp.pack())
+            .build()
+    };
+    for number in 0..40 {
+        ctx.add_header(header(number, number * 10_000));
+    }
+    let tip_header = header(40, 1_000_000_000);
+    let checker = SinceChecker::new_with_header_dep_resolver(tip_header.clone(), Arc::new(ctx));
+    let live_cell = LiveCell {
+        output: CellOutput::default(),
+        output_data: Bytes::default(),
+        out_point: random_out_point(),
+        block_number: 10,
+        tx_index: 1,
+    };
+    let is_spendable = |checker: &SinceChecker, seconds: u64, relative: bool| {
+        let since = Since::new(SinceType::Timestamp, seconds, relative).value();
+        checker.is_spendable(since, &live_cell).unwrap()
+    };
+
+    // the median time of the blocks 4..=40 is the timestamp of the block 22
+    assert!(is_spendable(&checker, 220, false));
+    assert!(!is_spendable(&checker, 221, false));
+
+    // the relative timestamp starts from the median time of the blocks 0..=9
+    assert!(is_spendable(&checker, 170, true));
+    assert!(!is_spendable(&checker, 171, true));
+
+    // the median time can not be resolved without a header dep resolver
+    let checker = SinceChecker::new(tip_header);
+    assert!(!is_spendable(&checker, 1, false));
+
+    // the block number is checked against the next block of the tip
+    let since = Since::new(SinceType::BlockNumber, 41, false).value();
+    assert!(checker.is_spendable(since, &live_cell).unwrap());
+    let since = Since::new(SinceType::BlockNumber, 42, false).value();
+    assert!(!checker.is_spendable(since, &live_cell).unwrap());
+}
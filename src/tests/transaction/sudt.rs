@@ -0,0 +1,203 @@
+use ckb_types::{
+    bytes::Bytes,
+    core::ScriptHashType,
+    h256,
+    packed::{CellInput, CellOutput, Script},
+    prelude::*,
+};
+
+use crate::{
+    constants::ONE_CKB,
+    test_util::{random_out_point, Context},
+    tests::{
+        build_sighash_script, init_context, ACCOUNT1_ARG, ACCOUNT1_KEY, ACCOUNT2_ARG, ACCOUNT3_ARG,
+    },
+    transaction::{
+        builder::{ChangeDecision, CkbTransactionBuilder, SudtTransactionBuilder},
+        input::InputIterator,
+        signer::{SignContexts, TransactionSigner},
+        TransactionBuilderConfiguration,
+    },
+    NetworkInfo,
+};
+
+fn build_testnet_sudt_script(owner: &Script) -> Script {
+    Script::new_builder()
+        .code_hash(
+            h256!("0xc5e5dcf215925f7ef4dfaf5f4b4f105bc321c02776d6e7d52a1db3fcd9d011a4").pack(),
+        )
+        .hash_type(ScriptHashType::Type.into())
+        .args(owner.calc_script_hash().as_bytes().pack())
+        .build()
+}
+
+fn add_sudt_cell(ctx: &mut Context, lock: &Script, sudt_type_script: &Script, amount: u128) {
+    let output = CellOutput::new_builder()
+        .capacity((142 * ONE_CKB).pack())
+        .lock(lock.clone())
+        .type_(Some(sudt_type_script.clone()).pack())
+        .build();
+    ctx.add_live_cell(
+        CellInput::new(random_out_point(), 0),
+        output,
+        Bytes::from(amount.to_le_bytes().to_vec()),
+        None,
+    );
+}
+
+#[test]
+fn test_sudt_transfer_with_change() {
+    let owner = build_sighash_script(ACCOUNT3_ARG);
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let sudt_type_script = build_testnet_sudt_script(&owner);
+    let mut ctx = init_context(Vec::new(), vec![(sender.clone(), Some(200 * ONE_CKB))]);
+    add_sudt_cell(&mut ctx, &sender, &sudt_type_script, 300);
+    add_sudt_cell(&mut ctx, &sender, &sudt_type_script, 500);
+
+    let network_info = NetworkInfo::testnet();
+    let build = |amount: u128| {
+        let configuration =
+            TransactionBuilderConfiguration::new_with_network(network_info.clone()).unwrap();
+        let iterator = InputIterator::new_with_cell_collector(
+            vec![sender.clone()],
+            Box::new(ctx.to_live_cells_context()) as Box<_>,
+        );
+        let mut builder =
+            SudtTransactionBuilder::new(configuration, iterator, owner.clone(), false).unwrap();
+        builder.add_output(receiver.clone(), amount);
+        builder.build(&Default::default())
+    };
+
+    // only 800 sUDT in the inputs
+    assert!(build(801).is_err());
+
+    let mut tx_with_groups = build(600).expect("build failed");
+    TransactionSigner::new(&network_info)
+        .sign_transaction(
+            &mut tx_with_groups,
+            &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+        )
+        .unwrap();
+    let tx = tx_with_groups.get_tx_view().clone();
+
+    // the sUDT cells can't pay the capacity of the change cell, a plain CKB cell is collected
+    assert_eq!(tx.inputs().len(), 3);
+    let inputs: Vec<_> = tx
+        .input_pts_iter()
+        .map(|out_point| ctx.get_input(&out_point).unwrap())
+        .collect();
+    assert_eq!(inputs[0].0.type_().to_opt(), Some(sudt_type_script.clone()));
+    assert_eq!(inputs[1].0.type_().to_opt(), Some(sudt_type_script.clone()));
+    assert!(inputs[2].0.type_().is_none());
+
+    assert_eq!(tx.outputs().len(), 2);
+    assert_eq!(tx.output(0).unwrap().lock(), receiver);
+    assert_eq!(tx.output(1).unwrap().lock(), sender);
+    for output in tx.outputs() {
+        assert_eq!(output.type_().to_opt(), Some(sudt_type_script.clone()));
+    }
+    let outputs_data = tx
+        .outputs_data()
+        .into_iter()
+        .map(|data| data.raw_data())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        outputs_data,
+        vec![
+            Bytes::from(600u128.to_le_bytes().to_vec()),
+            Bytes::from(200u128.to_le_bytes().to_vec())
+        ]
+    );
+
+    let change_capacity: u64 = tx.output(1).unwrap().capacity().unpack();
+    let fee = (142 * 2 + 200 - 142) * ONE_CKB - change_capacity;
+    assert_eq!(tx.data().as_reader().serialized_size_in_block() as u64, fee);
+    assert_eq!(
+        tx_with_groups.get_change_decision(),
+        Some(&ChangeDecision::ChangeOutput {
+            output_index: 1,
+            capacity: change_capacity
+        })
+    );
+
+    let sudt_group = tx_with_groups
+        .get_script_groups()
+        .iter()
+        .find(|group| group.script == sudt_type_script)
+        .unwrap();
+    assert_eq!(sudt_group.input_indices, vec![0, 1]);
+    assert_eq!(sudt_group.output_indices, vec![0, 1]);
+}
+
+#[test]
+fn test_sudt_transfer_without_sudt_change() {
+    let owner = build_sighash_script(ACCOUNT3_ARG);
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let receiver = build_sighash_script(ACCOUNT2_ARG);
+    let sudt_type_script = build_testnet_sudt_script(&owner);
+    let mut ctx = init_context(Vec::new(), vec![(sender.clone(), Some(200 * ONE_CKB))]);
+    add_sudt_cell(&mut ctx, &sender, &sudt_type_script, 300);
+    add_sudt_cell(&mut ctx, &sender, &sudt_type_script, 500);
+
+    let network_info = NetworkInfo::testnet();
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(network_info.clone()).unwrap();
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender.clone()],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let mut builder = SudtTransactionBuilder::new(configuration, iterator, owner, false).unwrap();
+    builder.add_output(receiver.clone(), 800);
+    let mut tx_with_groups = builder.build(&Default::default()).expect("build failed");
+    TransactionSigner::new(&network_info)
+        .sign_transaction(
+            &mut tx_with_groups,
+            &SignContexts::new_sighash_h256(vec![ACCOUNT1_KEY.clone()]).unwrap(),
+        )
+        .unwrap();
+    let tx = tx_with_groups.get_tx_view().clone();
+
+    // all the sUDT is sent, the change is a plain CKB cell which is paid by the sUDT cells
+    assert_eq!(tx.inputs().len(), 2);
+    assert_eq!(tx.outputs().len(), 2);
+    assert_eq!(
+        tx.output(0).unwrap().type_().to_opt(),
+        Some(sudt_type_script.clone())
+    );
+    let change_output = tx.output(1).unwrap();
+    assert_eq!(change_output.lock(), sender);
+    assert!(change_output.type_().is_none());
+    assert!(tx.outputs_data().get(1).unwrap().raw_data().is_empty());
+
+    let sudt_group = tx_with_groups
+        .get_script_groups()
+        .iter()
+        .find(|group| group.script == sudt_type_script)
+        .unwrap();
+    assert_eq!(sudt_group.output_indices, vec![0]);
+}
+
+#[test]
+fn test_sudt_amount_overflow() {
+    let owner = build_sighash_script(ACCOUNT3_ARG);
+    let sender = build_sighash_script(ACCOUNT1_ARG);
+    let sudt_type_script = build_testnet_sudt_script(&owner);
+    let mut ctx = init_context(Vec::new(), vec![(sender.clone(), Some(200 * ONE_CKB))]);
+    add_sudt_cell(&mut ctx, &sender, &sudt_type_script, 300);
+    add_sudt_cell(&mut ctx, &sender, &sudt_type_script, u128::MAX);
+
+    let configuration =
+        TransactionBuilderConfiguration::new_with_network(NetworkInfo::testnet()).unwrap();
+    let iterator = InputIterator::new_with_cell_collector(
+        vec![sender],
+        Box::new(ctx.to_live_cells_context()) as Box<_>,
+    );
+    let mut builder = SudtTransactionBuilder::new(configuration, iterator, owner, false).unwrap();
+    builder.add_output(build_sighash_script(ACCOUNT2_ARG), u128::MAX);
+    let err = match builder.build(&Default::default()) {
+        Ok(_) => panic!("the sUDT amount overflows"),
+        Err(err) => err,
+    };
+    assert!(err.to_string().contains("sudt amount overflow"));
+}
@@ -0,0 +1,475 @@
+//! Async versions of the default trait implementations, use the async ckb jsonrpc clients as backend.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::StreamExt;
+use lru::LruCache;
+use parking_lot::Mutex;
+
+use ckb_jsonrpc_types::{self as json_types, Either};
+use ckb_types::{
+    bytes::Bytes,
+    core::{HeaderView, TransactionView},
+    packed::{Byte32, CellOutput, OutPoint, Transaction, TransactionReader},
+    prelude::*,
+};
+
+use super::{
+    offchain_impls::CollectResult, OffchainCellCollector, OffchainTransactionDependencyProvider,
+};
+use crate::rpc::ckb_indexer::{Order, SearchKey, Tip};
+use crate::rpc::{AsyncCkbRpcClient, AsyncIndexerRpcClient, PageSize};
+use crate::traits::{
+    AsyncCellCollector, AsyncHeaderDepResolver, AsyncTransactionDependencyProvider,
+    CellCollectorError, CellQueryOptions, LiveCell, QueryOrder, TransactionDependencyError,
+    TransactionDependencyProvider,
+};
+use crate::util::get_max_mature_number_async;
+
+/// A header_dep resolver use async ckb jsonrpc client as backend
+pub struct AsyncDefaultHeaderDepResolver {
+    ckb_client: AsyncCkbRpcClient,
+}
+impl AsyncDefaultHeaderDepResolver {
+    pub fn new(ckb_client: &str) -> AsyncDefaultHeaderDepResolver {
+        let ckb_client = AsyncCkbRpcClient::new(ckb_client);
+        AsyncDefaultHeaderDepResolver { ckb_client }
+    }
+}
+impl AsyncHeaderDepResolver for AsyncDefaultHeaderDepResolver {
+    fn resolve_by_tx<'a>(
+        &'a self,
+        tx_hash: &'a Byte32,
+    ) -> BoxFuture<'a, Result<Option<HeaderView>, anyhow::Error>> {
+        async move {
+            if let Some(block_hash) = self
+                .ckb_client
+                .get_transaction(tx_hash.unpack())
+                .await
+                .map_err(|e| anyhow!(e))?
+                .and_then(|tx_with_status| tx_with_status.tx_status.block_hash)
+            {
+                Ok(self
+                    .ckb_client
+                    .get_header(block_hash)
+                    .await
+                    .map_err(|e| anyhow!(e))?
+                    .map(Into::into))
+            } else {
+                Ok(None)
+            }
+        }
+        .boxed()
+    }
+    fn resolve_by_number(
+        &self,
+        number: u64,
+    ) -> BoxFuture<'_, Result<Option<HeaderView>, anyhow::Error>> {
+        async move {
+            Ok(self
+                .ckb_client
+                .get_header_by_number(number.into())
+                .await
+                .map_err(|e| anyhow!(e))?
+                .map(Into::into))
+        }
+        .boxed()
+    }
+}
+
+/// A cell collector use ckb-indexer as backend through the async jsonrpc clients
+#[derive(Clone)]
+pub struct AsyncDefaultCellCollector {
+    indexer_client: AsyncIndexerRpcClient,
+    ckb_client: AsyncCkbRpcClient,
+    offchain: OffchainCellCollector,
+    acceptable_indexer_leftbehind: u64,
+}
+
+impl AsyncDefaultCellCollector {
+    pub fn new(ckb_client: &str) -> AsyncDefaultCellCollector {
+        let indexer_client = AsyncIndexerRpcClient::new(ckb_client);
+        let ckb_client = AsyncCkbRpcClient::new(ckb_client);
+        AsyncDefaultCellCollector {
+            indexer_client,
+            ckb_client,
+            offchain: OffchainCellCollector::default(),
+            acceptable_indexer_leftbehind: 1,
+        }
+    }
+
+    /// THe acceptable ckb-indexer leftbehind block number (default = 1)
+    pub fn acceptable_indexer_leftbehind(&self) -> u64 {
+        self.acceptable_indexer_leftbehind
+    }
+    /// Set the acceptable ckb-indexer leftbehind block number
+    pub fn set_acceptable_indexer_leftbehind(&mut self, value: u64) {
+        self.acceptable_indexer_leftbehind = value;
+    }
+
+    /// Same as `DefaultCellCollector::check_ckb_chain`, but waits with `tokio::time::sleep`.
+    pub async fn check_ckb_chain(&mut self) -> Result<(), CellCollectorError> {
+        let tip_number = self
+            .ckb_client
+            .get_tip_block_number()
+            .await
+            .map_err(|err| CellCollectorError::Internal(err.into()))?;
+
+        for _ in 0..100 {
+            match self
+                .indexer_client
+                .get_indexer_tip()
+                .await
+                .map_err(|err| CellCollectorError::Internal(err.into()))?
+            {
+                Some(Tip { block_number, .. }) => {
+                    if tip_number.value()
+                        > block_number.value() + self.acceptable_indexer_leftbehind
+                    {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                    } else {
+                        return Ok(());
+                    }
+                }
+                None => {
+                    return Err(CellCollectorError::Other(anyhow!(
+                        "ckb-indexer server not synced"
+                    )));
+                }
+            }
+        }
+        Err(CellCollectorError::Other(anyhow!(
+            "ckb-indexer server inconsistent with currently connected ckb node or not synced!"
+        )))
+    }
+}
+
+impl AsyncCellCollector for AsyncDefaultCellCollector {
+    fn collect_live_cells<'a>(
+        &'a mut self,
+        query: &'a CellQueryOptions,
+        apply_changes: bool,
+    ) -> BoxFuture<'a, Result<(Vec<LiveCell>, u64), CellCollectorError>> {
+        async move {
+            let max_mature_number = get_max_mature_number_async(&self.ckb_client)
+                .await
+                .map_err(|err| CellCollectorError::Internal(anyhow!(err)))?;
+
+            self.offchain.max_mature_number = max_mature_number;
+            let tip_num = self
+                .ckb_client
+                .get_tip_block_number()
+                .await
+                .map_err(|err| CellCollectorError::Internal(anyhow!(err)))?
+                .value();
+            let CollectResult {
+                cells,
+                rest_cells,
+                mut total_capacity,
+            } = self.offchain.collect(query, tip_num);
+            let mut cells: Vec<_> = cells.into_iter().map(|c| c.0).collect();
+
+            if !query.is_satisfied(total_capacity, cells.len()) {
+                self.check_ckb_chain().await?;
+                let order = match query.order {
+                    QueryOrder::Asc => Order::Asc,
+                    QueryOrder::Desc => Order::Desc,
+                };
+                let mut ret_cells: HashMap<_, _> = cells
+                    .into_iter()
+                    .map(|c| (c.out_point.clone(), c))
+                    .collect();
+                let locked_cells = self.offchain.locked_cells.clone();
+                let search_key = SearchKey::from(query.clone());
+                const MAX_LIMIT: u32 = 4096;
+                let page_size = PageSize::adaptive(query.limit.unwrap_or(16), MAX_LIMIT);
+                let mut cells_stream = self.indexer_client.iter_cells(search_key, order, page_size);
+                while let Some(cell) = cells_stream.next().await {
+                    let cell = cell.map_err(|err| CellCollectorError::Internal(err.into()))?;
+                    let live_cell = LiveCell::from(cell);
+                    if !query.match_cell(&live_cell, max_mature_number)
+                        || locked_cells.contains_key(&(
+                            live_cell.out_point.tx_hash().unpack(),
+                            live_cell.out_point.index().unpack(),
+                        ))
+                    {
+                        continue;
+                    }
+                    let capacity: u64 = live_cell.output.capacity().unpack();
+                    // use cell from indexer to replace offchain cell
+                    if ret_cells
+                        .insert(live_cell.out_point.clone(), live_cell)
+                        .is_none()
+                    {
+                        total_capacity += capacity;
+                    }
+                    if query.is_satisfied(total_capacity, ret_cells.len()) {
+                        break;
+                    }
+                }
+                cells = ret_cells.into_values().collect();
+            }
+            if apply_changes {
+                self.offchain.live_cells = rest_cells;
+                for cell in &cells {
+                    self.lock_cell(cell.out_point.clone(), tip_num)?;
+                }
+            }
+            Ok((cells, total_capacity))
+        }
+        .boxed()
+    }
+
+    fn lock_cell(
+        &mut self,
+        out_point: OutPoint,
+        tip_block_number: u64,
+    ) -> Result<(), CellCollectorError> {
+        self.offchain.lock_cell(out_point, tip_block_number)
+    }
+    fn apply_tx(
+        &mut self,
+        tx: Transaction,
+        tip_block_number: u64,
+    ) -> Result<(), CellCollectorError> {
+        self.offchain.apply_tx(tx, tip_block_number)
+    }
+    fn reset(&mut self) {
+        self.offchain.reset();
+    }
+}
+
+struct AsyncDefaultTxDepProviderInner {
+    tx_cache: LruCache<Byte32, TransactionView>,
+    cell_cache: LruCache<OutPoint, (CellOutput, Bytes)>,
+    header_cache: LruCache<Byte32, HeaderView>,
+    offchain_cache: OffchainTransactionDependencyProvider,
+}
+
+/// A transaction dependency provider use async ckb rpc client as backend, and with LRU cache supported
+#[derive(Clone)]
+pub struct AsyncDefaultTransactionDependencyProvider {
+    rpc_client: AsyncCkbRpcClient,
+    // the lock is never held across an await point
+    inner: Arc<Mutex<AsyncDefaultTxDepProviderInner>>,
+}
+
+impl AsyncDefaultTransactionDependencyProvider {
+    /// Arguments:
+    ///   * `url` is the ckb http jsonrpc server url
+    ///   * When `cache_capacity` is 0 for not using cache.
+    pub fn new(url: &str, cache_capacity: usize) -> AsyncDefaultTransactionDependencyProvider {
+        let rpc_client = AsyncCkbRpcClient::new(url);
+        let inner = AsyncDefaultTxDepProviderInner {
+            tx_cache: LruCache::new(cache_capacity),
+            cell_cache: LruCache::new(cache_capacity),
+            header_cache: LruCache::new(cache_capacity),
+            offchain_cache: OffchainTransactionDependencyProvider::new(),
+        };
+        AsyncDefaultTransactionDependencyProvider {
+            rpc_client,
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    pub fn apply_tx(
+        &mut self,
+        tx: Transaction,
+        tip_block_number: u64,
+    ) -> Result<(), TransactionDependencyError> {
+        let mut inner = self.inner.lock();
+        inner.offchain_cache.apply_tx(tx, tip_block_number)?;
+        Ok(())
+    }
+
+    pub async fn get_cell_with_data(
+        &self,
+        out_point: &OutPoint,
+    ) -> Result<(CellOutput, Bytes), TransactionDependencyError> {
+        let cached = self.inner.lock().cell_cache.get(out_point).cloned();
+        if let Some(pair) = cached {
+            return Ok(pair);
+        }
+
+        let cell_with_status = self
+            .rpc_client
+            .get_live_cell(out_point.clone().into(), true)
+            .await
+            .map_err(|err| TransactionDependencyError::Other(err.into()))?;
+        if cell_with_status.status != "live" {
+            return Err(TransactionDependencyError::Other(anyhow!(
+                "invalid cell status: {:?}",
+                cell_with_status.status
+            )));
+        }
+        let cell = cell_with_status.cell.unwrap();
+        let output = CellOutput::from(cell.output);
+        let output_data = cell.data.unwrap().content.into_bytes();
+        self.inner
+            .lock()
+            .cell_cache
+            .put(out_point.clone(), (output.clone(), output_data.clone()));
+        Ok((output, output_data))
+    }
+
+    /// Fetch the cells of all inputs of the transaction in one batch request and put them
+    /// into the cache. The inputs failed to fetch are left to the later queries.
+    pub async fn prefetch_inputs(
+        &self,
+        tx: &TransactionView,
+    ) -> Result<(), TransactionDependencyError> {
+        let out_points: Vec<OutPoint> = {
+            let inner = self.inner.lock();
+            tx.input_pts_iter()
+                .filter(|out_point| {
+                    !inner.cell_cache.contains(out_point)
+                        && inner.offchain_cache.get_cell(out_point).is_err()
+                })
+                .collect()
+        };
+        let params = out_points
+            .iter()
+            .map(|out_point| (json_types::OutPoint::from(out_point.clone()), true))
+            .collect();
+        let results = self
+            .rpc_client
+            .post_batch::<_, json_types::CellWithStatus>("get_live_cell", params)
+            .await
+            .map_err(|err| TransactionDependencyError::Other(err.into()))?;
+        let mut inner = self.inner.lock();
+        for (out_point, result) in out_points.into_iter().zip(results) {
+            if let Ok(json_types::CellWithStatus {
+                cell:
+                    Some(json_types::CellInfo {
+                        output,
+                        data: Some(data),
+                    }),
+                status,
+            }) = result
+            {
+                if status == "live" {
+                    let pair = (CellOutput::from(output), data.content.into_bytes());
+                    inner.cell_cache.put(out_point, pair);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AsyncTransactionDependencyProvider for AsyncDefaultTransactionDependencyProvider {
+    fn get_transaction<'a>(
+        &'a self,
+        tx_hash: &'a Byte32,
+    ) -> BoxFuture<'a, Result<TransactionView, TransactionDependencyError>> {
+        async move {
+            {
+                let mut inner = self.inner.lock();
+                if let Some(tx) = inner.tx_cache.get(tx_hash) {
+                    return Ok(tx.clone());
+                }
+                let ret = inner.offchain_cache.get_transaction(tx_hash);
+                if ret.is_ok() {
+                    return ret;
+                }
+            }
+            let tx_with_status = self
+                .rpc_client
+                .get_transaction(tx_hash.unpack())
+                .await
+                .map_err(|err| TransactionDependencyError::Other(err.into()))?
+                .ok_or_else(|| TransactionDependencyError::NotFound("transaction".to_string()))?;
+            if tx_with_status.tx_status.status != json_types::Status::Committed {
+                return Err(TransactionDependencyError::Other(anyhow!(
+                    "invalid transaction status: {:?}",
+                    tx_with_status.tx_status
+                )));
+            }
+            let tx = match tx_with_status.transaction.unwrap().inner {
+                Either::Left(t) => Transaction::from(t.inner).into_view(),
+                Either::Right(bytes) => TransactionReader::from_slice(bytes.as_bytes())
+                    .map(|reader| reader.to_entity().into_view())
+                    .map_err(|err| anyhow!("invalid molecule encoded TransactionView: {}", err))?,
+            };
+            self.inner.lock().tx_cache.put(tx_hash.clone(), tx.clone());
+            Ok(tx)
+        }
+        .boxed()
+    }
+    fn get_cell<'a>(
+        &'a self,
+        out_point: &'a OutPoint,
+    ) -> BoxFuture<'a, Result<CellOutput, TransactionDependencyError>> {
+        async move {
+            let ret = self.inner.lock().offchain_cache.get_cell(out_point);
+            if ret.is_ok() {
+                return ret;
+            }
+            self.get_cell_with_data(out_point)
+                .await
+                .map(|(output, _)| output)
+        }
+        .boxed()
+    }
+    fn get_cell_data<'a>(
+        &'a self,
+        out_point: &'a OutPoint,
+    ) -> BoxFuture<'a, Result<Bytes, TransactionDependencyError>> {
+        async move {
+            let ret = self.inner.lock().offchain_cache.get_cell_data(out_point);
+            if ret.is_ok() {
+                return ret;
+            }
+            self.get_cell_with_data(out_point)
+                .await
+                .map(|(_, output_data)| output_data)
+        }
+        .boxed()
+    }
+    fn get_header<'a>(
+        &'a self,
+        block_hash: &'a Byte32,
+    ) -> BoxFuture<'a, Result<HeaderView, TransactionDependencyError>> {
+        async move {
+            let cached = self.inner.lock().header_cache.get(block_hash).cloned();
+            if let Some(header) = cached {
+                return Ok(header);
+            }
+            let header = self
+                .rpc_client
+                .get_header(block_hash.unpack())
+                .await
+                .map_err(|err| TransactionDependencyError::Other(err.into()))?
+                .map(HeaderView::from)
+                .ok_or_else(|| TransactionDependencyError::NotFound("header".to_string()))?;
+            self.inner
+                .lock()
+                .header_cache
+                .put(block_hash.clone(), header.clone());
+            Ok(header)
+        }
+        .boxed()
+    }
+
+    fn get_block_extension<'a>(
+        &'a self,
+        block_hash: &'a Byte32,
+    ) -> BoxFuture<'a, Result<Option<ckb_types::packed::Bytes>, TransactionDependencyError>> {
+        async move {
+            let block = self
+                .rpc_client
+                .get_block(block_hash.unpack())
+                .await
+                .map_err(|err| TransactionDependencyError::Other(err.into()))?;
+            match block {
+                Some(block) => Ok(block.extension.map(ckb_types::packed::Bytes::from)),
+                None => Ok(None),
+            }
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,336 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use anyhow::anyhow;
+use ckb_jsonrpc_types::Status;
+use ckb_types::{
+    core::TransactionView,
+    packed::{Byte32, OutPoint, Transaction},
+    prelude::*,
+};
+use parking_lot::Mutex;
+
+use super::{CellCollector, CellCollectorError, CellQueryOptions, DefaultCellCollector, LiveCell};
+use crate::CkbRpcClient;
+
+/// The transaction which is sent but not committed yet.
+struct PendingTransaction {
+    tx_hash: Byte32,
+    outputs: Vec<LiveCell>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    /// The cells spent by the pending transactions, or locked by [`CellCollector::lock_cell`] without a transaction
+    locked_cells: HashMap<OutPoint, Option<Byte32>>,
+    /// The pending transactions in the applied order
+    pending_txs: Vec<PendingTransaction>,
+    /// The cells reserved by the clones, which are not applied yet
+    reserved_cells: HashSet<OutPoint>,
+}
+
+impl CacheState {
+    fn spendable_outputs(&self) -> impl Iterator<Item = &LiveCell> {
+        self.pending_txs
+            .iter()
+            .flat_map(|tx| tx.outputs.iter())
+            .filter(move |cell| self.is_available(&cell.out_point))
+    }
+
+    fn is_available(&self, out_point: &OutPoint) -> bool {
+        !self.locked_cells.contains_key(out_point) && !self.reserved_cells.contains(out_point)
+    }
+
+    fn remove_tx(&mut self, tx_hash: &Byte32) -> Option<PendingTransaction> {
+        let index = self
+            .pending_txs
+            .iter()
+            .position(|tx| &tx.tx_hash == tx_hash)?;
+        Some(self.pending_txs.remove(index))
+    }
+}
+
+/// A wallet cell cache shared by the input iterators of the transactions built in the same process,
+/// e.g. a hot wallet sending many transactions in a block.
+///
+/// The inputs of the applied transactions are locked until the transactions are committed or rejected,
+/// and their outputs (e.g. the change outputs) are spendable before they are committed, so the transactions
+/// can be chained without double spending each other.
+///
+/// Cloning the cache shares the pending transactions and the locked cells, while the backend cell
+/// collector is cloned, so each clone can be used by an [`InputIterator`](crate::transaction::input::InputIterator).
+///
+/// A cell is reserved by a clone with [`WalletCellCache::reserve_cell`], the other clones skip it
+/// until the clone applies a transaction, is reset or is dropped, so the transactions built
+/// concurrently don't spend the same cells. The [`InputIterator`](crate::transaction::input::InputIterator)
+/// reserves the inputs it returns, and the transaction builders add the built transactions as pending
+/// transactions, which should be rejected by [`WalletCellCache::reject_tx`] if they are not sent.
+pub struct WalletCellCache {
+    collector: Box<dyn CellCollector + Send>,
+    state: Arc<Mutex<CacheState>>,
+    /// The cells reserved by this clone
+    reserved: HashSet<OutPoint>,
+    /// The pending outputs collected by this clone with `apply_changes`
+    collected: HashSet<OutPoint>,
+}
+
+impl Clone for WalletCellCache {
+    fn clone(&self) -> Self {
+        Self {
+            collector: dyn_clone::clone_box(&*self.collector),
+            state: Arc::clone(&self.state),
+            reserved: HashSet::new(),
+            collected: HashSet::new(),
+        }
+    }
+}
+
+impl Drop for WalletCellCache {
+    fn drop(&mut self) {
+        self.release_reserved();
+    }
+}
+
+impl WalletCellCache {
+    /// Create a cache with the backend cell collector, it collects the committed cells.
+    pub fn new(collector: Box<dyn CellCollector + Send>) -> Self {
+        Self {
+            collector,
+            state: Arc::new(Mutex::new(CacheState::default())),
+            reserved: HashSet::new(),
+            collected: HashSet::new(),
+        }
+    }
+
+    /// Create a cache backed by the [`DefaultCellCollector`].
+    pub fn new_with_url(url: &str) -> Self {
+        Self::new(Box::new(DefaultCellCollector::new(url)))
+    }
+
+    /// Add a transaction which is sent to the node, its inputs are locked and its outputs are spendable.
+    pub fn add_pending_tx(&self, tx: &TransactionView) {
+        let tx_hash = tx.hash();
+        let mut state = self.state.lock();
+        if state
+            .pending_txs
+            .iter()
+            .any(|pending| pending.tx_hash == tx_hash)
+        {
+            return;
+        }
+        for out_point in tx.input_pts_iter() {
+            state.locked_cells.insert(out_point, Some(tx_hash.clone()));
+        }
+        let outputs = tx
+            .outputs_with_data_iter()
+            .enumerate()
+            .map(|(index, (output, output_data))| LiveCell {
+                output,
+                output_data,
+                out_point: OutPoint::new(tx_hash.clone(), index as u32),
+                block_number: 0,
+                tx_index: 0,
+            })
+            .collect();
+        state
+            .pending_txs
+            .push(PendingTransaction { tx_hash, outputs });
+    }
+
+    /// The transaction is committed, its inputs are dead and its outputs are collected by the backend from now on.
+    pub fn commit_tx(&self, tx_hash: &Byte32) {
+        let mut state = self.state.lock();
+        if state.remove_tx(tx_hash).is_some() {
+            state
+                .locked_cells
+                .retain(|_, spent_by| spent_by.as_ref() != Some(tx_hash));
+        }
+    }
+
+    /// The transaction is rejected, its inputs are unlocked and its outputs are removed,
+    /// the pending transactions spending its outputs are rejected too.
+    pub fn reject_tx(&self, tx_hash: &Byte32) {
+        let mut state = self.state.lock();
+        let mut rejected = vec![tx_hash.clone()];
+        while let Some(tx_hash) = rejected.pop() {
+            let tx = match state.remove_tx(&tx_hash) {
+                Some(tx) => tx,
+                None => continue,
+            };
+            state
+                .locked_cells
+                .retain(|_, spent_by| spent_by.as_ref() != Some(&tx_hash));
+            for cell in tx.outputs {
+                if let Some(Some(child_hash)) = state.locked_cells.remove(&cell.out_point) {
+                    rejected.push(child_hash);
+                }
+            }
+        }
+    }
+
+    /// Query the status of the pending transactions by the `get_transaction` rpc, the committed
+    /// transactions are committed and the rejected (or unknown) ones are rejected.
+    ///
+    /// The transactions should be sent before calling it, otherwise they are unknown to the node.
+    pub fn update_status(&self, client: &CkbRpcClient) -> Result<(), CellCollectorError> {
+        for tx_hash in self.pending_tx_hashes() {
+            let status = client
+                .get_transaction_status(tx_hash.unpack())
+                .map_err(|err| CellCollectorError::Internal(anyhow!(err)))?
+                .tx_status
+                .status;
+            match status {
+                Status::Committed => self.commit_tx(&tx_hash),
+                Status::Rejected | Status::Unknown => {
+                    log::debug!("pending transaction {} is {:?}", tx_hash, status);
+                    self.reject_tx(&tx_hash)
+                }
+                Status::Pending | Status::Proposed => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The hashes of the pending transactions in the applied order.
+    pub fn pending_tx_hashes(&self) -> Vec<Byte32> {
+        let state = self.state.lock();
+        state
+            .pending_txs
+            .iter()
+            .map(|tx| tx.tx_hash.clone())
+            .collect()
+    }
+
+    /// The outputs of the pending transactions which are not spent by other pending transactions.
+    pub fn pending_outputs(&self) -> Vec<LiveCell> {
+        let state = self.state.lock();
+        state.spendable_outputs().cloned().collect()
+    }
+
+    pub fn is_locked(&self, out_point: &OutPoint) -> bool {
+        self.state.lock().locked_cells.contains_key(out_point)
+    }
+
+    /// Unlock the cell locked by [`CellCollector::lock_cell`] or a pending transaction.
+    pub fn unlock_cell(&self, out_point: &OutPoint) {
+        self.state.lock().locked_cells.remove(out_point);
+    }
+
+    /// Whether the cell is reserved by a clone of the cache and not released yet.
+    pub fn is_reserved(&self, out_point: &OutPoint) -> bool {
+        self.state.lock().reserved_cells.contains(out_point)
+    }
+
+    /// Reserve the cell for this clone, return false if it's locked or reserved by another clone.
+    ///
+    /// The cell is checked and reserved under the same lock, so the concurrent clones never reserve the same cell.
+    pub fn reserve_cell(&mut self, out_point: &OutPoint) -> bool {
+        if self.reserved.contains(out_point) {
+            return true;
+        }
+        let mut state = self.state.lock();
+        if !state.is_available(out_point) {
+            return false;
+        }
+        state.reserved_cells.insert(out_point.clone());
+        self.reserved.insert(out_point.clone());
+        true
+    }
+
+    /// Release the cell reserved by this clone, e.g. it's not used by the transaction.
+    pub fn release_cell(&mut self, out_point: &OutPoint) {
+        if self.reserved.remove(out_point) {
+            self.state.lock().reserved_cells.remove(out_point);
+        }
+    }
+
+    fn release_reserved(&mut self) {
+        if self.reserved.is_empty() {
+            return;
+        }
+        let mut state = self.state.lock();
+        for out_point in self.reserved.drain() {
+            state.reserved_cells.remove(&out_point);
+        }
+    }
+}
+
+impl CellCollector for WalletCellCache {
+    fn collect_live_cells(
+        &mut self,
+        query: &CellQueryOptions,
+        apply_changes: bool,
+    ) -> Result<(Vec<LiveCell>, u64), CellCollectorError> {
+        let mut cells: Vec<LiveCell> = Vec::new();
+        let mut total_capacity = 0u64;
+        // the pending outputs are spent first, the cells reserved by the other clones are skipped
+        {
+            let state = self.state.lock();
+            for cell in state.spendable_outputs() {
+                if query.is_satisfied(total_capacity, cells.len()) {
+                    break;
+                }
+                if self.collected.contains(&cell.out_point) || !query.match_cell(cell, 0) {
+                    continue;
+                }
+                total_capacity += Unpack::<u64>::unpack(&cell.output.capacity());
+                cells.push(cell.clone());
+            }
+        }
+        if apply_changes {
+            self.collected
+                .extend(cells.iter().map(|cell| cell.out_point.clone()));
+        }
+
+        while !query.is_satisfied(total_capacity, cells.len()) {
+            let (live_cells, _) = self.collector.collect_live_cells(query, apply_changes)?;
+            if live_cells.is_empty() {
+                break;
+            }
+            {
+                let state = self.state.lock();
+                for cell in live_cells {
+                    if !state.is_available(&cell.out_point)
+                        || cells.iter().any(|c| c.out_point == cell.out_point)
+                    {
+                        continue;
+                    }
+                    total_capacity += Unpack::<u64>::unpack(&cell.output.capacity());
+                    cells.push(cell);
+                }
+            }
+            // the backend returns the same cells without applying the changes
+            if !apply_changes {
+                break;
+            }
+        }
+        Ok((cells, total_capacity))
+    }
+
+    fn lock_cell(
+        &mut self,
+        out_point: OutPoint,
+        _tip_block_number: u64,
+    ) -> Result<(), CellCollectorError> {
+        self.state.lock().locked_cells.insert(out_point, None);
+        Ok(())
+    }
+
+    fn apply_tx(
+        &mut self,
+        tx: Transaction,
+        _tip_block_number: u64,
+    ) -> Result<(), CellCollectorError> {
+        self.add_pending_tx(&tx.into_view());
+        self.release_reserved();
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.collector.reset();
+        self.collected.clear();
+        self.release_reserved();
+    }
+}
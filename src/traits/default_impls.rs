@@ -24,7 +24,7 @@ use super::{
     OffchainTransactionDependencyProvider,
 };
 use crate::rpc::ckb_indexer::{Order, SearchKey, Tip};
-use crate::rpc::{CkbRpcClient, IndexerRpcClient};
+use crate::rpc::{CkbRpcClient, IndexerRpcClient, PageSize};
 use crate::traits::{
     CellCollector, CellCollectorError, CellDepResolver, CellQueryOptions, HeaderDepResolver,
     LiveCell, QueryOrder, Signer, SignerError, TransactionDependencyError,
@@ -325,7 +325,7 @@ impl CellCollector for DefaultCellCollector {
         } = self.offchain.collect(query, tip_num);
         let mut cells: Vec<_> = cells.into_iter().map(|c| c.0).collect();
 
-        if total_capacity < query.min_total_capacity {
+        if !query.is_satisfied(total_capacity, cells.len()) {
             self.check_ckb_chain()?;
             let order = match query.order {
                 QueryOrder::Asc => Order::Asc,
@@ -338,41 +338,28 @@ impl CellCollector for DefaultCellCollector {
             let locked_cells = self.offchain.locked_cells.clone();
             let search_key = SearchKey::from(query.clone());
             const MAX_LIMIT: u32 = 4096;
-            let mut limit: u32 = query.limit.unwrap_or(16);
-            let mut last_cursor: Option<json_types::JsonBytes> = None;
-            while total_capacity < query.min_total_capacity {
-                let page = self
-                    .indexer_client
-                    .get_cells(search_key.clone(), order.clone(), limit.into(), last_cursor)
-                    .map_err(|err| CellCollectorError::Internal(err.into()))?;
-                if page.objects.is_empty() {
-                    break;
+            let page_size = PageSize::adaptive(query.limit.unwrap_or(16), MAX_LIMIT);
+            for cell in self.indexer_client.iter_cells(search_key, order, page_size) {
+                let cell = cell.map_err(|err| CellCollectorError::Internal(err.into()))?;
+                let live_cell = LiveCell::from(cell);
+                if !query.match_cell(&live_cell, max_mature_number)
+                    || locked_cells.contains_key(&(
+                        live_cell.out_point.tx_hash().unpack(),
+                        live_cell.out_point.index().unpack(),
+                    ))
+                {
+                    continue;
                 }
-                for cell in page.objects {
-                    let live_cell = LiveCell::from(cell);
-                    if !query.match_cell(&live_cell, max_mature_number)
-                        || locked_cells.contains_key(&(
-                            live_cell.out_point.tx_hash().unpack(),
-                            live_cell.out_point.index().unpack(),
-                        ))
-                    {
-                        continue;
-                    }
-                    let capacity: u64 = live_cell.output.capacity().unpack();
-                    // use cell from indexer to replace offchain cell
-                    if ret_cells
-                        .insert(live_cell.out_point.clone(), live_cell)
-                        .is_none()
-                    {
-                        total_capacity += capacity;
-                    }
-                    if total_capacity >= query.min_total_capacity {
-                        break;
-                    }
+                let capacity: u64 = live_cell.output.capacity().unpack();
+                // use cell from indexer to replace offchain cell
+                if ret_cells
+                    .insert(live_cell.out_point.clone(), live_cell)
+                    .is_none()
+                {
+                    total_capacity += capacity;
                 }
-                last_cursor = Some(page.last_cursor);
-                if limit < MAX_LIMIT {
-                    limit *= 2;
+                if query.is_satisfied(total_capacity, ret_cells.len()) {
+                    break;
                 }
             }
             cells = ret_cells.into_values().collect();
@@ -567,6 +554,51 @@ impl TransactionDependencyProvider for DefaultTransactionDependencyProvider {
             None => Ok(None),
         }
     }
+
+    /// Fetch the cells of all inputs of the transaction in one batch request and put them
+    /// into the cache. The inputs failed to fetch are left to the later queries.
+    fn prefetch_inputs(&self, tx: &TransactionView) -> Result<(), TransactionDependencyError> {
+        // the lock is not held during the batch request, so the other queries are not blocked
+        let (out_points, rpc_client): (Vec<OutPoint>, _) = {
+            let inner = self.inner.lock();
+            let out_points = tx
+                .input_pts_iter()
+                .filter(|out_point| {
+                    !inner.cell_cache.contains(out_point)
+                        && inner.offchain_cache.get_cell(out_point).is_err()
+                })
+                .collect();
+            (out_points, inner.rpc_client.clone())
+        };
+        if out_points.is_empty() {
+            return Ok(());
+        }
+        let params = out_points
+            .iter()
+            .map(|out_point| (json_types::OutPoint::from(out_point.clone()), true))
+            .collect();
+        let results = rpc_client
+            .post_batch::<_, json_types::CellWithStatus>("get_live_cell", params)
+            .map_err(|err| TransactionDependencyError::Other(err.into()))?;
+        let mut inner = self.inner.lock();
+        for (out_point, result) in out_points.into_iter().zip(results) {
+            if let Ok(json_types::CellWithStatus {
+                cell:
+                    Some(json_types::CellInfo {
+                        output,
+                        data: Some(data),
+                    }),
+                status,
+            }) = result
+            {
+                if status == "live" {
+                    let pair = (CellOutput::from(output), data.content.into_bytes());
+                    inner.cell_cache.put(out_point, pair);
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 /// A signer use secp256k1 raw key, the id is `blake160(pubkey)`.
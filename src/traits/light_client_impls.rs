@@ -3,7 +3,6 @@ use std::collections::HashMap;
 use anyhow::anyhow;
 use dashmap::DashMap;
 
-use ckb_jsonrpc_types as json_types;
 use ckb_types::{
     bytes::Bytes,
     core::{HeaderView, TransactionView},
@@ -14,7 +13,7 @@ use ckb_types::{
 use super::{offchain_impls::CollectResult, OffchainCellCollector};
 use crate::rpc::{
     ckb_light_client::{FetchStatus, Order, SearchKey},
-    LightClientRpcClient,
+    LightClientRpcClient, PageSize,
 };
 use crate::traits::{
     CellCollector, CellCollectorError, CellQueryOptions, HeaderDepResolver, LiveCell, QueryOrder,
@@ -272,7 +271,7 @@ impl CellCollector for LightClientCellCollector {
         } = self.offchain.collect(query, tip_num);
         let mut cells: Vec<_> = cells.into_iter().map(|c| c.0).collect();
 
-        if total_capacity < query.min_total_capacity {
+        if !query.is_satisfied(total_capacity, cells.len()) {
             let order = match query.order {
                 QueryOrder::Asc => Order::Asc,
                 QueryOrder::Desc => Order::Desc,
@@ -284,40 +283,27 @@ impl CellCollector for LightClientCellCollector {
             let locked_cells = self.offchain.locked_cells.clone();
             let search_key = SearchKey::from(query.clone());
             const MAX_LIMIT: u32 = 4096;
-            let mut limit: u32 = query.limit.unwrap_or(16);
-            let mut last_cursor: Option<json_types::JsonBytes> = None;
-            while total_capacity < query.min_total_capacity {
-                let page = self
-                    .light_client
-                    .get_cells(search_key.clone(), order.clone(), limit.into(), last_cursor)
-                    .map_err(|err| CellCollectorError::Internal(err.into()))?;
-                if page.objects.is_empty() {
-                    break;
+            let page_size = PageSize::adaptive(query.limit.unwrap_or(16), MAX_LIMIT);
+            for cell in self.light_client.iter_cells(search_key, order, page_size) {
+                let cell = cell.map_err(|err| CellCollectorError::Internal(err.into()))?;
+                let live_cell = LiveCell::from(cell);
+                if !query.match_cell(&live_cell, max_mature_number)
+                    || locked_cells.contains_key(&(
+                        live_cell.out_point.tx_hash().unpack(),
+                        live_cell.out_point.index().unpack(),
+                    ))
+                {
+                    continue;
                 }
-                for cell in page.objects {
-                    let live_cell = LiveCell::from(cell);
-                    if !query.match_cell(&live_cell, max_mature_number)
-                        || locked_cells.contains_key(&(
-                            live_cell.out_point.tx_hash().unpack(),
-                            live_cell.out_point.index().unpack(),
-                        ))
-                    {
-                        continue;
-                    }
-                    let capacity: u64 = live_cell.output.capacity().unpack();
-                    if ret_cells
-                        .insert(live_cell.out_point.clone(), live_cell)
-                        .is_none()
-                    {
-                        total_capacity += capacity;
-                    }
-                    if total_capacity >= query.min_total_capacity {
-                        break;
-                    }
+                let capacity: u64 = live_cell.output.capacity().unpack();
+                if ret_cells
+                    .insert(live_cell.out_point.clone(), live_cell)
+                    .is_none()
+                {
+                    total_capacity += capacity;
                 }
-                last_cursor = Some(page.last_cursor);
-                if limit < MAX_LIMIT {
-                    limit *= 2;
+                if query.is_satisfied(total_capacity, ret_cells.len()) {
+                    break;
                 }
             }
             cells = ret_cells.into_values().collect();
@@ -1,11 +1,18 @@
 //! The traits defined here is intent to describe the requirements of current
 //!  library code and only implemented the trait in upper level code.
 
+pub mod async_impls;
+pub mod cell_cache;
 pub mod default_impls;
 pub mod dummy_impls;
 pub mod light_client_impls;
 pub mod offchain_impls;
 
+pub use async_impls::{
+    AsyncDefaultCellCollector, AsyncDefaultHeaderDepResolver,
+    AsyncDefaultTransactionDependencyProvider,
+};
+pub use cell_cache::WalletCellCache;
 pub use default_impls::{
     DefaultCellCollector, DefaultCellDepResolver, DefaultHeaderDepResolver,
     DefaultTransactionDependencyProvider, SecpCkbRawKeySigner,
@@ -20,6 +27,7 @@ pub use offchain_impls::{
 };
 
 use dyn_clone::DynClone;
+use futures::future::BoxFuture;
 use thiserror::Error;
 
 use ckb_hash::blake2b_256;
@@ -108,6 +116,12 @@ pub trait TransactionDependencyProvider: Sync + Send {
         &self,
         block_hash: &Byte32,
     ) -> Result<Option<ckb_types::packed::Bytes>, TransactionDependencyError>;
+
+    /// Fetch the cells of all inputs of the transaction in advance, e.g. in one batch request, before
+    /// they are queried one by one. The default implementation does nothing.
+    fn prefetch_inputs(&self, _tx: &TransactionView) -> Result<(), TransactionDependencyError> {
+        Ok(())
+    }
 }
 
 // Implement CellDataProvider trait is currently for `DaoCalculator`
@@ -256,6 +270,9 @@ pub struct CellQueryOptions {
     /// satisfied will stop collecting. The default value is 1 shannon means
     /// collect only one cell at most.
     pub min_total_capacity: u64,
+    /// Stop collecting when `max_cell_count` cells are collected even if `min_total_capacity`
+    /// is not satisfied, the default value None means no limit.
+    pub max_cell_count: Option<usize>,
     pub script_search_mode: Option<SearchMode>,
 }
 impl CellQueryOptions {
@@ -273,6 +290,7 @@ impl CellQueryOptions {
             limit: None,
             maturity: MaturityOption::Mature,
             min_total_capacity: 1,
+            max_cell_count: None,
             script_search_mode: None,
         }
     }
@@ -282,6 +300,13 @@ impl CellQueryOptions {
     pub fn new_type(primary_script: Script) -> CellQueryOptions {
         CellQueryOptions::new(primary_script, PrimaryScriptType::Type)
     }
+    /// Whether the collection should stop, either `min_total_capacity` or `max_cell_count` is reached.
+    pub fn is_satisfied(&self, total_capacity: u64, cell_count: usize) -> bool {
+        total_capacity >= self.min_total_capacity
+            || self
+                .max_cell_count
+                .is_some_and(|max_cell_count| cell_count >= max_cell_count)
+    }
     pub fn match_cell(&self, cell: &LiveCell, max_mature_number: u64) -> bool {
         fn extract_raw_data(script: &Script) -> Vec<u8> {
             [
@@ -424,6 +449,77 @@ pub trait HeaderDepResolver {
     fn resolve_by_number(&self, number: u64) -> Result<Option<HeaderView>, anyhow::Error>;
 }
 
+/// Async version of [`CellCollector`], the offchain state is updated synchronously.
+pub trait AsyncCellCollector: DynClone + Send + Sync {
+    /// Collect live cells by query options, if `apply_changes` is true will
+    /// mark all collected cells as dead cells.
+    fn collect_live_cells<'a>(
+        &'a mut self,
+        query: &'a CellQueryOptions,
+        apply_changes: bool,
+    ) -> BoxFuture<'a, Result<(Vec<LiveCell>, u64), CellCollectorError>>;
+
+    /// Mark this cell as dead cell
+    fn lock_cell(
+        &mut self,
+        out_point: OutPoint,
+        tip_block_number: u64,
+    ) -> Result<(), CellCollectorError>;
+    /// Mark all inputs as dead cells and outputs as live cells in the transaction.
+    fn apply_tx(
+        &mut self,
+        tx: Transaction,
+        tip_block_number: u64,
+    ) -> Result<(), CellCollectorError>;
+
+    /// Clear cache and locked cells
+    fn reset(&mut self);
+}
+
+/// Async version of [`TransactionDependencyProvider`].
+pub trait AsyncTransactionDependencyProvider: Sync + Send {
+    /// For verify certain cell belong to certain transaction
+    fn get_transaction<'a>(
+        &'a self,
+        tx_hash: &'a Byte32,
+    ) -> BoxFuture<'a, Result<TransactionView, TransactionDependencyError>>;
+    /// For get the output information of inputs or cell_deps, those cell should be live cell
+    fn get_cell<'a>(
+        &'a self,
+        out_point: &'a OutPoint,
+    ) -> BoxFuture<'a, Result<CellOutput, TransactionDependencyError>>;
+    /// For get the output data information of inputs or cell_deps
+    fn get_cell_data<'a>(
+        &'a self,
+        out_point: &'a OutPoint,
+    ) -> BoxFuture<'a, Result<Bytes, TransactionDependencyError>>;
+    /// For get the header information of header_deps
+    fn get_header<'a>(
+        &'a self,
+        block_hash: &'a Byte32,
+    ) -> BoxFuture<'a, Result<HeaderView, TransactionDependencyError>>;
+    /// For get_block_extension
+    fn get_block_extension<'a>(
+        &'a self,
+        block_hash: &'a Byte32,
+    ) -> BoxFuture<'a, Result<Option<ckb_types::packed::Bytes>, TransactionDependencyError>>;
+}
+
+/// Async version of [`HeaderDepResolver`].
+pub trait AsyncHeaderDepResolver: Sync + Send {
+    /// Resolve header dep by trancation hash
+    fn resolve_by_tx<'a>(
+        &'a self,
+        tx_hash: &'a Byte32,
+    ) -> BoxFuture<'a, Result<Option<HeaderView>, anyhow::Error>>;
+
+    /// Resolve header dep by block number
+    fn resolve_by_number(
+        &self,
+        number: u64,
+    ) -> BoxFuture<'_, Result<Option<HeaderView>, anyhow::Error>>;
+}
+
 // test cases make sure new added exception won't breadk `anyhow!(e_variable)` usage,
 #[cfg(test)]
 mod anyhow_tests {
@@ -92,16 +92,18 @@ impl OffchainCellCollector {
     ) -> CollectResult {
         self.truncate(tip_block_number);
         let mut total_capacity = 0;
+        let mut cell_count = 0;
         let (cells, rest_cells): (Vec<_>, Vec<_>) =
             self.live_cells
                 .clone()
                 .into_iter()
                 .partition(|(cell, _tip_num)| {
-                    if total_capacity < query.min_total_capacity
+                    if !query.is_satisfied(total_capacity, cell_count)
                         && query.match_cell(cell, self.max_mature_number)
                     {
                         let capacity: u64 = cell.output.capacity().unpack();
                         total_capacity += capacity;
+                        cell_count += 1;
                         true
                     } else {
                         false
@@ -0,0 +1,393 @@
+use anyhow::anyhow;
+use ckb_types::{
+    core::Capacity,
+    packed::{self, CellOutput, Script},
+    prelude::*,
+};
+
+use crate::{
+    core::TransactionBuilder,
+    transaction::{
+        handler::HandlerContexts, input::InputIterator, TransactionBuilderConfiguration,
+    },
+    tx_builder::TxBuilderError,
+    Address, HumanCapacity, TransactionWithScriptGroups,
+};
+
+use super::{inner_build, ChangeDecision, DefaultChangeBuilder};
+
+/// The maximum size of a transaction accepted by the tx-pool.
+pub const MAX_TX_SIZE: u64 = 512000;
+
+/// The size reserved for the inputs, witnesses, cell deps and the change output while splitting the
+/// recipients into transactions.
+const RESERVED_TX_SIZE: u64 = 32000;
+
+/// Who pays the fee of a batch payment.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BatchFeeMode {
+    /// The sender pays the fee, the recipients receive the full amounts.
+    #[default]
+    PaidBySender,
+    /// The fee is deducted from the recipients in proportion to their amounts.
+    DeductedProportionally,
+    /// The fee is deducted from the recipients equally.
+    DeductedEqually,
+}
+
+/// The payment to a recipient in a batch transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecipientPayment {
+    /// The index of the recipient in the order they are added
+    pub recipient_index: usize,
+    /// The index of the output in the transaction
+    pub output_index: usize,
+    /// The capacity received by the recipient
+    pub capacity: u64,
+    /// The fee deducted from the recipient
+    pub deducted_fee: u64,
+}
+
+/// A transaction of the batch payment and the recipients paid by it.
+pub struct PaymentBatch {
+    pub tx_with_groups: TransactionWithScriptGroups,
+    pub payments: Vec<RecipientPayment>,
+}
+
+/// A batch payment builder, it pays many recipients with one or more transactions, the recipients are
+/// split into multiple transactions when the output count or the transaction size limit would be exceeded.
+///
+/// The transactions are built with the same input iterator, so they don't spend the same inputs,
+/// but the change output of a transaction is not spent by the following ones unless the input iterator
+/// is created with a wallet cell cache, which the transactions are added to as pending transactions.
+pub struct BatchPaymentBuilder {
+    /// The change lock script, the default change lock script is the last lock script of the input iterator
+    change_lock: Script,
+    /// The transaction builder configuration
+    configuration: TransactionBuilderConfiguration,
+    /// The input iterator, used for building transactions with cell collector
+    input_iter: InputIterator,
+    /// The recipient outputs in the order they are added
+    recipients: Vec<CellOutput>,
+    fee_mode: BatchFeeMode,
+    /// The maximum number of the recipient outputs in a transaction
+    max_outputs_per_tx: Option<usize>,
+    /// The maximum size of a transaction
+    max_tx_size: u64,
+}
+
+impl BatchPaymentBuilder {
+    pub fn new(configuration: TransactionBuilderConfiguration, input_iter: InputIterator) -> Self {
+        Self {
+            change_lock: input_iter
+                .lock_scripts()
+                .last()
+                .expect("input iter should not be empty")
+                .clone(),
+            configuration,
+            input_iter,
+            recipients: Vec::new(),
+            fee_mode: BatchFeeMode::default(),
+            max_outputs_per_tx: None,
+            max_tx_size: MAX_TX_SIZE,
+        }
+    }
+
+    /// Update the change lock script.
+    pub fn set_change_lock(&mut self, lock_script: Script) {
+        self.change_lock = lock_script;
+    }
+
+    /// Add a recipient, the capacity must be enough for the output cell, e.g. 61 CKB for a
+    /// secp256k1-sig-hash-all address.
+    pub fn add_recipient(
+        &mut self,
+        address: &Address,
+        capacity: HumanCapacity,
+    ) -> Result<(), TxBuilderError> {
+        let output = CellOutput::new_builder()
+            .lock(Script::from(address))
+            .capacity(capacity.0.pack())
+            .build();
+        let occupied_capacity = occupied_capacity(&output);
+        if capacity.0 < occupied_capacity {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "the capacity {} of the recipient {} is less than the occupied capacity {}",
+                capacity,
+                address,
+                HumanCapacity(occupied_capacity)
+            )));
+        }
+        self.recipients.push(output);
+        Ok(())
+    }
+
+    /// Set who pays the fee, the fee deducted from the recipients is returned to the change output,
+    /// so the build fails if the small change is added to a recipient output, see
+    /// [`SmallChangePolicy::AddToOutput`](super::SmallChangePolicy::AddToOutput).
+    pub fn set_fee_mode(&mut self, fee_mode: BatchFeeMode) {
+        self.fee_mode = fee_mode;
+    }
+
+    /// Set the maximum number of the recipient outputs in a transaction.
+    pub fn set_max_outputs_per_tx(&mut self, max_outputs_per_tx: Option<usize>) {
+        self.max_outputs_per_tx = max_outputs_per_tx;
+    }
+
+    /// Set the maximum size of a transaction, the default value is [`MAX_TX_SIZE`].
+    pub fn set_max_tx_size(&mut self, max_tx_size: u64) {
+        self.max_tx_size = max_tx_size;
+    }
+
+    /// Split the recipients into chunks by the output count and the size of the outputs.
+    fn split_recipients(&self) -> Vec<Vec<usize>> {
+        let max_outputs_size = self.max_tx_size.saturating_sub(RESERVED_TX_SIZE);
+        let mut chunks: Vec<Vec<usize>> = Vec::new();
+        let mut chunk = Vec::new();
+        let mut outputs_size = 0u64;
+        for (index, output) in self.recipients.iter().enumerate() {
+            // the output and its empty data take 4 more bytes each for the offset in the vector
+            let output_size =
+                (output.as_slice().len() + packed::Bytes::default().as_slice().len() + 8) as u64;
+            let chunk_full = self
+                .max_outputs_per_tx
+                .map(|max_outputs| chunk.len() >= max_outputs)
+                .unwrap_or(false)
+                || outputs_size + output_size > max_outputs_size;
+            if chunk_full && !chunk.is_empty() {
+                chunks.push(std::mem::take(&mut chunk));
+                outputs_size = 0;
+            }
+            chunk.push(index);
+            outputs_size += output_size;
+        }
+        if !chunk.is_empty() {
+            chunks.push(chunk);
+        }
+        chunks
+    }
+
+    /// Build the transactions to pay all the recipients, the recipients of each transaction are reported
+    /// in [`PaymentBatch::payments`].
+    pub fn build_batches(
+        self,
+        contexts: &HandlerContexts,
+    ) -> Result<Vec<PaymentBatch>, TxBuilderError> {
+        if self.recipients.is_empty() {
+            return Err(TxBuilderError::InvalidParameter(anyhow!("no recipient")));
+        }
+        let chunks = self.split_recipients();
+        let Self {
+            change_lock,
+            mut configuration,
+            mut input_iter,
+            recipients,
+            fee_mode,
+            max_tx_size,
+            ..
+        } = self;
+        configuration.update_fee_rate()?;
+
+        let mut batches = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            let mut tx = TransactionBuilder::default();
+            for recipient_index in &chunk {
+                tx.output(recipients[*recipient_index].clone());
+                tx.output_data(packed::Bytes::default());
+            }
+            let change_builder =
+                DefaultChangeBuilder::new(&configuration, change_lock.clone(), Vec::new());
+            let mut tx_with_groups = inner_build(
+                tx,
+                change_builder,
+                Vec::new(),
+                input_iter.by_ref(),
+                &configuration,
+                contexts,
+            )?;
+            let tx_size = tx_with_groups
+                .get_tx_view()
+                .data()
+                .as_reader()
+                .serialized_size_in_block() as u64;
+            if tx_size > max_tx_size {
+                return Err(TxBuilderError::Other(anyhow!(
+                    "the transaction size {} exceeds the limit {}, try a smaller output count",
+                    tx_size,
+                    max_tx_size
+                )));
+            }
+
+            let output_indices = recipient_output_indices(&tx_with_groups, chunk.len());
+            let deducted_fees = match fee_mode {
+                BatchFeeMode::PaidBySender => vec![0; chunk.len()],
+                _ => {
+                    let fee = configuration.fee_calculator().fee(tx_size);
+                    let deducted_fees = split_fee(fee, &tx_with_groups, &output_indices, fee_mode);
+                    deduct_fee(&mut tx_with_groups, &output_indices, &deducted_fees, fee)?;
+                    deducted_fees
+                }
+            };
+            input_iter.add_pending_tx(tx_with_groups.get_tx_view())?;
+            let payments = chunk
+                .into_iter()
+                .zip(output_indices)
+                .zip(deducted_fees)
+                .map(
+                    |((recipient_index, output_index), deducted_fee)| RecipientPayment {
+                        recipient_index,
+                        output_index,
+                        capacity: tx_with_groups
+                            .get_tx_view()
+                            .output(output_index)
+                            .map(|output| output.capacity().unpack())
+                            .unwrap_or_default(),
+                        deducted_fee,
+                    },
+                )
+                .collect();
+            batches.push(PaymentBatch {
+                tx_with_groups,
+                payments,
+            });
+        }
+        Ok(batches)
+    }
+}
+
+fn occupied_capacity(output: &CellOutput) -> u64 {
+    output
+        .occupied_capacity(Capacity::zero())
+        .expect("occupied capacity")
+        .as_u64()
+}
+
+/// The output indices of the recipients, they are the outputs in the order they are added,
+/// skipping the change output.
+fn recipient_output_indices(
+    tx_with_groups: &TransactionWithScriptGroups,
+    recipients_count: usize,
+) -> Vec<usize> {
+    let change_index = match tx_with_groups.get_change_decision() {
+        Some(ChangeDecision::ChangeOutput { output_index, .. }) => Some(*output_index),
+        _ => None,
+    };
+    (0..tx_with_groups.get_tx_view().outputs().len())
+        .filter(|index| Some(*index) != change_index)
+        .take(recipients_count)
+        .collect()
+}
+
+/// Split the fee among the recipient outputs, the remainder of the division is paid by the first
+/// recipients, one shannon each.
+fn split_fee(
+    fee: u64,
+    tx_with_groups: &TransactionWithScriptGroups,
+    output_indices: &[usize],
+    fee_mode: BatchFeeMode,
+) -> Vec<u64> {
+    let tx = tx_with_groups.get_tx_view();
+    let capacities: Vec<u64> = output_indices
+        .iter()
+        .map(|index| {
+            tx.output(*index)
+                .map(|output| output.capacity().unpack())
+                .unwrap_or_default()
+        })
+        .collect();
+    let recipients_count = output_indices.len();
+    let mut fees: Vec<u64> = match fee_mode {
+        BatchFeeMode::DeductedProportionally => {
+            let total: u128 = capacities.iter().map(|c| *c as u128).sum();
+            capacities
+                .iter()
+                .map(|capacity| (fee as u128 * *capacity as u128 / total) as u64)
+                .collect()
+        }
+        _ => vec![fee / recipients_count as u64; recipients_count],
+    };
+    let remainder = fee - fees.iter().sum::<u64>();
+    for fee in fees.iter_mut().take(remainder as usize) {
+        *fee += 1;
+    }
+    fees
+}
+
+/// Deduct the fees from the recipient outputs and return the total to the change output.
+fn deduct_fee(
+    tx_with_groups: &mut TransactionWithScriptGroups,
+    output_indices: &[usize],
+    deducted_fees: &[u64],
+    fee: u64,
+) -> Result<(), TxBuilderError> {
+    let change_index = match tx_with_groups.get_change_decision() {
+        Some(ChangeDecision::ChangeOutput { output_index, .. }) => *output_index,
+        // the fee would be returned to the recipient it is deducted from
+        Some(ChangeDecision::AddedToOutput { output_index, .. })
+            if output_indices.contains(output_index) =>
+        {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "the small change is added to the recipient output {}, \
+                 it can not receive the fee deducted from the recipients",
+                output_index
+            )))
+        }
+        Some(ChangeDecision::AddedToOutput { output_index, .. }) => *output_index,
+        _ => {
+            return Err(TxBuilderError::Other(anyhow!(
+                "no change output to return the fee deducted from the recipients"
+            )))
+        }
+    };
+    let tx = tx_with_groups.get_tx_view();
+    let mut outputs: Vec<CellOutput> = tx.outputs().into_iter().collect();
+    for (index, deducted_fee) in output_indices.iter().zip(deducted_fees) {
+        let output = &outputs[*index];
+        let capacity: u64 = output.capacity().unpack();
+        let occupied_capacity = occupied_capacity(output);
+        let capacity = capacity
+            .checked_sub(*deducted_fee)
+            .filter(|capacity| *capacity >= occupied_capacity)
+            .ok_or_else(|| {
+                TxBuilderError::Other(anyhow!(
+                    "the capacity {} of the output is not enough to pay the fee {}",
+                    HumanCapacity(capacity),
+                    HumanCapacity(*deducted_fee)
+                ))
+            })?;
+        outputs[*index] = output
+            .clone()
+            .as_builder()
+            .capacity(capacity.pack())
+            .build();
+    }
+    let change_capacity: u64 = outputs[change_index].capacity().unpack();
+    outputs[change_index] = outputs[change_index]
+        .clone()
+        .as_builder()
+        .capacity((change_capacity + fee).pack())
+        .build();
+    let tx = tx.as_advanced_builder().set_outputs(outputs).build();
+    tx_with_groups.set_tx_view(tx);
+    if let Some(change_decision) = tx_with_groups.get_change_decision().copied() {
+        let change_decision = match change_decision {
+            ChangeDecision::ChangeOutput {
+                output_index,
+                capacity,
+            } => ChangeDecision::ChangeOutput {
+                output_index,
+                capacity: capacity + fee,
+            },
+            ChangeDecision::AddedToOutput {
+                output_index,
+                capacity,
+            } => ChangeDecision::AddedToOutput {
+                output_index,
+                capacity: capacity + fee,
+            },
+            change_decision => change_decision,
+        };
+        tx_with_groups.set_change_decision(Some(change_decision));
+    }
+    Ok(())
+}
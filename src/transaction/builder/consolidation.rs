@@ -0,0 +1,280 @@
+use anyhow::anyhow;
+use ckb_types::{
+    core::Capacity,
+    packed::{self, CellOutput, Script},
+    prelude::*,
+};
+
+use crate::{
+    core::TransactionBuilder,
+    rpc::ckb_indexer::SearchMode,
+    traits::{CellCollector, CellQueryOptions, DefaultCellCollector, LiveCell, ValueRangeOption},
+    transaction::{
+        handler::HandlerContexts, input::TransactionInput, TransactionBuilderConfiguration,
+    },
+    tx_builder::{BalanceTxCapacityError, TxBuilderError},
+    CkbRpcClient, TransactionWithScriptGroups,
+};
+
+use super::{
+    batch::MAX_TX_SIZE, estimate_cycles, inner_build, CkbTransactionBuilder, DefaultChangeBuilder,
+};
+
+/// The default maximum cycles of a transaction accepted by the tx-pool, see `max_tx_verify_cycles`.
+pub const DEFAULT_MAX_TX_VERIFY_CYCLES: u64 = 70_000_000;
+
+/// A cell consolidation builder, it merges the smallest plain cells (without type script and data) of
+/// the lock scripts into one or a few outputs, e.g. sweeping the dust cells of a wallet.
+///
+/// [`build_next`](ConsolidationBuilder::build_next) can be called repeatedly until the cell count is
+/// under the target, the built transactions are applied to the cell collector, so their inputs are not
+/// collected again and their outputs are counted as the cells of the wallet.
+///
+/// The inputs are unlocked by the registered script handlers, e.g. sighash, multisig and OmniLock,
+/// with the handler contexts.
+pub struct ConsolidationBuilder {
+    /// The transaction builder configuration
+    configuration: TransactionBuilderConfiguration,
+    /// The lock scripts of the cells to consolidate
+    lock_scripts: Vec<Script>,
+    /// The cell collector to collect the cells of the lock scripts
+    cell_collector: Box<dyn CellCollector>,
+    /// The rpc client to get the tip block number while applying the transactions to the cell collector
+    ckb_client: Option<CkbRpcClient>,
+    /// The lock script of the merged outputs, the default one is the first lock script
+    output_lock: Script,
+    /// The number of the merged outputs
+    output_count: usize,
+    /// The maximum number of the inputs in a transaction
+    max_inputs: usize,
+    /// The cell count of the lock scripts to reach
+    target_cell_count: Option<usize>,
+    /// The maximum size of a transaction
+    max_tx_size: u64,
+    /// The maximum cycles of a transaction
+    max_cycles: u64,
+}
+
+impl ConsolidationBuilder {
+    /// Create a consolidation builder with the [`DefaultCellCollector`] of the network.
+    pub fn new(configuration: TransactionBuilderConfiguration, lock_scripts: Vec<Script>) -> Self {
+        let url = configuration.network_info().url.clone();
+        let mut builder = Self::new_with_cell_collector(
+            configuration,
+            lock_scripts,
+            Box::new(DefaultCellCollector::new(&url)),
+        );
+        builder.ckb_client = Some(CkbRpcClient::new(&url));
+        builder
+    }
+
+    pub fn new_with_cell_collector(
+        configuration: TransactionBuilderConfiguration,
+        lock_scripts: Vec<Script>,
+        cell_collector: Box<dyn CellCollector>,
+    ) -> Self {
+        Self {
+            output_lock: lock_scripts
+                .first()
+                .expect("lock scripts should not be empty")
+                .clone(),
+            configuration,
+            lock_scripts,
+            cell_collector,
+            ckb_client: None,
+            output_count: 1,
+            max_inputs: 500,
+            target_cell_count: None,
+            max_tx_size: MAX_TX_SIZE,
+            max_cycles: DEFAULT_MAX_TX_VERIFY_CYCLES,
+        }
+    }
+
+    /// Set the lock script of the merged outputs.
+    pub fn set_output_lock(&mut self, output_lock: Script) {
+        self.output_lock = output_lock;
+    }
+
+    /// Set the number of the merged outputs, the capacity is split equally, the default value is 1.
+    ///
+    /// Less outputs are created if the split capacity is less than the occupied capacity of an output.
+    pub fn set_output_count(&mut self, output_count: usize) {
+        self.output_count = output_count.max(1);
+    }
+
+    /// Set the maximum number of the inputs in a transaction, the default value is 500.
+    pub fn set_max_inputs(&mut self, max_inputs: usize) {
+        self.max_inputs = max_inputs;
+    }
+
+    /// Stop consolidating when the cell count of the lock scripts is not more than the target.
+    pub fn set_target_cell_count(&mut self, target_cell_count: Option<usize>) {
+        self.target_cell_count = target_cell_count;
+    }
+
+    /// Set the maximum size of a transaction, the default value is [`MAX_TX_SIZE`].
+    pub fn set_max_tx_size(&mut self, max_tx_size: u64) {
+        self.max_tx_size = max_tx_size;
+    }
+
+    /// Set the maximum cycles of a transaction, the default value is [`DEFAULT_MAX_TX_VERIFY_CYCLES`].
+    ///
+    /// The cycles are estimated with the environment of the configuration and the script handlers,
+    /// see [`ScriptHandler::estimate_cycles`](crate::transaction::handler::ScriptHandler::estimate_cycles).
+    pub fn set_max_cycles(&mut self, max_cycles: u64) {
+        self.max_cycles = max_cycles;
+    }
+
+    /// Collect the plain cells of the lock scripts, ordered by capacity from the smallest.
+    ///
+    /// At most `target_cell_count + max_inputs` cells are collected (in the order of the cell collector),
+    /// which are enough to fill a transaction and to tell whether the target is reached, so the cells
+    /// to merge are the smallest ones of the collected cells instead of the whole wallet.
+    pub fn collect_cells(&mut self) -> Result<Vec<LiveCell>, TxBuilderError> {
+        let max_cell_count = self
+            .target_cell_count
+            .unwrap_or(self.output_count)
+            .saturating_add(self.max_inputs);
+        let mut cells = Vec::new();
+        for lock_script in &self.lock_scripts {
+            if cells.len() >= max_cell_count {
+                break;
+            }
+            let mut query = CellQueryOptions::new_lock(lock_script.clone());
+            query.script_search_mode = Some(SearchMode::Exact);
+            query.secondary_script_len_range = Some(ValueRangeOption::new_exact(0));
+            query.data_len_range = Some(ValueRangeOption::new_exact(0));
+            query.min_total_capacity = u64::MAX;
+            query.max_cell_count = Some(max_cell_count - cells.len());
+            let (live_cells, _) = self.cell_collector.collect_live_cells(&query, false)?;
+            cells.extend(live_cells);
+        }
+        cells.sort_by_key(|cell| Unpack::<u64>::unpack(&cell.output.capacity()));
+        Ok(cells)
+    }
+
+    /// Build a transaction to merge the smallest cells, return None if the cell count is not more
+    /// than the target (or the output count), the transaction is applied to the cell collector.
+    pub fn build_next(
+        &mut self,
+        contexts: &HandlerContexts,
+    ) -> Result<Option<TransactionWithScriptGroups>, TxBuilderError> {
+        self.configuration.update_fee_rate()?;
+        let cells = self.collect_cells()?;
+        let target_cell_count = self.target_cell_count.unwrap_or(self.output_count);
+        if cells.len() <= target_cell_count {
+            return Ok(None);
+        }
+        let mut inputs_count = (cells.len() - target_cell_count + self.output_count)
+            .min(self.max_inputs)
+            .min(cells.len());
+
+        // less inputs are merged if the transaction exceeds the size or cycles limit
+        while inputs_count > self.output_count {
+            let tx_with_groups = self.build_with_cells(&cells[..inputs_count], contexts)?;
+            let tx_view = tx_with_groups.get_tx_view();
+            let tx_size = tx_view.data().as_reader().serialized_size_in_block() as u64;
+            let cycles = estimate_cycles(
+                &TransactionBuilder::from(tx_view),
+                tx_with_groups.get_script_groups(),
+                &self.configuration,
+                contexts,
+            )?;
+            if tx_size <= self.max_tx_size && cycles <= self.max_cycles {
+                let tip_block_number = match &self.ckb_client {
+                    Some(client) => client
+                        .get_tip_block_number()
+                        .map_err(|err| TxBuilderError::Other(anyhow!(err)))?
+                        .value(),
+                    None => 0,
+                };
+                self.cell_collector
+                    .apply_tx(tx_view.data(), tip_block_number)?;
+                return Ok(Some(tx_with_groups));
+            }
+            log::debug!(
+                "{} inputs exceed the limits, size: {}, cycles: {}",
+                inputs_count,
+                tx_size,
+                cycles
+            );
+            inputs_count /= 2;
+        }
+        Err(TxBuilderError::Other(anyhow!(
+            "can not merge the cells within the size and cycles limits"
+        )))
+    }
+
+    fn build_with_cells(
+        &self,
+        cells: &[LiveCell],
+        contexts: &HandlerContexts,
+    ) -> Result<TransactionWithScriptGroups, TxBuilderError> {
+        let inputs: Vec<TransactionInput> = cells
+            .iter()
+            .map(|cell| TransactionInput::new(cell.clone(), 0))
+            .collect();
+        let total_capacity: u64 = cells
+            .iter()
+            .map(|cell| Unpack::<u64>::unpack(&cell.output.capacity()))
+            .sum();
+
+        // every output must hold its occupied capacity, less outputs are created if the cells are not enough
+        let occupied_capacity = CellOutput::new_builder()
+            .lock(self.output_lock.clone())
+            .build()
+            .occupied_capacity(Capacity::zero())
+            .expect("occupied capacity")
+            .as_u64();
+        let output_count = (self.output_count as u64).min(total_capacity / occupied_capacity);
+        if output_count == 0 {
+            return Err(BalanceTxCapacityError::CapacityNotEnough(format!(
+                "the capacity of the cells {} is less than the occupied capacity {} of an output",
+                total_capacity, occupied_capacity
+            ))
+            .into());
+        }
+        if output_count < self.output_count as u64 {
+            log::debug!(
+                "the capacity {} is only split into {} outputs",
+                total_capacity,
+                output_count
+            );
+        }
+
+        // the last output is the change output, which takes the rest capacity (including the remainder
+        // of the split) after the fee
+        let mut tx = TransactionBuilder::default();
+        let capacity = total_capacity / output_count;
+        for _ in 1..output_count {
+            tx.output(
+                CellOutput::new_builder()
+                    .lock(self.output_lock.clone())
+                    .capacity(capacity.pack())
+                    .build(),
+            );
+            tx.output_data(packed::Bytes::default());
+        }
+        let change_builder =
+            DefaultChangeBuilder::new(&self.configuration, self.output_lock.clone(), Vec::new());
+        inner_build(
+            tx,
+            change_builder,
+            inputs,
+            std::iter::empty(),
+            &self.configuration,
+            contexts,
+        )
+    }
+}
+
+impl CkbTransactionBuilder for ConsolidationBuilder {
+    fn build(
+        mut self,
+        contexts: &HandlerContexts,
+    ) -> Result<TransactionWithScriptGroups, TxBuilderError> {
+        self.build_next(contexts)?.ok_or_else(|| {
+            TxBuilderError::Other(anyhow!("the cell count is not more than the target"))
+        })
+    }
+}
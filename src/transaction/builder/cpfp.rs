@@ -0,0 +1,215 @@
+use anyhow::anyhow;
+use ckb_types::{
+    core::TransactionView,
+    packed::{self, CellOutput, OutPoint, Script},
+    prelude::*,
+    H256,
+};
+
+use crate::{
+    core::TransactionBuilder,
+    traits::LiveCell,
+    transaction::{
+        fee_bumper::get_pool_transaction,
+        handler::HandlerContexts,
+        input::{InputIterator, TransactionInput},
+        TransactionBuilderConfiguration,
+    },
+    tx_builder::TxBuilderError,
+    CkbRpcClient, TransactionWithScriptGroups,
+};
+
+use super::{build_with_selector, CkbTransactionBuilder, DefaultChangeBuilder};
+
+/// A child-pays-for-parent (CPFP) transaction builder, it spends the outputs of a transaction
+/// in the tx-pool with a child transaction, which pays the fee to make the fee rate of the package
+/// (the child and its unconfirmed ancestors) reach the fee rate of the configuration.
+///
+/// The parent transaction is applied to the cell collector of the input iterator, so its outputs
+/// are treated as live cells and its inputs are not collected again.
+pub struct CpfpTransactionBuilder {
+    /// The change lock script, the default change lock script is the last lock script of the input iterator
+    change_lock: Script,
+    /// The transaction builder configuration
+    configuration: TransactionBuilderConfiguration,
+    /// The input iterator, used for collecting more inputs to pay the fee
+    input_iter: InputIterator,
+    /// The parent transaction in the tx-pool
+    parent_tx: TransactionView,
+    /// The outputs of the parent transaction to spend, the default outputs are the ones locked by
+    /// the lock scripts of the input iterator
+    parent_output_indices: Option<Vec<usize>>,
+    /// The total fee and weight of the parent and its unconfirmed ancestors
+    ancestors: Option<(u64, u64)>,
+    /// The tip block number when the parent transaction is applied to the cell collector
+    tip_block_number: u64,
+    /// The inner transaction builder
+    tx: TransactionBuilder,
+}
+
+impl CpfpTransactionBuilder {
+    pub fn new(
+        configuration: TransactionBuilderConfiguration,
+        input_iter: InputIterator,
+        parent_tx: TransactionView,
+    ) -> Self {
+        Self {
+            change_lock: input_iter
+                .lock_scripts()
+                .last()
+                .expect("input iter should not be empty")
+                .clone(),
+            configuration,
+            input_iter,
+            parent_tx,
+            parent_output_indices: None,
+            ancestors: None,
+            tip_block_number: 0,
+            tx: TransactionBuilder::default(),
+        }
+    }
+
+    /// Create a CPFP builder for a transaction in the tx-pool of the node, the fee and weight of
+    /// its ancestors are fetched by the `get_pool_tx_detail_info` rpc.
+    pub fn new_with_tx_hash(
+        url: &str,
+        tx_hash: &H256,
+        configuration: TransactionBuilderConfiguration,
+        input_iter: InputIterator,
+    ) -> Result<Self, TxBuilderError> {
+        let client = CkbRpcClient::new(url);
+        let (parent_tx, _) = get_pool_transaction(&client, tx_hash)?;
+        let detail = client
+            .get_pool_tx_detail_info(tx_hash.clone())
+            .map_err(|err| TxBuilderError::Other(anyhow!(err)))?;
+        let tip_block_number = client
+            .get_tip_block_number()
+            .map_err(|err| TxBuilderError::Other(anyhow!(err)))?;
+
+        let mut builder = Self::new(configuration, input_iter, parent_tx);
+        builder.set_ancestors(
+            detail.score_sortkey.ancestors_fee.value(),
+            detail.score_sortkey.ancestors_weight.value(),
+        );
+        builder.set_tip_block_number(tip_block_number.value());
+        Ok(builder)
+    }
+
+    /// Update the change lock script.
+    pub fn set_change_lock(&mut self, lock_script: Script) {
+        self.change_lock = lock_script;
+    }
+
+    /// Add an output cell and output data to the child transaction.
+    pub fn add_output_and_data(&mut self, output: CellOutput, data: packed::Bytes) {
+        self.tx.output(output);
+        self.tx.output_data(data);
+    }
+
+    /// Set the outputs of the parent transaction to spend.
+    pub fn set_parent_output_indices(&mut self, parent_output_indices: Vec<usize>) {
+        self.parent_output_indices = Some(parent_output_indices);
+    }
+
+    /// Set the total fee and weight of the parent and its unconfirmed ancestors, e.g. `ancestors_fee`
+    /// and `ancestors_weight` of `get_pool_tx_detail_info`.
+    ///
+    /// If it's not set, the parent is treated as the only unconfirmed ancestor, its fee is resolved with
+    /// the environment of the configuration and its weight is the transaction size.
+    pub fn set_ancestors(&mut self, ancestors_fee: u64, ancestors_weight: u64) {
+        self.ancestors = Some((ancestors_fee, ancestors_weight));
+    }
+
+    /// Set the tip block number to apply the parent transaction to the cell collector.
+    pub fn set_tip_block_number(&mut self, tip_block_number: u64) {
+        self.tip_block_number = tip_block_number;
+    }
+}
+
+impl CkbTransactionBuilder for CpfpTransactionBuilder {
+    fn build(
+        self,
+        contexts: &HandlerContexts,
+    ) -> Result<TransactionWithScriptGroups, TxBuilderError> {
+        let Self {
+            change_lock,
+            mut configuration,
+            mut input_iter,
+            parent_tx,
+            parent_output_indices,
+            ancestors,
+            tip_block_number,
+            tx,
+        } = self;
+        configuration.update_fee_rate()?;
+
+        let (ancestors_fee, ancestors_weight) = match ancestors {
+            Some(ancestors) => ancestors,
+            None => {
+                let tx_dep_provider = configuration.get_environment().tx_dep_provider();
+                let mut inputs_capacity = 0u64;
+                for out_point in parent_tx.input_pts_iter() {
+                    let cell = tx_dep_provider.get_cell(&out_point)?;
+                    inputs_capacity += Unpack::<u64>::unpack(&cell.capacity());
+                }
+                let outputs_capacity: u64 = parent_tx
+                    .outputs()
+                    .into_iter()
+                    .map(|output| Unpack::<u64>::unpack(&output.capacity()))
+                    .sum();
+                let parent_fee = inputs_capacity.saturating_sub(outputs_capacity);
+                let parent_size = parent_tx.data().as_reader().serialized_size_in_block() as u64;
+                (parent_fee, parent_size)
+            }
+        };
+        log::debug!(
+            "ancestors fee: {}, ancestors weight: {}",
+            ancestors_fee,
+            ancestors_weight
+        );
+
+        // the parent outputs are spent as the required inputs
+        let parent_hash = parent_tx.hash();
+        let mut inputs = Vec::new();
+        for (index, (output, output_data)) in parent_tx.outputs_with_data_iter().enumerate() {
+            let spend = match &parent_output_indices {
+                Some(indices) => indices.contains(&index),
+                None => {
+                    input_iter.lock_scripts().contains(&output.lock())
+                        && output.type_().to_opt().is_none()
+                        && output_data.is_empty()
+                }
+            };
+            if spend {
+                let live_cell = LiveCell {
+                    output,
+                    output_data,
+                    out_point: OutPoint::new(parent_hash.clone(), index as u32),
+                    block_number: 0,
+                    tx_index: 0,
+                };
+                inputs.push(TransactionInput::new(live_cell, 0));
+            }
+        }
+        if inputs.is_empty() {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "no output of the parent transaction {:#x} to spend",
+                parent_hash
+            )));
+        }
+        input_iter.apply_tx(parent_tx.data(), tip_block_number)?;
+
+        let mut change_builder = DefaultChangeBuilder::new(&configuration, change_lock, Vec::new());
+        change_builder.set_ancestors(ancestors_fee, ancestors_weight);
+
+        build_with_selector(
+            tx,
+            change_builder,
+            inputs,
+            input_iter,
+            None,
+            &configuration,
+            contexts,
+        )
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::anyhow;
+use ckb_types::{
+    core::Capacity,
+    packed::{self, CellOutput, Script},
+    prelude::{Builder, Entity, Pack},
+};
+
+use crate::{
+    core::TransactionBuilder,
+    transaction::{
+        handler::{dao::is_dao_context, HandlerContexts},
+        input::{InputIterator, TransactionInput},
+        TransactionBuilderConfiguration,
+    },
+    tx_builder::TxBuilderError,
+    TransactionWithScriptGroups,
+};
+
+use super::{build_with_selector, CkbTransactionBuilder, DefaultChangeBuilder, PinnedInputs};
+
+/// A Nervos DAO transaction builder implementation, it works with the DAO contexts in
+/// [`crate::transaction::handler::dao`] to build deposit, prepare and withdraw transactions.
+///
+/// The inputs from the input iterator are only used to pay the fee (and the deposit capacity),
+/// the DAO compensation of a withdraw transaction goes to the change output.
+pub struct DaoTransactionBuilder {
+    /// The change lock script, the default change lock script is the last lock script of the input iterator
+    change_lock: Script,
+    /// The transaction builder configuration
+    configuration: TransactionBuilderConfiguration,
+    /// The input iterator, used for building transaction with cell collector
+    input_iter: InputIterator,
+    /// The inputs which must be included in the transaction
+    inputs: Vec<TransactionInput>,
+    /// The inner transaction builder
+    tx: TransactionBuilder,
+}
+
+impl DaoTransactionBuilder {
+    pub fn new(configuration: TransactionBuilderConfiguration, input_iter: InputIterator) -> Self {
+        Self {
+            change_lock: input_iter
+                .lock_scripts()
+                .last()
+                .expect("input iter should not be empty")
+                .clone(),
+            configuration,
+            input_iter,
+            inputs: Vec::new(),
+            tx: TransactionBuilder::default(),
+        }
+    }
+
+    /// Update the change lock script.
+    pub fn set_change_lock(&mut self, lock_script: Script) {
+        self.change_lock = lock_script;
+    }
+
+    /// Add an output cell and output data to the transaction.
+    pub fn add_output_and_data(&mut self, output: CellOutput, data: packed::Bytes) {
+        self.tx.output(output);
+        self.tx.output_data(data);
+    }
+
+    /// Add an output cell with the given lock script and capacity, the type script and the output data are empty.
+    pub fn add_output<S: Into<Script>>(&mut self, output_lock_script: S, capacity: Capacity) {
+        let output = CellOutput::new_builder()
+            .capacity(capacity.pack())
+            .lock(output_lock_script.into())
+            .build();
+        self.add_output_and_data(output, packed::Bytes::default());
+    }
+}
+
+impl PinnedInputs for DaoTransactionBuilder {
+    fn pinned_inputs_mut(&mut self) -> &mut Vec<TransactionInput> {
+        &mut self.inputs
+    }
+}
+
+impl CkbTransactionBuilder for DaoTransactionBuilder {
+    fn build(
+        self,
+        contexts: &HandlerContexts,
+    ) -> Result<TransactionWithScriptGroups, TxBuilderError> {
+        if !contexts
+            .contexts
+            .iter()
+            .any(|context| is_dao_context(context.as_ref()))
+        {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "no dao context found"
+            )));
+        }
+
+        let Self {
+            change_lock,
+            mut configuration,
+            input_iter,
+            inputs,
+            tx,
+        } = self;
+        configuration.update_fee_rate()?;
+
+        let change_builder = DefaultChangeBuilder::new(&configuration, change_lock, Vec::new());
+
+        build_with_selector(
+            tx,
+            change_builder,
+            inputs,
+            input_iter,
+            None,
+            &configuration,
+            contexts,
+        )
+    }
+}
@@ -20,13 +20,36 @@ impl FeeCalculator {
         self.fee(tx_size)
     }
 
+    /// The fee of a child transaction to make the fee rate of the package with its unconfirmed
+    /// ancestors reach the fee rate, it's not less than the fee of the transaction itself.
+    pub fn fee_with_ancestors(
+        &self,
+        weight: u64,
+        ancestors_fee: u64,
+        ancestors_weight: u64,
+    ) -> u64 {
+        self.fee(ancestors_weight.saturating_add(weight))
+            .saturating_sub(ancestors_fee)
+            .max(self.fee(weight))
+    }
+
     pub fn fee_with_tx_builder(&self, tx_builder: &TransactionBuilder) -> u64 {
+        self.fee_with_tx_builder_and_cycles(tx_builder, 0)
+    }
+
+    /// The fee of the transaction, the weight is the larger one of the transaction size and the cycles
+    /// converted with [`bytes_per_cycle`].
+    pub fn fee_with_tx_builder_and_cycles(
+        &self,
+        tx_builder: &TransactionBuilder,
+        cycles: u64,
+    ) -> u64 {
         let tx_size = tx_builder
             .clone()
             .build()
             .data()
             .as_reader()
             .serialized_size_in_block();
-        self.fee(tx_size as u64)
+        self.fee_with_cycle(tx_size as u64, cycles)
     }
 }
@@ -1,24 +1,42 @@
 use std::collections::HashMap;
 
-use super::{handler::HandlerContexts, input::TransactionInput};
+use anyhow::anyhow;
+
+use super::{
+    handler::HandlerContexts,
+    input::{InputIterator, InputSelector, TransactionInput},
+};
 use crate::{
     core::TransactionBuilder,
-    traits::CellCollectorError,
+    traits::{CellCollectorError, HeaderDepResolver, TransactionDependencyProvider},
     transaction::TransactionBuilderConfiguration,
-    tx_builder::{BalanceTxCapacityError, TxBuilderError},
+    tx_builder::{bytes_per_cycle, BalanceTxCapacityError, TxBuilderError},
     ScriptGroup, TransactionWithScriptGroups,
 };
 use ckb_types::{
+    bytes::Bytes,
     core::{Capacity, TransactionView},
-    packed::{self, Byte32, CellOutput, Script},
+    packed::{self, Byte32, CellOutput, OutPoint, Script, WitnessArgs},
     prelude::{Builder, Entity, Pack, Unpack},
 };
+pub mod batch;
+pub mod consolidation;
+pub mod cpfp;
+pub mod dao;
 pub mod fee_calculator;
 pub mod simple;
 pub mod sudt;
 
+pub use batch::{BatchFeeMode, BatchPaymentBuilder, PaymentBatch, RecipientPayment};
+pub use consolidation::ConsolidationBuilder;
+pub use cpfp::CpfpTransactionBuilder;
+pub use dao::DaoTransactionBuilder;
 pub use fee_calculator::FeeCalculator;
 pub use simple::SimpleTransactionBuilder;
+pub use sudt::{SudtChangeBuilder, SudtTransactionBuilder};
+
+/// The max cycles of a block, the cycles limit to run a script group.
+const MAX_BLOCK_CYCLES: u64 = 3_500_000_000;
 
 /// CKB transaction builder trait.
 pub trait CkbTransactionBuilder {
@@ -28,23 +46,112 @@ pub trait CkbTransactionBuilder {
     ) -> Result<TransactionWithScriptGroups, TxBuilderError>;
 }
 
+/// The transaction builders accepting the inputs which must be included in the transaction,
+/// they are placed before the inputs collected by the input iterator.
+pub trait PinnedInputs {
+    /// The inputs which must be included in the transaction.
+    fn pinned_inputs_mut(&mut self) -> &mut Vec<TransactionInput>;
+
+    /// Add an input which must be included in the transaction.
+    fn add_input(&mut self, input: TransactionInput) {
+        self.pinned_inputs_mut().push(input);
+    }
+
+    /// Add the cell of the out point as an input which must be included in the transaction,
+    /// see [`TransactionInput::new_with_out_point`].
+    fn add_input_out_point(
+        &mut self,
+        out_point: OutPoint,
+        since: u64,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+        header_dep_resolver: &dyn HeaderDepResolver,
+    ) -> Result<(), TxBuilderError> {
+        let input = TransactionInput::new_with_out_point(
+            out_point,
+            since,
+            tx_dep_provider,
+            header_dep_resolver,
+        )?;
+        self.add_input(input);
+        Ok(())
+    }
+}
+
+/// The policy to handle the change which is less than the occupied capacity of the change output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SmallChangePolicy {
+    /// Collect more inputs until the change output is valid.
+    #[default]
+    CollectMoreInputs,
+    /// Remove the change output and pay the change as fee, if the change is not more than `max_extra_fee` shannons.
+    MergeIntoFee { max_extra_fee: u64 },
+    /// Remove the change output and add the change to the output at `output_index`.
+    AddToOutput { output_index: usize },
+}
+
+/// How the change of a transaction is handled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeDecision {
+    /// The change is put into the change output at `output_index`.
+    ChangeOutput { output_index: usize, capacity: u64 },
+    /// There is no change output, the change is paid as extra fee.
+    MergedIntoFee { capacity: u64 },
+    /// There is no change output, the change is added to the output at `output_index`.
+    AddedToOutput { output_index: usize, capacity: u64 },
+}
+
 /// Change output builder trait.
 pub trait ChangeBuilder {
     /// Initialize the change output and data, and add it to the transaction builder.
     fn init(&self, tx: &mut TransactionBuilder);
 
-    /// Check if the inputs has enough capacity to build the transaction and pay the fee.
-    fn check_balance(&mut self, input: TransactionInput, tx: &mut TransactionBuilder) -> bool;
+    /// Add an input which can be used to balance the transaction.
+    fn add_input(&mut self, input: TransactionInput);
+
+    /// Check if the inputs has enough capacity to build the transaction and pay the fee,
+    /// `tx_size` is the estimated size in bytes of the final transaction.
+    fn check_balance(&mut self, tx: &mut TransactionBuilder, tx_size: u64) -> bool;
 
-    /// Finalize the transaction with the change capacity and data.
-    fn finalize(&self, tx: TransactionBuilder) -> TransactionView;
+    /// Set the cycles of the transaction, the fee of [`finalize`](ChangeBuilder::finalize) should be
+    /// paid by the larger one of the transaction size and the cycles converted with `bytes_per_cycle`.
+    ///
+    /// The default implementation ignores the cycles.
+    fn set_cycles(&mut self, _cycles: u64) {}
+
+    /// Merge the change up to `max_extra_fee` into the fee instead of adding a change output, e.g. the
+    /// inputs are matched by the [`BranchAndBoundSelector`](crate::transaction::input::BranchAndBoundSelector)
+    /// without a change output.
+    ///
+    /// The default implementation ignores it.
+    fn set_max_extra_fee(&mut self, _max_extra_fee: u64) {}
+
+    /// Finalize the transaction with the change capacity and data, and report how the change is handled.
+    fn finalize(
+        &self,
+        tx: TransactionBuilder,
+    ) -> Result<(TransactionView, ChangeDecision), TxBuilderError>;
 }
 
 /// A simple implementation for the change output builder trait.
+///
+/// The change less than the occupied capacity of the change output is handled by
+/// the [`SmallChangePolicy`] of the configuration.
 pub struct DefaultChangeBuilder<'a> {
     configuration: &'a TransactionBuilderConfiguration,
     change_lock: Script,
     inputs: Vec<TransactionInput>,
+    /// Whether the change output should be removed according to the small change policy
+    remove_change: bool,
+    /// The change merged into the fee regardless of the small change policy, see [`ChangeBuilder::set_max_extra_fee`]
+    max_extra_fee: Option<u64>,
+    /// Whether the removed change is merged into the fee by `max_extra_fee`
+    merge_change: bool,
+    /// The cycles of the transaction, it's 0 if the cycles are not checked
+    cycles: u64,
+    /// The total fee of the unconfirmed ancestors, see [`set_ancestors`](DefaultChangeBuilder::set_ancestors)
+    ancestors_fee: u64,
+    /// The total weight of the unconfirmed ancestors
+    ancestors_weight: u64,
 }
 
 impl<'a> DefaultChangeBuilder<'a> {
@@ -58,9 +165,22 @@ impl<'a> DefaultChangeBuilder<'a> {
             configuration,
             change_lock,
             inputs,
+            remove_change: false,
+            max_extra_fee: None,
+            merge_change: false,
+            cycles: 0,
+            ancestors_fee: 0,
+            ancestors_weight: 0,
         }
     }
 
+    /// Set the total fee and weight of the unconfirmed ancestors, the fee is paid to make the fee rate
+    /// of the package reach the fee rate of the configuration (CPFP).
+    pub fn set_ancestors(&mut self, ancestors_fee: u64, ancestors_weight: u64) {
+        self.ancestors_fee = ancestors_fee;
+        self.ancestors_weight = ancestors_weight;
+    }
+
     /// Returns the change output and its data.
     pub fn get_change(&self) -> (CellOutput, packed::Bytes) {
         let change_output = CellOutput::new_builder()
@@ -69,6 +189,25 @@ impl<'a> DefaultChangeBuilder<'a> {
         let change_output_data = packed::Bytes::default();
         (change_output, change_output_data)
     }
+
+    fn fee(&self, weight: u64) -> u64 {
+        self.configuration.fee_calculator().fee_with_ancestors(
+            weight,
+            self.ancestors_fee,
+            self.ancestors_weight,
+        )
+    }
+
+    fn inputs_capacity(&self) -> u64 {
+        self.inputs.iter().map(|i| i.capacity()).sum()
+    }
+}
+
+fn outputs_capacity(tx: &TransactionBuilder) -> u64 {
+    tx.get_outputs()
+        .iter()
+        .map(|o| Unpack::<u64>::unpack(&o.capacity()))
+        .sum()
 }
 
 impl<'a> ChangeBuilder for DefaultChangeBuilder<'a> {
@@ -78,59 +217,275 @@ impl<'a> ChangeBuilder for DefaultChangeBuilder<'a> {
         tx.output_data(change_output_data);
     }
 
-    fn check_balance(&mut self, input: TransactionInput, tx: &mut TransactionBuilder) -> bool {
+    fn add_input(&mut self, input: TransactionInput) {
         self.inputs.push(input);
+    }
 
-        let outputs_capacity: u64 = tx
-            .get_outputs()
-            .iter()
-            .map(|o| Unpack::<u64>::unpack(&o.capacity()))
-            .sum();
+    fn set_cycles(&mut self, cycles: u64) {
+        self.cycles = cycles;
+    }
+
+    fn set_max_extra_fee(&mut self, max_extra_fee: u64) {
+        self.max_extra_fee = Some(max_extra_fee);
+    }
+
+    fn check_balance(&mut self, tx: &mut TransactionBuilder, tx_size: u64) -> bool {
+        self.remove_change = false;
+        self.merge_change = false;
+        let outputs_capacity = outputs_capacity(tx);
         let (change_output, change_output_data) = self.get_change();
         let occupied_capacity = change_output
             .occupied_capacity(Capacity::bytes(change_output_data.len()).unwrap())
             .unwrap()
             .as_u64();
 
-        let fee_calculator = self.configuration.fee_calculator();
-        let required_capacity = outputs_capacity
-            + occupied_capacity
-            + fee_calculator.fee(self.configuration.estimate_tx_size);
+        let required_capacity = outputs_capacity + occupied_capacity + self.fee(tx_size);
 
-        let inputs_capacity: u64 = self
-            .inputs
-            .iter()
-            .map(|i| Unpack::<u64>::unpack(&i.previous_output().capacity()))
-            .sum();
-        inputs_capacity >= required_capacity
+        let inputs_capacity = self.inputs_capacity();
+        if inputs_capacity >= required_capacity {
+            return true;
+        }
+
+        // the change is too small for a change output, try the small change policy,
+        // the output and its data take 4 more bytes each for the offset in the vector
+        let change_size =
+            (change_output.as_slice().len() + change_output_data.as_slice().len() + 8) as u64;
+        let fee = self.fee(tx_size.saturating_sub(change_size));
+        let small_change = match inputs_capacity.checked_sub(outputs_capacity + fee) {
+            Some(small_change) => small_change,
+            None => return false,
+        };
+        if self
+            .max_extra_fee
+            .is_some_and(|max_extra_fee| small_change <= max_extra_fee)
+        {
+            log::debug!("small change {} shannons, merge into fee", small_change);
+            self.remove_change = true;
+            self.merge_change = true;
+            return true;
+        }
+        self.remove_change = match self.configuration.small_change_policy {
+            SmallChangePolicy::CollectMoreInputs => {
+                log::debug!(
+                    "small change {} shannons, collect more inputs",
+                    small_change
+                );
+                false
+            }
+            SmallChangePolicy::MergeIntoFee { max_extra_fee } => small_change <= max_extra_fee,
+            SmallChangePolicy::AddToOutput { .. } => true,
+        };
+        self.remove_change
     }
 
-    fn finalize(&self, mut tx: TransactionBuilder) -> TransactionView {
-        // update change output capacity to real value
-        let fee_calculator = self.configuration.fee_calculator();
-        let fee = fee_calculator.fee_with_tx_builder(&tx);
-        let inputs_capacity: u64 = self
-            .inputs
-            .iter()
-            .map(|i| Unpack::<u64>::unpack(&i.previous_output().capacity()))
-            .sum();
+    fn finalize(
+        &self,
+        mut tx: TransactionBuilder,
+    ) -> Result<(TransactionView, ChangeDecision), TxBuilderError> {
+        // the change output is always the last output
+        let change_index = tx.outputs.len() - 1;
+        if self.remove_change {
+            tx.outputs.pop();
+            tx.outputs_data.pop();
+        }
+
+        // update change capacity to real value
+        let tx_size = tx
+            .clone()
+            .build()
+            .data()
+            .as_reader()
+            .serialized_size_in_block() as u64;
+        let fee = self.fee(tx_size.max((self.cycles as f64 * bytes_per_cycle()) as u64));
+        let inputs_capacity = self.inputs_capacity();
+        let outputs_capacity = outputs_capacity(&tx);
+        let change_capacity = inputs_capacity
+            .checked_sub(outputs_capacity + fee)
+            .ok_or_else(|| {
+                BalanceTxCapacityError::CapacityNotEnough(format!(
+                    "inputs capacity {} is less than outputs capacity {} plus fee {}",
+                    inputs_capacity, outputs_capacity, fee
+                ))
+            })?;
+
+        let decision = match (self.remove_change, self.configuration.small_change_policy) {
+            (false, _) => {
+                let change_output = tx.outputs[change_index]
+                    .clone()
+                    .as_builder()
+                    .capacity(change_capacity.pack())
+                    .build();
+                let occupied_capacity = change_output
+                    .occupied_capacity(
+                        Capacity::bytes(tx.outputs_data[change_index].len()).unwrap(),
+                    )
+                    .unwrap()
+                    .as_u64();
+                if change_capacity < occupied_capacity {
+                    return Err(BalanceTxCapacityError::CapacityNotEnough(format!(
+                        "change capacity {} is less than the occupied capacity {}",
+                        change_capacity, occupied_capacity
+                    ))
+                    .into());
+                }
+                tx.set_output(change_index, change_output);
+                ChangeDecision::ChangeOutput {
+                    output_index: change_index,
+                    capacity: change_capacity,
+                }
+            }
+            (true, SmallChangePolicy::AddToOutput { output_index }) if !self.merge_change => {
+                let output = tx
+                    .outputs
+                    .get(output_index)
+                    .ok_or(TxBuilderError::NoOutputForSmallChange)?;
+                let capacity: u64 = output.capacity().unpack();
+                let output = output
+                    .clone()
+                    .as_builder()
+                    .capacity((capacity + change_capacity).pack())
+                    .build();
+                tx.set_output(output_index, output);
+                ChangeDecision::AddedToOutput {
+                    output_index,
+                    capacity: change_capacity,
+                }
+            }
+            (true, _) => ChangeDecision::MergedIntoFee {
+                capacity: change_capacity,
+            },
+        };
+        log::debug!("change decision of the transaction: {:?}", decision);
+        Ok((tx.build(), decision))
+    }
+}
+
+/// a helper fn to build a transaction with the `required_inputs` placed in front of the inputs
+/// from the input iterator, the inputs from the input iterator are ordered by the input selector if any,
+/// at most `max_candidates` of the selector are collected from the input iterator.
+///
+/// The built transaction is added to the wallet cell cache of the input iterator as a pending transaction,
+/// see [`InputIterator::add_pending_tx`].
+fn build_with_selector<CB: ChangeBuilder>(
+    tx: TransactionBuilder,
+    change_builder: CB,
+    required_inputs: Vec<TransactionInput>,
+    mut input_iter: InputIterator,
+    input_selector: Option<Box<dyn InputSelector>>,
+    configuration: &TransactionBuilderConfiguration,
+    contexts: &HandlerContexts,
+) -> Result<TransactionWithScriptGroups, TxBuilderError> {
+    // the required inputs may also be collected by the input iterator
+    let required_out_points: Vec<OutPoint> = required_inputs
+        .iter()
+        .map(|input| input.live_cell.out_point.clone())
+        .collect();
+    let filtered_iter = input_iter.by_ref().filter(move |input| match input {
+        Ok(input) => !required_out_points.contains(&input.live_cell.out_point),
+        Err(_) => true,
+    });
+
+    let tx_with_groups = if let Some(mut input_selector) = input_selector {
+        let max_candidates = input_selector.max_candidates().unwrap_or(usize::MAX);
+        let candidates = filtered_iter
+            .take(max_candidates)
+            .collect::<Result<Vec<_>, _>>()?;
         let outputs_capacity: u64 = tx
             .get_outputs()
             .iter()
             .map(|o| Unpack::<u64>::unpack(&o.capacity()))
             .sum();
-        let change_capacity = inputs_capacity - outputs_capacity - fee;
-        let change_output = tx
-            .outputs
-            .last()
-            .unwrap()
-            .clone()
-            .as_builder()
-            .capacity(change_capacity.pack())
-            .build();
-        tx.set_output(tx.outputs.len() - 1, change_output);
-        tx.build()
+        let required_capacity: u64 = required_inputs.iter().map(|i| i.capacity()).sum();
+        let (base_fee, input_fee) = estimate_selection_fee(
+            &tx,
+            &required_inputs,
+            candidates.first(),
+            configuration,
+            contexts,
+        );
+        input_selector.set_fee(base_fee, input_fee);
+        let mut change_builder = change_builder;
+        if let Some(max_extra_fee) = input_selector.max_extra_fee() {
+            change_builder.set_max_extra_fee(max_extra_fee);
+        }
+        let candidate_out_points: Vec<OutPoint> = candidates
+            .iter()
+            .map(|input| input.live_cell.out_point.clone())
+            .collect();
+        let selected = input_selector.select(
+            candidates,
+            outputs_capacity.saturating_sub(required_capacity),
+        );
+        // only the selected candidates stay reserved in the wallet cell cache
+        let unselected: Vec<OutPoint> = candidate_out_points
+            .into_iter()
+            .filter(|out_point| {
+                !selected
+                    .iter()
+                    .any(|input| &input.live_cell.out_point == out_point)
+            })
+            .collect();
+        input_iter.release_cells(&unselected);
+        inner_build(
+            tx,
+            change_builder,
+            required_inputs,
+            selected.into_iter().map(Ok),
+            configuration,
+            contexts,
+        )?
+    } else {
+        inner_build(
+            tx,
+            change_builder,
+            required_inputs,
+            filtered_iter,
+            configuration,
+            contexts,
+        )?
+    };
+    input_iter.add_pending_tx(tx_with_groups.get_tx_view())?;
+    Ok(tx_with_groups)
+}
+
+/// Estimate the fee of the transaction without a change output for the input selector, the base fee
+/// includes the required inputs and the placeholder witness for the lock script of the first candidate,
+/// the other candidates are assumed to share the lock script groups.
+fn estimate_selection_fee(
+    tx: &TransactionBuilder,
+    required_inputs: &[TransactionInput],
+    first_candidate: Option<&TransactionInput>,
+    configuration: &TransactionBuilderConfiguration,
+    contexts: &HandlerContexts,
+) -> (u64, u64) {
+    let mut tx = tx.clone();
+    let mut lock_groups: HashMap<Byte32, ScriptGroup> = HashMap::default();
+    for (input_index, input) in required_inputs.iter().chain(first_candidate).enumerate() {
+        tx.input(input.cell_input());
+        if tx.witnesses.len() <= input_index {
+            tx.witness(packed::Bytes::default());
+        }
+        let lock_script = input.previous_output().lock();
+        lock_groups
+            .entry(lock_script.calc_script_hash())
+            .or_insert_with(|| ScriptGroup::from_lock_script(&lock_script))
+            .input_indices
+            .push(input_index);
     }
+    let tx_size = estimate_tx_size(&tx, &lock_groups, configuration, contexts)
+        .unwrap_or(configuration.estimate_tx_size);
+    // the cell input, and the empty witness with its offset in the vector
+    let input_size = (packed::CellInput::TOTAL_SIZE + 8) as u64;
+    let base_size = match first_candidate {
+        Some(_) => tx_size.saturating_sub(input_size),
+        None => tx_size,
+    };
+    let fee_calculator = configuration.fee_calculator();
+    let base_fee = fee_calculator.fee(base_size);
+    let input_fee = fee_calculator
+        .fee(base_size + input_size)
+        .saturating_sub(base_fee);
+    (base_fee, input_fee)
 }
 
 /// a helper fn to build a transaction with common logic
@@ -140,6 +495,7 @@ fn inner_build<
 >(
     mut tx: TransactionBuilder,
     mut change_builder: CB,
+    required_inputs: Vec<TransactionInput>,
     input_iter: I,
     configuration: &TransactionBuilderConfiguration,
     contexts: &HandlerContexts,
@@ -147,25 +503,41 @@ fn inner_build<
     let mut lock_groups: HashMap<Byte32, ScriptGroup> = HashMap::default();
     let mut type_groups: HashMap<Byte32, ScriptGroup> = HashMap::default();
 
-    // setup outputs' type script group
-    for (output_idx, output) in tx.get_outputs().clone().iter().enumerate() {
-        if let Some(type_script) = &output.type_().to_opt() {
-            type_groups
-                .entry(type_script.calc_script_hash())
-                .or_insert_with(|| ScriptGroup::from_type_script(type_script))
-                .output_indices
-                .push(output_idx);
+    // prepare the transaction with script handlers, e.g. add the required inputs and outputs
+    let environment = configuration.get_environment();
+    let mut prepared_inputs = vec![];
+    for handler in configuration.get_script_handlers() {
+        for context in &contexts.contexts {
+            if handler.prepare_transaction_with_environment(
+                &mut prepared_inputs,
+                &mut tx,
+                context.as_ref(),
+                environment,
+            )? {
+                break;
+            }
         }
     }
+    // the required inputs follow the prepared inputs, all of them must be included
+    prepared_inputs.extend(required_inputs);
+    let prepared_inputs_len = prepared_inputs.len();
 
     // setup change output and data
     change_builder.init(&mut tx);
 
-    // collect inputs
-    for (input_index, input) in input_iter.enumerate() {
+    // collect inputs, the prepared inputs are always placed in front
+    for (input_index, input) in prepared_inputs
+        .into_iter()
+        .map(Ok)
+        .chain(input_iter)
+        .enumerate()
+    {
         let input = input?;
         tx.input(input.cell_input());
-        tx.witness(packed::Bytes::default());
+        // the witness may already be set by script handlers while preparing
+        if tx.witnesses.len() <= input_index {
+            tx.witness(packed::Bytes::default());
+        }
 
         let previous_output = input.previous_output();
         let lock_script = previous_output.lock();
@@ -183,29 +555,160 @@ fn inner_build<
                 .push(input_index);
         }
 
-        // check if we have enough inputs
-        if change_builder.check_balance(input, &mut tx) {
-            // handle script groups
-            let mut script_groups: Vec<ScriptGroup> = lock_groups
-                .into_values()
-                .chain(type_groups.into_values())
-                .collect();
-
-            for script_group in script_groups.iter_mut() {
-                for handler in configuration.get_script_handlers() {
-                    for context in &contexts.contexts {
-                        if handler.build_transaction(&mut tx, script_group, context.as_ref())? {
-                            break;
-                        }
+        change_builder.add_input(input);
+        // all the prepared inputs must be included
+        if input_index + 1 < prepared_inputs_len {
+            continue;
+        }
+
+        // check if we have enough inputs with the estimated transaction size
+        let estimated_tx_size = estimate_tx_size(&tx, &lock_groups, configuration, contexts)
+            .unwrap_or(configuration.estimate_tx_size);
+        if !change_builder.check_balance(&mut tx, estimated_tx_size) {
+            continue;
+        }
+
+        // setup outputs' type script group, the change output may have a type script,
+        // which may be updated by the change builder while balancing
+        let mut output_type_groups = type_groups.clone();
+        for (output_idx, output) in tx.get_outputs().iter().enumerate() {
+            if let Some(type_script) = &output.type_().to_opt() {
+                output_type_groups
+                    .entry(type_script.calc_script_hash())
+                    .or_insert_with(|| ScriptGroup::from_type_script(type_script))
+                    .output_indices
+                    .push(output_idx);
+            }
+        }
+
+        // handle script groups
+        let mut final_tx = tx.clone();
+        let mut script_groups: Vec<ScriptGroup> = lock_groups
+            .values()
+            .cloned()
+            .chain(output_type_groups.into_values())
+            .collect();
+        for script_group in script_groups.iter_mut() {
+            for handler in configuration.get_script_handlers() {
+                for context in &contexts.contexts {
+                    if handler.build_transaction_with_environment(
+                        &mut final_tx,
+                        script_group,
+                        context.as_ref(),
+                        environment,
+                    )? {
+                        break;
                     }
                 }
             }
+        }
 
-            let tx_view = change_builder.finalize(tx);
-
-            return Ok(TransactionWithScriptGroups::new(tx_view, script_groups));
+        // re-check with the real size, the handlers may add more data than estimated
+        let tx_size = final_tx
+            .clone()
+            .build()
+            .data()
+            .as_reader()
+            .serialized_size_in_block() as u64;
+        if tx_size <= estimated_tx_size || change_builder.check_balance(&mut final_tx, tx_size) {
+            if configuration.check_cycle_fee {
+                // the heavy scripts must pay the fee by cycles, re-check with the cycles weight
+                let cycles = estimate_cycles(&final_tx, &script_groups, configuration, contexts)?;
+                let cycles_size = (cycles as f64 * bytes_per_cycle()) as u64;
+                change_builder.set_cycles(cycles);
+                if cycles_size > tx_size.max(estimated_tx_size)
+                    && !change_builder.check_balance(&mut final_tx, cycles_size)
+                {
+                    continue;
+                }
+            }
+            let (tx_view, change_decision) = change_builder.finalize(final_tx)?;
+            let mut tx_with_groups = TransactionWithScriptGroups::new(tx_view, script_groups);
+            tx_with_groups.set_change_decision(Some(change_decision));
+            tx_with_groups.set_fee_rate(Some(configuration.get_fee_rate()));
+            return Ok(tx_with_groups);
         }
     }
 
     Err(BalanceTxCapacityError::CapacityNotEnough("can not find enough inputs".to_string()).into())
 }
+
+/// Estimate the cycles of the transaction by running the script groups with the build environment,
+/// the cycles of a script group which fails with the placeholder witnesses are estimated by the
+/// script handlers, an error is returned if no handler can estimate them.
+fn estimate_cycles(
+    tx: &TransactionBuilder,
+    script_groups: &[ScriptGroup],
+    configuration: &TransactionBuilderConfiguration,
+    contexts: &HandlerContexts,
+) -> Result<u64, TxBuilderError> {
+    let tx_view = tx.clone().build();
+    let environment = configuration.get_environment();
+    let mut total_cycles = 0u64;
+    for script_group in script_groups {
+        let cycles = match environment.verify_script_group(&tx_view, script_group, MAX_BLOCK_CYCLES)
+        {
+            Ok(cycles) => cycles,
+            Err(err) => {
+                let estimated = configuration
+                    .get_script_handlers()
+                    .iter()
+                    .find_map(|handler| {
+                        contexts.contexts.iter().find_map(|context| {
+                            handler.estimate_cycles(script_group, context.as_ref())
+                        })
+                    });
+                log::debug!(
+                    "verify script group {} failed: {}, estimated cycles: {:?}",
+                    script_group.script,
+                    err,
+                    estimated
+                );
+                estimated.ok_or_else(|| {
+                    TxBuilderError::Other(anyhow!(
+                        "can not estimate the cycles of the script group {}, \
+                         it fails with the placeholder witnesses: {}",
+                        script_group.script,
+                        err
+                    ))
+                })?
+            }
+        };
+        total_cycles = total_cycles.saturating_add(cycles);
+    }
+    Ok(total_cycles)
+}
+
+/// Estimate the size of the transaction after the placeholder witnesses of the lock script groups
+/// are filled, return None if any lock script group can not be estimated by the script handlers.
+fn estimate_tx_size(
+    tx: &TransactionBuilder,
+    lock_groups: &HashMap<Byte32, ScriptGroup>,
+    configuration: &TransactionBuilderConfiguration,
+    contexts: &HandlerContexts,
+) -> Option<u64> {
+    let mut tx = tx.clone();
+    for script_group in lock_groups.values() {
+        let lock_size = configuration
+            .get_script_handlers()
+            .iter()
+            .find_map(|handler| {
+                contexts.contexts.iter().find_map(|context| {
+                    handler.estimate_witness_size(script_group, context.as_ref())
+                })
+            })?;
+        let index = *script_group.input_indices.first()?;
+        let witness_data = tx.get_witnesses().get(index)?.raw_data();
+        let witness = if witness_data.is_empty() {
+            WitnessArgs::new_builder()
+        } else {
+            WitnessArgs::from_slice(witness_data.as_ref())
+                .ok()?
+                .as_builder()
+        }
+        .lock(Some(Bytes::from(vec![0u8; lock_size])).pack())
+        .build();
+        tx.set_witness(index, witness.as_bytes().pack());
+    }
+    Some(tx.build().data().as_reader().serialized_size_in_block() as u64)
+}
@@ -1,7 +1,9 @@
 use crate::{
     core::TransactionBuilder,
     transaction::{
-        handler::HandlerContexts, input::InputIterator, TransactionBuilderConfiguration,
+        handler::HandlerContexts,
+        input::{InputIterator, InputSelector, TransactionInput},
+        TransactionBuilderConfiguration,
     },
     tx_builder::TxBuilderError,
     TransactionWithScriptGroups,
@@ -12,7 +14,7 @@ use ckb_types::{
     prelude::{Builder, Entity, Pack},
 };
 
-use super::{inner_build, CkbTransactionBuilder, DefaultChangeBuilder};
+use super::{build_with_selector, CkbTransactionBuilder, DefaultChangeBuilder, PinnedInputs};
 
 /// A simple transaction builder implementation, it will build a transaction with enough capacity to pay for the outputs and the fee.
 pub struct SimpleTransactionBuilder {
@@ -22,6 +24,10 @@ pub struct SimpleTransactionBuilder {
     configuration: TransactionBuilderConfiguration,
     /// The input iterator, used for building transaction with cell collector
     input_iter: InputIterator,
+    /// The input selector, used for choosing and ordering the inputs from the input iterator
+    input_selector: Option<Box<dyn InputSelector>>,
+    /// The inputs which must be included in the transaction
+    inputs: Vec<TransactionInput>,
     /// The inner transaction builder
     tx: TransactionBuilder,
 }
@@ -36,10 +42,17 @@ impl SimpleTransactionBuilder {
                 .clone(),
             configuration,
             input_iter,
+            input_selector: None,
+            inputs: Vec::new(),
             tx: TransactionBuilder::default(),
         }
     }
 
+    /// Set the input selector, the inputs are consumed in the indexer order by default.
+    pub fn set_input_selector(&mut self, input_selector: Box<dyn InputSelector>) {
+        self.input_selector = Some(input_selector);
+    }
+
     /// Update the change lock script.
     pub fn set_change_lock(&mut self, lock_script: Script) {
         self.change_lock = lock_script;
@@ -61,6 +74,12 @@ impl SimpleTransactionBuilder {
     }
 }
 
+impl PinnedInputs for SimpleTransactionBuilder {
+    fn pinned_inputs_mut(&mut self) -> &mut Vec<TransactionInput> {
+        &mut self.inputs
+    }
+}
+
 impl CkbTransactionBuilder for SimpleTransactionBuilder {
     fn build(
         self,
@@ -68,17 +87,24 @@ impl CkbTransactionBuilder for SimpleTransactionBuilder {
     ) -> Result<TransactionWithScriptGroups, TxBuilderError> {
         let Self {
             change_lock,
-            configuration,
+            mut configuration,
             input_iter,
+            input_selector,
+            inputs,
             tx,
         } = self;
+        configuration.update_fee_rate()?;
 
-        let change_builder = DefaultChangeBuilder {
-            configuration: &configuration,
-            change_lock,
-            inputs: Vec::new(),
-        };
+        let change_builder = DefaultChangeBuilder::new(&configuration, change_lock, Vec::new());
 
-        inner_build(tx, change_builder, input_iter, &configuration, contexts)
+        build_with_selector(
+            tx,
+            change_builder,
+            inputs,
+            input_iter,
+            input_selector,
+            &configuration,
+            contexts,
+        )
     }
 }
@@ -3,7 +3,9 @@ use std::convert::TryInto;
 use crate::{
     core::TransactionBuilder,
     transaction::{
-        handler::HandlerContexts, input::InputIterator, TransactionBuilderConfiguration,
+        handler::HandlerContexts,
+        input::{InputIterator, InputSelector, TransactionInput},
+        TransactionBuilderConfiguration,
     },
     tx_builder::{BalanceTxCapacityError, TxBuilderError},
     NetworkInfo, NetworkType, TransactionWithScriptGroups,
@@ -11,13 +13,16 @@ use crate::{
 use anyhow::anyhow;
 
 use ckb_types::{
-    core::{Capacity, ScriptHashType},
+    core::{Capacity, ScriptHashType, TransactionView},
     h256,
-    packed::{self, Bytes, CellOutput, Script},
+    packed::{self, CellOutput, Script},
     prelude::*,
 };
 
-use super::{inner_build, CkbTransactionBuilder, DefaultChangeBuilder};
+use super::{
+    build_with_selector, ChangeBuilder, ChangeDecision, CkbTransactionBuilder,
+    DefaultChangeBuilder, PinnedInputs,
+};
 
 /// A sUDT transaction builder implementation
 pub struct SudtTransactionBuilder {
@@ -27,10 +32,14 @@ pub struct SudtTransactionBuilder {
     configuration: TransactionBuilderConfiguration,
     /// The input iterator, used for building transaction with cell collector
     input_iter: InputIterator,
+    /// The input selector, used for choosing and ordering the capacity inputs from the input iterator
+    input_selector: Option<Box<dyn InputSelector>>,
     /// The identifier of the sUDT
     sudt_owner_lock_script: Script,
     /// Whether we are in owner mode
     owner_mode: bool,
+    /// The inputs which must be included in the transaction
+    inputs: Vec<TransactionInput>,
     /// The inner transaction builder
     tx: TransactionBuilder,
 }
@@ -50,8 +59,10 @@ impl SudtTransactionBuilder {
                 .clone(),
             configuration,
             input_iter,
+            input_selector: None,
             sudt_owner_lock_script: sudt_owner_lock_script.into(),
             owner_mode,
+            inputs: Vec::new(),
             tx: TransactionBuilder::default(),
         })
     }
@@ -61,6 +72,11 @@ impl SudtTransactionBuilder {
         self.change_lock = lock_script;
     }
 
+    /// Set the input selector for the capacity inputs, the sUDT inputs are always collected in the indexer order.
+    pub fn set_input_selector(&mut self, input_selector: Box<dyn InputSelector>) {
+        self.input_selector = Some(input_selector);
+    }
+
     /// Add an output cell and output data to the transaction.
     pub fn add_output_and_data(&mut self, output: CellOutput, data: packed::Bytes) {
         self.tx.output(output);
@@ -131,70 +147,287 @@ fn parse_u128(data: &[u8]) -> Result<u128, TxBuilderError> {
     Ok(u128::from_le_bytes(data_bytes.try_into().unwrap()))
 }
 
+/// The sUDT amount of a pinned sUDT cell is counted.
+impl PinnedInputs for SudtTransactionBuilder {
+    fn pinned_inputs_mut(&mut self) -> &mut Vec<TransactionInput> {
+        &mut self.inputs
+    }
+}
+
 impl CkbTransactionBuilder for SudtTransactionBuilder {
     fn build(
-        mut self,
+        self,
         contexts: &HandlerContexts,
     ) -> Result<TransactionWithScriptGroups, TxBuilderError> {
-        if !self.owner_mode {
-            // Add change output for sudt with zero amount as placeholder
-            self.add_output(self.change_lock.clone(), 0);
-        }
-
         let Self {
             change_lock,
-            configuration,
-            mut input_iter,
+            mut configuration,
+            input_iter,
+            input_selector,
             sudt_owner_lock_script,
             owner_mode,
-            mut tx,
+            inputs,
+            tx,
         } = self;
-
-        let change_builder = DefaultChangeBuilder {
-            configuration: &configuration,
-            change_lock,
-            inputs: Vec::new(),
-        };
+        configuration.update_fee_rate()?;
 
         if owner_mode {
-            inner_build(tx, change_builder, input_iter, &configuration, contexts)
+            let change_builder = DefaultChangeBuilder::new(&configuration, change_lock, Vec::new());
+            build_with_selector(
+                tx,
+                change_builder,
+                inputs,
+                input_iter,
+                input_selector,
+                &configuration,
+                contexts,
+            )
         } else {
             let sudt_type_script =
                 build_sudt_type_script(configuration.network_info(), &sudt_owner_lock_script);
             let mut sudt_input_iter = input_iter.clone();
-            sudt_input_iter.set_type_script(Some(sudt_type_script));
-
-            let outputs_sudt_amount: u128 = tx
-                .outputs_data
-                .iter()
-                .map(|data| parse_u128(data.raw_data().as_ref()))
-                .collect::<Result<Vec<u128>, TxBuilderError>>()
-                .map(|u128_vec| u128_vec.iter().sum())?;
+            sudt_input_iter.set_type_script(Some(sudt_type_script.clone()));
 
-            let mut inputs_sudt_amount = 0;
+            let outputs_sudt_amount = outputs_sudt_amount(&tx, &sudt_type_script)?;
 
-            for input in sudt_input_iter {
-                let input = input?;
-                let input_amount = parse_u128(input.live_cell.output_data.as_ref())?;
-                inputs_sudt_amount += input_amount;
-                input_iter.push_input(input);
+            // collect the sUDT cells (after the pinned inputs) until the amount is covered, the capacity and the fee
+            // are paid by the plain CKB cells from the input iterator if it's not enough
+            let sudt_type = Some(sudt_type_script.clone()).pack();
+            let mut inputs_sudt_amount = inputs
+                .iter()
+                .filter(|input| input.live_cell.output.type_().as_slice() == sudt_type.as_slice())
+                .try_fold(0u128, |amount, input| {
+                    amount.checked_add(sudt_amount(input.live_cell.output_data.as_ref()))
+                })
+                .ok_or_else(sudt_amount_overflow)?;
+            let mut sudt_inputs = inputs;
+            // the sUDT inputs stay reserved until the transaction is added to the wallet cell cache
+            for input in sudt_input_iter.by_ref() {
                 if inputs_sudt_amount >= outputs_sudt_amount {
-                    let change_output_data: Bytes = (inputs_sudt_amount - outputs_sudt_amount)
-                        .to_le_bytes()
-                        .pack();
-                    tx.set_output_data(tx.outputs_data.len() - 1, change_output_data);
-                    return inner_build(tx, change_builder, input_iter, &configuration, contexts);
+                    break;
+                }
+                let input = input?;
+                let out_point = &input.live_cell.out_point;
+                if sudt_inputs
+                    .iter()
+                    .any(|pinned| &pinned.live_cell.out_point == out_point)
+                {
+                    continue;
                 }
+                inputs_sudt_amount = inputs_sudt_amount
+                    .checked_add(sudt_amount(input.live_cell.output_data.as_ref()))
+                    .ok_or_else(sudt_amount_overflow)?;
+                sudt_inputs.push(input);
+            }
+            if inputs_sudt_amount < outputs_sudt_amount {
+                return Err(BalanceTxCapacityError::CapacityNotEnough(format!(
+                    "can not find enough sudt inputs, required: {}, found: {}",
+                    outputs_sudt_amount, inputs_sudt_amount
+                ))
+                .into());
             }
 
-            Err(
-                BalanceTxCapacityError::CapacityNotEnough("can not find enough inputs".to_string())
-                    .into(),
+            let change_builder =
+                SudtChangeBuilder::new(&configuration, change_lock, sudt_type_script);
+            build_with_selector(
+                tx,
+                change_builder,
+                sudt_inputs,
+                input_iter,
+                input_selector,
+                &configuration,
+                contexts,
             )
         }
     }
 }
 
+/// The sUDT amount of the cell data, it's the first 16 bytes in little endian.
+fn sudt_amount(data: &[u8]) -> u128 {
+    let mut amount_bytes = [0u8; 16];
+    let len = data.len().min(16);
+    amount_bytes[..len].copy_from_slice(&data[..len]);
+    u128::from_le_bytes(amount_bytes)
+}
+
+fn sudt_amount_overflow() -> TxBuilderError {
+    TxBuilderError::Other(anyhow!("sudt amount overflow"))
+}
+
+fn outputs_sudt_amount(
+    tx: &TransactionBuilder,
+    sudt_type_script: &Script,
+) -> Result<u128, TxBuilderError> {
+    let mut amount: u128 = 0;
+    for (output, data) in tx.get_outputs().iter().zip(tx.get_outputs_data().iter()) {
+        if output.type_().to_opt().as_ref() == Some(sudt_type_script) {
+            amount = amount
+                .checked_add(parse_u128(data.raw_data().as_ref())?)
+                .ok_or_else(sudt_amount_overflow)?;
+        }
+    }
+    Ok(amount)
+}
+
+/// A change builder for sUDT transactions, it balances both the sUDT amount and the capacity.
+///
+/// The change output is a sUDT cell which holds the surplus sUDT amount and the surplus capacity,
+/// or a plain CKB cell if all the sUDT amount of the inputs is spent, so the small change policy of
+/// the configuration is not applied.
+pub struct SudtChangeBuilder<'a> {
+    configuration: &'a TransactionBuilderConfiguration,
+    change_lock: Script,
+    sudt_type_script: Script,
+    inputs: Vec<TransactionInput>,
+    /// The sUDT amount of the inputs, None if it overflows
+    inputs_sudt_amount: Option<u128>,
+    cycles: u64,
+}
+
+impl<'a> SudtChangeBuilder<'a> {
+    pub fn new(
+        configuration: &'a TransactionBuilderConfiguration,
+        change_lock: Script,
+        sudt_type_script: Script,
+    ) -> Self {
+        Self {
+            configuration,
+            change_lock,
+            sudt_type_script,
+            inputs: Vec::new(),
+            inputs_sudt_amount: Some(0),
+            cycles: 0,
+        }
+    }
+
+    /// Returns the change output with zero sUDT amount and its data.
+    pub fn get_change(&self) -> (CellOutput, packed::Bytes) {
+        let change_output = CellOutput::new_builder()
+            .lock(self.change_lock.clone())
+            .type_(Some(self.sudt_type_script.clone()).pack())
+            .build();
+        let change_output_data = 0u128.to_le_bytes().pack();
+        (change_output, change_output_data)
+    }
+
+    fn inputs_capacity(&self) -> u64 {
+        self.inputs.iter().map(|i| i.capacity()).sum()
+    }
+
+    /// Update the change output (always the last output) with the sUDT change, it's a plain CKB cell
+    /// if there is no sUDT change. Returns the occupied capacity of the change output.
+    fn update_change(&self, tx: &mut TransactionBuilder) -> Result<u64, TxBuilderError> {
+        let change_index = tx.outputs.len() - 1;
+        let (change_output, change_output_data) = self.get_change();
+        tx.set_output(change_index, change_output.clone());
+        tx.set_output_data(change_index, change_output_data);
+
+        let inputs_sudt_amount = self.inputs_sudt_amount.ok_or_else(sudt_amount_overflow)?;
+        let outputs_sudt_amount = outputs_sudt_amount(tx, &self.sudt_type_script)?;
+        let change_sudt_amount = inputs_sudt_amount
+            .checked_sub(outputs_sudt_amount)
+            .ok_or_else(|| {
+                BalanceTxCapacityError::CapacityNotEnough(format!(
+                    "inputs sudt amount {} is less than outputs sudt amount {}",
+                    inputs_sudt_amount, outputs_sudt_amount
+                ))
+            })?;
+        let (change_output, change_output_data) = if change_sudt_amount == 0 {
+            let change_output = change_output
+                .as_builder()
+                .type_(None::<Script>.pack())
+                .build();
+            (change_output, packed::Bytes::default())
+        } else {
+            (change_output, change_sudt_amount.to_le_bytes().pack())
+        };
+        let occupied_capacity = change_output
+            .occupied_capacity(Capacity::bytes(change_output_data.len()).unwrap())
+            .unwrap()
+            .as_u64();
+        tx.set_output(change_index, change_output);
+        tx.set_output_data(change_index, change_output_data);
+        Ok(occupied_capacity)
+    }
+}
+
+impl<'a> ChangeBuilder for SudtChangeBuilder<'a> {
+    fn init(&self, tx: &mut TransactionBuilder) {
+        let (change_output, change_output_data) = self.get_change();
+        tx.output(change_output);
+        tx.output_data(change_output_data);
+    }
+
+    fn add_input(&mut self, input: TransactionInput) {
+        if input.previous_output().type_().to_opt().as_ref() == Some(&self.sudt_type_script) {
+            self.inputs_sudt_amount = self.inputs_sudt_amount.and_then(|amount| {
+                amount.checked_add(sudt_amount(input.live_cell.output_data.as_ref()))
+            });
+        }
+        self.inputs.push(input);
+    }
+
+    fn set_cycles(&mut self, cycles: u64) {
+        self.cycles = cycles;
+    }
+
+    fn check_balance(&mut self, tx: &mut TransactionBuilder, tx_size: u64) -> bool {
+        let occupied_capacity = match self.update_change(tx) {
+            Ok(occupied_capacity) => occupied_capacity,
+            Err(_) => return false,
+        };
+        let outputs_capacity: u64 = tx
+            .get_outputs()
+            .iter()
+            .map(|o| Unpack::<u64>::unpack(&o.capacity()))
+            .sum();
+        let fee = self.configuration.fee_calculator().fee(tx_size);
+        self.inputs_capacity() >= outputs_capacity + occupied_capacity + fee
+    }
+
+    fn finalize(
+        &self,
+        mut tx: TransactionBuilder,
+    ) -> Result<(TransactionView, ChangeDecision), TxBuilderError> {
+        // the change output is always the last output
+        let change_index = tx.outputs.len() - 1;
+        let occupied_capacity = self.update_change(&mut tx)?;
+
+        let fee = self
+            .configuration
+            .fee_calculator()
+            .fee_with_tx_builder_and_cycles(&tx, self.cycles);
+        let inputs_capacity = self.inputs_capacity();
+        let outputs_capacity: u64 = tx
+            .get_outputs()
+            .iter()
+            .map(|o| Unpack::<u64>::unpack(&o.capacity()))
+            .sum();
+        let change_capacity = inputs_capacity
+            .checked_sub(outputs_capacity + fee)
+            .filter(|capacity| *capacity >= occupied_capacity)
+            .ok_or_else(|| {
+                BalanceTxCapacityError::CapacityNotEnough(format!(
+                    "inputs capacity {} is not enough for outputs capacity {}, fee {} and change capacity {}",
+                    inputs_capacity, outputs_capacity, fee, occupied_capacity
+                ))
+            })?;
+        let change_output = tx.outputs[change_index]
+            .clone()
+            .as_builder()
+            .capacity(change_capacity.pack())
+            .build();
+        tx.set_output(change_index, change_output);
+        Ok((
+            tx.build(),
+            ChangeDecision::ChangeOutput {
+                output_index: change_index,
+                capacity: change_capacity,
+            },
+        ))
+    }
+}
+
 fn build_sudt_type_script(network_info: &NetworkInfo, sudt_owner_lock_script: &Script) -> Script {
     // code_hash from https://github.com/nervosnetwork/rfcs/blob/master/rfcs/0025-simple-udt/0025-simple-udt.md#notes
     let code_hash = match network_info.network_type {
@@ -0,0 +1,202 @@
+use std::{collections::HashSet, sync::Arc};
+
+use anyhow::anyhow;
+use ckb_chain_spec::consensus::Consensus;
+use ckb_script::{
+    ScriptGroupType as VerifierScriptGroupType, TransactionScriptsVerifier, TxVerifyEnv,
+};
+use ckb_traits::{CellDataProvider, ExtensionProvider, HeaderProvider};
+use ckb_types::{
+    bytes::Bytes,
+    core::{
+        cell::{resolve_transaction, CellProvider, CellStatus, HeaderChecker},
+        error::OutPointError,
+        BlockView, Cycle, HeaderView, TransactionView,
+    },
+    packed::{self, Byte32, OutPoint},
+};
+
+use crate::{
+    rpc::LightClientRpcClient,
+    traits::{
+        CellDepResolver, DefaultCellDepResolver, DefaultHeaderDepResolver,
+        DefaultTransactionDependencyProvider, HeaderDepResolver, LightClientHeaderDepResolver,
+        LightClientTransactionDependencyProvider, OffchainCellDepResolver,
+        OffchainHeaderDepResolver, OffchainTransactionDependencyProvider,
+        TransactionDependencyProvider,
+    },
+    tx_builder::TxBuilderError,
+    CkbRpcClient, ScriptGroup, ScriptGroupType,
+};
+
+/// The chain data resolvers used by the script handlers while building a transaction,
+/// e.g. resolving the header deps of a Nervos DAO withdraw transaction.
+///
+/// It's set on [`TransactionBuilderConfiguration`](super::TransactionBuilderConfiguration) and
+/// passed to [`ScriptHandler::prepare_transaction_with_environment`](super::handler::ScriptHandler::prepare_transaction_with_environment)
+/// and [`ScriptHandler::build_transaction_with_environment`](super::handler::ScriptHandler::build_transaction_with_environment).
+#[derive(Clone)]
+pub struct BuildEnvironment {
+    header_dep_resolver: Arc<dyn HeaderDepResolver>,
+    cell_dep_resolver: Arc<dyn CellDepResolver>,
+    tx_dep_provider: Arc<dyn TransactionDependencyProvider>,
+}
+
+impl BuildEnvironment {
+    pub fn new(
+        header_dep_resolver: Arc<dyn HeaderDepResolver>,
+        cell_dep_resolver: Arc<dyn CellDepResolver>,
+        tx_dep_provider: Arc<dyn TransactionDependencyProvider>,
+    ) -> Self {
+        Self {
+            header_dep_resolver,
+            cell_dep_resolver,
+            tx_dep_provider,
+        }
+    }
+
+    /// Create an environment backed by the ckb node rpc, the cell deps of the system scripts
+    /// are discovered from the genesis block.
+    pub fn new_with_rpc(url: &str) -> Result<Self, TxBuilderError> {
+        let genesis_block: BlockView = CkbRpcClient::new(url)
+            .get_block_by_number(0.into())
+            .map_err(|err| TxBuilderError::Other(anyhow!(err)))?
+            .ok_or_else(|| TxBuilderError::Other(anyhow!("genesis block not found")))?
+            .into();
+        let cell_dep_resolver = DefaultCellDepResolver::from_genesis(&genesis_block)
+            .map_err(|err| TxBuilderError::Other(anyhow!(err)))?;
+        Ok(Self::new(
+            Arc::new(DefaultHeaderDepResolver::new(url)),
+            Arc::new(cell_dep_resolver),
+            Arc::new(DefaultTransactionDependencyProvider::new(url, 10)),
+        ))
+    }
+
+    /// Create an environment backed by the ckb light client rpc, the cell deps of the system
+    /// scripts are discovered from the genesis block.
+    pub fn new_with_light_client(url: &str) -> Result<Self, TxBuilderError> {
+        let genesis_block: BlockView = LightClientRpcClient::new(url)
+            .get_genesis_block()
+            .map_err(|err| TxBuilderError::Other(anyhow!(err)))?
+            .into();
+        let cell_dep_resolver = DefaultCellDepResolver::from_genesis(&genesis_block)
+            .map_err(|err| TxBuilderError::Other(anyhow!(err)))?;
+        Ok(Self::new(
+            Arc::new(LightClientHeaderDepResolver::new(url)),
+            Arc::new(cell_dep_resolver),
+            Arc::new(LightClientTransactionDependencyProvider::new(url)),
+        ))
+    }
+
+    /// Create an environment which only uses the given offchain data.
+    pub fn new_offline(
+        header_dep_resolver: OffchainHeaderDepResolver,
+        cell_dep_resolver: OffchainCellDepResolver,
+        tx_dep_provider: OffchainTransactionDependencyProvider,
+    ) -> Self {
+        Self::new(
+            Arc::new(header_dep_resolver),
+            Arc::new(cell_dep_resolver),
+            Arc::new(tx_dep_provider),
+        )
+    }
+
+    pub fn header_dep_resolver(&self) -> &dyn HeaderDepResolver {
+        self.header_dep_resolver.as_ref()
+    }
+
+    pub fn cell_dep_resolver(&self) -> &dyn CellDepResolver {
+        self.cell_dep_resolver.as_ref()
+    }
+
+    pub fn tx_dep_provider(&self) -> &dyn TransactionDependencyProvider {
+        self.tx_dep_provider.as_ref()
+    }
+
+    /// Run the script group of the transaction with ckb-script and return the consumed cycles,
+    /// the input cells and cell deps are resolved by the transaction dependency provider, the input
+    /// cells are prefetched in advance, see [`TransactionDependencyProvider::prefetch_inputs`].
+    pub fn verify_script_group(
+        &self,
+        tx: &TransactionView,
+        script_group: &ScriptGroup,
+        max_cycles: Cycle,
+    ) -> Result<Cycle, TxBuilderError> {
+        self.tx_dep_provider.prefetch_inputs(tx)?;
+        let data_loader = DataLoader(Arc::clone(&self.tx_dep_provider));
+        let rtx = resolve_transaction(tx.clone(), &mut HashSet::new(), &data_loader, &data_loader)
+            .map_err(|err| TxBuilderError::Other(anyhow!("resolve transaction error: {}", err)))?;
+        let tip_header = HeaderView::new_advanced_builder().build();
+        let verifier = TransactionScriptsVerifier::new(
+            Arc::new(rtx),
+            data_loader,
+            Arc::new(Consensus::default()),
+            Arc::new(TxVerifyEnv::new_submit(&tip_header)),
+        );
+        let group_type = match script_group.group_type {
+            ScriptGroupType::Lock => VerifierScriptGroupType::Lock,
+            ScriptGroupType::Type => VerifierScriptGroupType::Type,
+        };
+        verifier
+            .verify_single(
+                group_type,
+                &script_group.script.calc_script_hash(),
+                max_cycles,
+            )
+            .map_err(|err| TxBuilderError::Other(anyhow!("verify script error: {}", err)))
+    }
+}
+
+impl Default for BuildEnvironment {
+    /// An offline environment without any chain data.
+    fn default() -> Self {
+        Self::new_offline(
+            OffchainHeaderDepResolver::default(),
+            OffchainCellDepResolver::default(),
+            OffchainTransactionDependencyProvider::default(),
+        )
+    }
+}
+
+/// The data loader of the script verifier, it's backed by the transaction dependency provider.
+#[derive(Clone)]
+struct DataLoader(Arc<dyn TransactionDependencyProvider>);
+
+impl DataLoader {
+    fn provider(&self) -> &dyn TransactionDependencyProvider {
+        self.0.as_ref()
+    }
+}
+
+impl CellDataProvider for DataLoader {
+    fn get_cell_data(&self, out_point: &OutPoint) -> Option<Bytes> {
+        CellDataProvider::get_cell_data(&self.provider(), out_point)
+    }
+    fn get_cell_data_hash(&self, out_point: &OutPoint) -> Option<Byte32> {
+        self.provider().get_cell_data_hash(out_point)
+    }
+}
+
+impl HeaderProvider for DataLoader {
+    fn get_header(&self, hash: &Byte32) -> Option<HeaderView> {
+        HeaderProvider::get_header(&self.provider(), hash)
+    }
+}
+
+impl HeaderChecker for DataLoader {
+    fn check_valid(&self, block_hash: &Byte32) -> Result<(), OutPointError> {
+        self.provider().check_valid(block_hash)
+    }
+}
+
+impl CellProvider for DataLoader {
+    fn cell(&self, out_point: &OutPoint, eager_load: bool) -> CellStatus {
+        self.provider().cell(out_point, eager_load)
+    }
+}
+
+impl ExtensionProvider for DataLoader {
+    fn get_block_extension(&self, hash: &Byte32) -> Option<packed::Bytes> {
+        ExtensionProvider::get_block_extension(&self.provider(), hash)
+    }
+}
@@ -0,0 +1,430 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use ckb_jsonrpc_types::{Either, Status, TransactionWithStatusResponse};
+use ckb_types::{
+    core::{Capacity, TransactionView},
+    packed::{self, CellOutput, Transaction, TransactionReader, WitnessArgs},
+    prelude::*,
+    H256,
+};
+
+use super::{
+    builder::{ChangeDecision, CkbTransactionBuilder, FeeCalculator},
+    handler::HandlerContexts,
+    input::{InputIterator, TransactionInput},
+    TransactionBuilderConfiguration,
+};
+use crate::{
+    core::TransactionBuilder,
+    traits::{DefaultTransactionDependencyProvider, TransactionDependencyProvider},
+    tx_builder::{BalanceTxCapacityError, TxBuilderError},
+    CkbRpcClient, ScriptGroup, ScriptGroupType, TransactionWithScriptGroups,
+};
+
+/// The default `min_rbf_rate` of the ckb node, in shannons/KB.
+pub const DEFAULT_MIN_RBF_RATE: u64 = 1500;
+
+/// Rebuild a pending transaction with a higher fee to replace it in the tx-pool (RBF).
+///
+/// All the inputs of the original transaction are kept, so the new transaction conflicts with it.
+/// The fee is paid by the larger one of the fee rate of the configuration and the fee of the original
+/// transaction plus the RBF increment (`min_rbf_rate` of the tx-pool), it's taken from the change output,
+/// more inputs are collected from the input iterator if the change output can not afford it.
+///
+/// The signatures are replaced by the placeholder witnesses of the script handlers, the returned
+/// transaction should be signed again by [`TransactionSigner`](super::signer::TransactionSigner).
+pub struct FeeBumper {
+    tx_with_groups: TransactionWithScriptGroups,
+    configuration: TransactionBuilderConfiguration,
+    tx_dep_provider: Arc<dyn TransactionDependencyProvider>,
+    input_iter: Option<InputIterator>,
+    change_output_index: Option<usize>,
+    min_rbf_rate: u64,
+    min_replace_fee: Option<u64>,
+}
+
+impl FeeBumper {
+    /// Create a fee bumper for a transaction built by the transaction builders, the change output
+    /// is taken from its change decision if any.
+    ///
+    /// The input cells of the transaction are resolved by `tx_dep_provider` to get the original fee,
+    /// e.g. a [`DefaultTransactionDependencyProvider`] backed by the ckb node rpc.
+    pub fn new(
+        tx_with_groups: TransactionWithScriptGroups,
+        configuration: TransactionBuilderConfiguration,
+        tx_dep_provider: Arc<dyn TransactionDependencyProvider>,
+    ) -> Self {
+        let change_output_index = match tx_with_groups.get_change_decision() {
+            Some(ChangeDecision::ChangeOutput { output_index, .. }) => Some(*output_index),
+            _ => None,
+        };
+        Self {
+            tx_with_groups,
+            configuration,
+            tx_dep_provider,
+            input_iter: None,
+            change_output_index,
+            min_rbf_rate: DEFAULT_MIN_RBF_RATE,
+            min_replace_fee: None,
+        }
+    }
+
+    /// Create a fee bumper for a transaction in the tx-pool of the node, the `min_rbf_rate` is
+    /// fetched by the `tx_pool_info` rpc.
+    ///
+    /// The input cells are resolved by the rpc to get the script groups and the original fee, and
+    /// the change output should be set by [`set_change_output_index`](FeeBumper::set_change_output_index).
+    pub fn new_with_tx_hash(
+        url: &str,
+        tx_hash: &H256,
+        configuration: TransactionBuilderConfiguration,
+    ) -> Result<Self, TxBuilderError> {
+        let client = CkbRpcClient::new(url);
+        let (tx, tx_with_status) = get_pool_transaction(&client, tx_hash)?;
+        let tx_dep_provider = Arc::new(DefaultTransactionDependencyProvider::new(url, 10));
+        let script_groups = resolve_script_groups(&tx, tx_dep_provider.as_ref())?;
+        let tx_pool_info = client
+            .tx_pool_info()
+            .map_err(|err| TxBuilderError::Other(anyhow!(err)))?;
+
+        let mut fee_bumper = Self::new(
+            TransactionWithScriptGroups::new(tx, script_groups),
+            configuration,
+            tx_dep_provider,
+        );
+        fee_bumper.min_rbf_rate = tx_pool_info.min_rbf_rate.value();
+        fee_bumper.min_replace_fee = tx_with_status.min_replace_fee.map(|fee| fee.value());
+        Ok(fee_bumper)
+    }
+
+    /// Set the input iterator to collect more inputs when the change output can not afford the fee.
+    pub fn set_input_iterator(&mut self, input_iter: InputIterator) {
+        self.input_iter = Some(input_iter);
+    }
+
+    /// Set the output to pay the fee, if it's None a change output is added with the lock of the
+    /// first collected input.
+    pub fn set_change_output_index(&mut self, change_output_index: Option<usize>) {
+        self.change_output_index = change_output_index;
+    }
+
+    /// Set the minimal extra fee rate (shannons/KB) of the replacement, see `min_rbf_rate` of `tx_pool_info`.
+    pub fn set_min_rbf_rate(&mut self, min_rbf_rate: u64) {
+        self.min_rbf_rate = min_rbf_rate;
+    }
+
+    pub fn get_min_rbf_rate(&self) -> u64 {
+        self.min_rbf_rate
+    }
+}
+
+impl CkbTransactionBuilder for FeeBumper {
+    fn build(
+        self,
+        contexts: &HandlerContexts,
+    ) -> Result<TransactionWithScriptGroups, TxBuilderError> {
+        let Self {
+            tx_with_groups,
+            mut configuration,
+            tx_dep_provider,
+            input_iter,
+            mut change_output_index,
+            min_rbf_rate,
+            min_replace_fee,
+        } = self;
+        let fee_rate = configuration.update_fee_rate()?;
+        let environment = configuration.get_environment();
+        let tx_view = tx_with_groups.get_tx_view();
+
+        let mut inputs_capacity = 0u64;
+        tx_dep_provider.prefetch_inputs(tx_view)?;
+        for input in tx_view.inputs() {
+            let cell = tx_dep_provider.get_cell(&input.previous_output())?;
+            inputs_capacity += Unpack::<u64>::unpack(&cell.capacity());
+        }
+        let original_fee = inputs_capacity
+            .checked_sub(outputs_capacity(
+                &tx_view.outputs().into_iter().collect::<Vec<_>>(),
+            ))
+            .ok_or_else(|| {
+                TxBuilderError::InvalidParameter(anyhow!(
+                    "the outputs capacity is more than the inputs capacity"
+                ))
+            })?;
+
+        let mut tx = TransactionBuilder::from(tx_view);
+        let mut script_groups = tx_with_groups.get_script_groups().to_vec();
+        strip_lock_witnesses(&mut tx, &script_groups)?;
+
+        let mut input_iter = input_iter;
+        loop {
+            // fill the placeholder witnesses to get the real size
+            let mut final_tx = tx.clone();
+            let mut final_script_groups = script_groups.clone();
+            for script_group in final_script_groups.iter_mut() {
+                for handler in configuration.get_script_handlers() {
+                    for context in &contexts.contexts {
+                        if handler.build_transaction_with_environment(
+                            &mut final_tx,
+                            script_group,
+                            context.as_ref(),
+                            environment,
+                        )? {
+                            break;
+                        }
+                    }
+                }
+            }
+
+            let tx_size = final_tx
+                .clone()
+                .build()
+                .data()
+                .as_reader()
+                .serialized_size_in_block() as u64;
+            let fee = FeeCalculator::new(fee_rate)
+                .fee(tx_size)
+                .max(original_fee + FeeCalculator::new(min_rbf_rate).fee(tx_size))
+                .max(min_replace_fee.unwrap_or_default());
+
+            if let Some(output_index) = change_output_index {
+                let change_output =
+                    final_tx.outputs.get(output_index).cloned().ok_or_else(|| {
+                        TxBuilderError::InvalidParameter(anyhow!(
+                            "change output {} not found",
+                            output_index
+                        ))
+                    })?;
+                let change_output_data = final_tx.outputs_data[output_index].clone();
+                let mut other_outputs = final_tx.outputs.clone();
+                other_outputs.remove(output_index);
+                let occupied_capacity = change_output
+                    .occupied_capacity(Capacity::bytes(change_output_data.len()).unwrap())
+                    .unwrap()
+                    .as_u64();
+                if let Some(change_capacity) = inputs_capacity
+                    .checked_sub(outputs_capacity(&other_outputs) + fee)
+                    .filter(|capacity| *capacity >= occupied_capacity)
+                {
+                    log::debug!(
+                        "bump the fee from {} to {} shannons, change capacity: {}",
+                        original_fee,
+                        fee,
+                        change_capacity
+                    );
+                    final_tx.set_output(
+                        output_index,
+                        change_output
+                            .as_builder()
+                            .capacity(change_capacity.pack())
+                            .build(),
+                    );
+                    let mut tx_with_groups =
+                        TransactionWithScriptGroups::new(final_tx.build(), final_script_groups);
+                    tx_with_groups.set_change_decision(Some(ChangeDecision::ChangeOutput {
+                        output_index,
+                        capacity: change_capacity,
+                    }));
+                    tx_with_groups.set_fee_rate(Some(fee_rate));
+                    if let Some(input_iter) = input_iter.as_mut() {
+                        input_iter.add_pending_tx(tx_with_groups.get_tx_view())?;
+                    }
+                    return Ok(tx_with_groups);
+                }
+            }
+
+            // the change output can not afford the fee, collect one more input
+            let input = match input_iter.as_mut().and_then(|iter| iter.next()) {
+                Some(input) => input?,
+                None => {
+                    return Err(BalanceTxCapacityError::CapacityNotEnough(format!(
+                        "can not find enough inputs to pay the fee {} to replace the transaction",
+                        fee
+                    ))
+                    .into())
+                }
+            };
+            if tx
+                .inputs
+                .iter()
+                .any(|cell_input| cell_input.previous_output() == input.live_cell.out_point)
+            {
+                continue;
+            }
+            add_input(&mut tx, &mut script_groups, &input);
+            inputs_capacity += input.capacity();
+            if change_output_index.is_none() {
+                let change_output = CellOutput::new_builder()
+                    .lock(input.previous_output().lock())
+                    .build();
+                tx.output(change_output);
+                tx.output_data(packed::Bytes::default());
+                change_output_index = Some(tx.outputs.len() - 1);
+            }
+        }
+    }
+}
+
+fn outputs_capacity(outputs: &[CellOutput]) -> u64 {
+    outputs
+        .iter()
+        .map(|output| Unpack::<u64>::unpack(&output.capacity()))
+        .sum()
+}
+
+/// Remove the signatures in the lock field of the witnesses, the placeholders are filled by the script handlers.
+fn strip_lock_witnesses(
+    tx: &mut TransactionBuilder,
+    script_groups: &[ScriptGroup],
+) -> Result<(), TxBuilderError> {
+    for script_group in script_groups {
+        if script_group.group_type != ScriptGroupType::Lock {
+            continue;
+        }
+        let index = match script_group.input_indices.first() {
+            Some(index) => *index,
+            None => continue,
+        };
+        let witness_data = match tx.get_witnesses().get(index) {
+            Some(witness) => witness.raw_data(),
+            None => continue,
+        };
+        if witness_data.is_empty() {
+            continue;
+        }
+        let witness = WitnessArgs::from_slice(witness_data.as_ref())
+            .map_err(|_| {
+                TxBuilderError::Other(anyhow!("invalid witness args of the input {}", index))
+            })?
+            .as_builder()
+            .lock(None::<ckb_types::bytes::Bytes>.pack())
+            .build();
+        tx.set_witness(index, witness.as_bytes().pack());
+    }
+    Ok(())
+}
+
+/// Add the input and its witness to the transaction, and put it into the script groups.
+fn add_input(
+    tx: &mut TransactionBuilder,
+    script_groups: &mut Vec<ScriptGroup>,
+    input: &TransactionInput,
+) {
+    tx.input(input.cell_input());
+    let input_index = tx.inputs.len() - 1;
+    while tx.witnesses.len() < tx.inputs.len() {
+        tx.witness(packed::Bytes::default());
+    }
+
+    let previous_output = input.previous_output();
+    add_to_script_group(
+        script_groups,
+        &previous_output.lock(),
+        ScriptGroupType::Lock,
+        input_index,
+    );
+    if let Some(type_script) = previous_output.type_().to_opt() {
+        add_to_script_group(
+            script_groups,
+            &type_script,
+            ScriptGroupType::Type,
+            input_index,
+        );
+    }
+}
+
+fn add_to_script_group(
+    script_groups: &mut Vec<ScriptGroup>,
+    script: &packed::Script,
+    group_type: ScriptGroupType,
+    input_index: usize,
+) {
+    match script_groups
+        .iter_mut()
+        .find(|group| group.group_type == group_type && &group.script == script)
+    {
+        Some(script_group) => script_group.input_indices.push(input_index),
+        None => {
+            let mut script_group = ScriptGroup::new(script, group_type);
+            script_group.input_indices.push(input_index);
+            script_groups.push(script_group);
+        }
+    }
+}
+
+/// Resolve the script groups of the transaction with the input cells from the transaction dependency provider.
+fn resolve_script_groups(
+    tx: &TransactionView,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+) -> Result<Vec<ScriptGroup>, TxBuilderError> {
+    let mut script_groups = Vec::new();
+    tx_dep_provider.prefetch_inputs(tx)?;
+    for (input_index, input) in tx.inputs().into_iter().enumerate() {
+        let cell = tx_dep_provider.get_cell(&input.previous_output())?;
+        add_to_script_group(
+            &mut script_groups,
+            &cell.lock(),
+            ScriptGroupType::Lock,
+            input_index,
+        );
+        if let Some(type_script) = cell.type_().to_opt() {
+            add_to_script_group(
+                &mut script_groups,
+                &type_script,
+                ScriptGroupType::Type,
+                input_index,
+            );
+        }
+    }
+    for (output_index, output) in tx.outputs().into_iter().enumerate() {
+        if let Some(type_script) = output.type_().to_opt() {
+            match script_groups.iter_mut().find(|group| {
+                group.group_type == ScriptGroupType::Type && group.script == type_script
+            }) {
+                Some(script_group) => script_group.output_indices.push(output_index),
+                None => {
+                    let mut script_group = ScriptGroup::from_type_script(&type_script);
+                    script_group.output_indices.push(output_index);
+                    script_groups.push(script_group);
+                }
+            }
+        }
+    }
+    Ok(script_groups)
+}
+
+/// Get a transaction in the tx-pool by the `get_transaction` rpc, the transaction is taken out of the response.
+pub(crate) fn get_pool_transaction(
+    client: &CkbRpcClient,
+    tx_hash: &H256,
+) -> Result<(TransactionView, TransactionWithStatusResponse), TxBuilderError> {
+    let mut tx_with_status = client
+        .get_transaction(tx_hash.clone())
+        .map_err(|err| TxBuilderError::Other(anyhow!(err)))?
+        .ok_or_else(|| TxBuilderError::Other(anyhow!("transaction {:#x} not found", tx_hash)))?;
+    match tx_with_status.tx_status.status {
+        Status::Pending | Status::Proposed => {}
+        status => {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "transaction {:#x} is {:?}, it's not in the tx-pool",
+                tx_hash,
+                status
+            )))
+        }
+    }
+    let tx = match tx_with_status
+        .transaction
+        .take()
+        .ok_or_else(|| TxBuilderError::Other(anyhow!("transaction {:#x} not found", tx_hash)))?
+        .inner
+    {
+        Either::Left(tx) => Transaction::from(tx.inner).into_view(),
+        Either::Right(bytes) => TransactionReader::from_slice(bytes.as_bytes())
+            .map(|reader| reader.to_entity().into_view())
+            .map_err(|err| {
+                TxBuilderError::Other(anyhow!("invalid molecule encoded TransactionView: {}", err))
+            })?,
+    };
+    Ok((tx, tx_with_status))
+}
@@ -0,0 +1,142 @@
+use anyhow::anyhow;
+
+use crate::{tx_builder::TxBuilderError, CkbRpcClient};
+
+/// The policy to decide the fee rate (shannons/KB) of a transaction, the transaction builders
+/// consult it at build time, see [`TransactionBuilderConfiguration::set_fee_rate_policy`](super::TransactionBuilderConfiguration::set_fee_rate_policy).
+pub trait FeeRatePolicy {
+    /// Returns the fee rate in shannons/KB.
+    fn fee_rate(&self) -> Result<u64, TxBuilderError>;
+}
+
+/// A fixed fee rate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedFeeRatePolicy {
+    fee_rate: u64,
+}
+
+impl FixedFeeRatePolicy {
+    pub fn new(fee_rate: u64) -> Self {
+        Self { fee_rate }
+    }
+}
+
+impl FeeRatePolicy for FixedFeeRatePolicy {
+    fn fee_rate(&self) -> Result<u64, TxBuilderError> {
+        Ok(self.fee_rate)
+    }
+}
+
+/// Which value of the fee rate statistics is used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FeeRateStatistic {
+    Mean,
+    #[default]
+    Median,
+}
+
+/// The fee rate from the statistics of the recent blocks returned by the `get_fee_rate_statistics` rpc.
+#[derive(Clone)]
+pub struct StatisticsFeeRatePolicy {
+    client: CkbRpcClient,
+    /// The number of the recent blocks to calculate the statistics, the node uses 21 by default.
+    target: Option<u64>,
+    statistic: FeeRateStatistic,
+    /// The fee rate used when the node has no statistics, e.g. there is no transaction in the recent blocks.
+    fallback_fee_rate: u64,
+}
+
+impl StatisticsFeeRatePolicy {
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: CkbRpcClient::new(url),
+            target: None,
+            statistic: FeeRateStatistic::default(),
+            fallback_fee_rate: 1000,
+        }
+    }
+
+    /// Set the number of the recent blocks to calculate the statistics, e.g. the confirmation target.
+    pub fn set_target(&mut self, target: Option<u64>) {
+        self.target = target;
+    }
+
+    pub fn set_statistic(&mut self, statistic: FeeRateStatistic) {
+        self.statistic = statistic;
+    }
+
+    pub fn set_fallback_fee_rate(&mut self, fallback_fee_rate: u64) {
+        self.fallback_fee_rate = fallback_fee_rate;
+    }
+}
+
+impl FeeRatePolicy for StatisticsFeeRatePolicy {
+    fn fee_rate(&self) -> Result<u64, TxBuilderError> {
+        let statistics = self
+            .client
+            .get_fee_rate_statistics(self.target.map(Into::into))
+            .map_err(|err| TxBuilderError::Other(anyhow!(err)))?;
+        let fee_rate = match statistics {
+            Some(statistics) => match self.statistic {
+                FeeRateStatistic::Mean => statistics.mean.value(),
+                FeeRateStatistic::Median => statistics.median.value(),
+            },
+            None => self.fallback_fee_rate,
+        };
+        Ok(fee_rate)
+    }
+}
+
+/// The minimal fee rate accepted by the tx-pool of the node, returned by the `tx_pool_info` rpc.
+#[derive(Clone)]
+pub struct TxPoolMinFeeRatePolicy {
+    client: CkbRpcClient,
+}
+
+impl TxPoolMinFeeRatePolicy {
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: CkbRpcClient::new(url),
+        }
+    }
+}
+
+impl FeeRatePolicy for TxPoolMinFeeRatePolicy {
+    fn fee_rate(&self) -> Result<u64, TxBuilderError> {
+        let tx_pool_info = self
+            .client
+            .tx_pool_info()
+            .map_err(|err| TxBuilderError::Other(anyhow!(err)))?;
+        Ok(tx_pool_info.min_fee_rate.value())
+    }
+}
+
+/// Limit the fee rate of the inner policy to `[floor, cap]`.
+pub struct BoundedFeeRatePolicy {
+    inner: Box<dyn FeeRatePolicy>,
+    floor: u64,
+    cap: u64,
+}
+
+impl BoundedFeeRatePolicy {
+    pub fn new(
+        inner: Box<dyn FeeRatePolicy>,
+        floor: u64,
+        cap: u64,
+    ) -> Result<Self, TxBuilderError> {
+        if floor > cap {
+            return Err(TxBuilderError::InvalidParameter(anyhow!(
+                "the fee rate floor {} is greater than the cap {}",
+                floor,
+                cap
+            )));
+        }
+        Ok(Self { inner, floor, cap })
+    }
+}
+
+impl FeeRatePolicy for BoundedFeeRatePolicy {
+    fn fee_rate(&self) -> Result<u64, TxBuilderError> {
+        Ok(self.inner.fee_rate()?.clamp(self.floor, self.cap))
+    }
+}
@@ -0,0 +1,451 @@
+use anyhow::anyhow;
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, DepType, ScriptHashType},
+    h256,
+    packed::{self, CellDep, CellOutput, OutPoint, Script, WitnessArgs},
+    prelude::*,
+};
+
+use crate::{
+    constants::DAO_TYPE_HASH,
+    core::TransactionBuilder,
+    traits::{
+        DefaultHeaderDepResolver, DefaultTransactionDependencyProvider, HeaderDepResolver,
+        LiveCell, TransactionDependencyProvider,
+    },
+    transaction::{environment::BuildEnvironment, input::TransactionInput},
+    tx_builder::{
+        dao::{DaoDepositReceiver, DaoPrepareItem},
+        TxBuilderError,
+    },
+    unlock::UnlockError,
+    util::{calculate_dao_maximum_withdraw4, minimal_unlock_point},
+    NetworkInfo, NetworkType, ScriptGroup, Since, SinceType,
+};
+
+use super::{HandlerContext, ScriptHandler};
+
+/// Nervos DAO script handler, it will setup the [Nervos DAO](https://github.com/nervosnetwork/rfcs/blob/master/rfcs/0023-dao-deposit-withdraw/0023-dao-deposit-withdraw.md) related data automatically.
+pub struct DaoScriptHandler {
+    cell_deps: Vec<CellDep>,
+}
+
+/// Context for depositing capacity into Nervos DAO.
+#[derive(Default)]
+pub struct DaoDepositContext {
+    pub receivers: Vec<DaoDepositReceiver>,
+}
+
+impl DaoDepositContext {
+    pub fn new(receivers: Vec<DaoDepositReceiver>) -> Self {
+        Self { receivers }
+    }
+
+    pub fn add_receiver(&mut self, receiver: DaoDepositReceiver) {
+        self.receivers.push(receiver);
+    }
+}
+
+impl HandlerContext for DaoDepositContext {}
+
+/// Context for the Nervos DAO withdraw phase 1 (prepare) transaction.
+pub struct DaoPrepareContext {
+    pub items: Vec<DaoPrepareItem>,
+    header_dep_resolver: Option<Box<dyn HeaderDepResolver>>,
+    tx_dep_provider: Option<Box<dyn TransactionDependencyProvider>>,
+}
+
+impl DaoPrepareContext {
+    /// Creates a prepare context which resolves the deposit cells and headers through the given ckb rpc url.
+    pub fn new(items: Vec<DaoPrepareItem>, rpc_url: &str) -> Self {
+        Self::new_with_resolvers(
+            items,
+            Box::new(DefaultHeaderDepResolver::new(rpc_url)),
+            Box::new(DefaultTransactionDependencyProvider::new(rpc_url, 10)),
+        )
+    }
+
+    pub fn new_with_resolvers(
+        items: Vec<DaoPrepareItem>,
+        header_dep_resolver: Box<dyn HeaderDepResolver>,
+        tx_dep_provider: Box<dyn TransactionDependencyProvider>,
+    ) -> Self {
+        Self {
+            items,
+            header_dep_resolver: Some(header_dep_resolver),
+            tx_dep_provider: Some(tx_dep_provider),
+        }
+    }
+
+    /// Creates a prepare context which resolves the deposit cells and headers with the
+    /// build environment of the configuration.
+    pub fn new_with_environment(items: Vec<DaoPrepareItem>) -> Self {
+        Self {
+            items,
+            header_dep_resolver: None,
+            tx_dep_provider: None,
+        }
+    }
+}
+
+impl HandlerContext for DaoPrepareContext {}
+
+/// Context for the Nervos DAO withdraw phase 2 transaction.
+pub struct DaoWithdrawContext {
+    /// The out points of the prepared cells to withdraw.
+    pub out_points: Vec<OutPoint>,
+    header_dep_resolver: Option<Box<dyn HeaderDepResolver>>,
+    tx_dep_provider: Option<Box<dyn TransactionDependencyProvider>>,
+}
+
+impl DaoWithdrawContext {
+    /// Creates a withdraw context which resolves the prepared cells and headers through the given ckb rpc url.
+    pub fn new(out_points: Vec<OutPoint>, rpc_url: &str) -> Self {
+        Self::new_with_resolvers(
+            out_points,
+            Box::new(DefaultHeaderDepResolver::new(rpc_url)),
+            Box::new(DefaultTransactionDependencyProvider::new(rpc_url, 10)),
+        )
+    }
+
+    pub fn new_with_resolvers(
+        out_points: Vec<OutPoint>,
+        header_dep_resolver: Box<dyn HeaderDepResolver>,
+        tx_dep_provider: Box<dyn TransactionDependencyProvider>,
+    ) -> Self {
+        Self {
+            out_points,
+            header_dep_resolver: Some(header_dep_resolver),
+            tx_dep_provider: Some(tx_dep_provider),
+        }
+    }
+
+    /// Creates a withdraw context which resolves the prepared cells and headers with the
+    /// build environment of the configuration.
+    pub fn new_with_environment(out_points: Vec<OutPoint>) -> Self {
+        Self {
+            out_points,
+            header_dep_resolver: None,
+            tx_dep_provider: None,
+        }
+    }
+}
+
+impl HandlerContext for DaoWithdrawContext {}
+
+/// Returns true if the context is one of the Nervos DAO contexts.
+pub fn is_dao_context(context: &dyn HandlerContext) -> bool {
+    let context = context.as_any();
+    context.is::<DaoDepositContext>()
+        || context.is::<DaoPrepareContext>()
+        || context.is::<DaoWithdrawContext>()
+}
+
+impl DaoScriptHandler {
+    pub fn is_match(&self, script: &Script) -> bool {
+        script.code_hash() == DAO_TYPE_HASH.pack()
+            && script.hash_type() == ScriptHashType::Type.into()
+    }
+
+    pub fn new_with_network(network: &NetworkInfo) -> Result<Self, TxBuilderError> {
+        let mut ret = Self { cell_deps: vec![] };
+        ret.init(network)?;
+        Ok(ret)
+    }
+
+    /// Create a Nervos DAO script handler with custom cell deps, e.g. for the dev chain.
+    pub fn new_with_cell_deps(cell_deps: Vec<CellDep>) -> Self {
+        Self { cell_deps }
+    }
+
+    pub fn dao_type_script() -> Script {
+        Script::new_builder()
+            .code_hash(DAO_TYPE_HASH.pack())
+            .hash_type(ScriptHashType::Type.into())
+            .build()
+    }
+
+    fn prepare_deposit(
+        &self,
+        tx_builder: &mut TransactionBuilder,
+        context: &DaoDepositContext,
+    ) -> Result<(), TxBuilderError> {
+        let dao_type_script = Self::dao_type_script();
+        for receiver in &context.receivers {
+            let output = CellOutput::new_builder()
+                .capacity(receiver.capacity.pack())
+                .lock(receiver.lock_script.clone())
+                .type_(Some(dao_type_script.clone()).pack())
+                .build();
+            tx_builder.output(output);
+            tx_builder.output_data(Bytes::from(vec![0u8; 8]).pack());
+        }
+        Ok(())
+    }
+
+    fn prepare_withdraw_phase1(
+        &self,
+        transaction_inputs: &mut Vec<TransactionInput>,
+        tx_builder: &mut TransactionBuilder,
+        context: &DaoPrepareContext,
+        environment: &BuildEnvironment,
+    ) -> Result<(), TxBuilderError> {
+        let dao_type_script = Self::dao_type_script();
+        let header_dep_resolver: &dyn HeaderDepResolver = match &context.header_dep_resolver {
+            Some(resolver) => resolver.as_ref(),
+            None => environment.header_dep_resolver(),
+        };
+        let tx_dep_provider: &dyn TransactionDependencyProvider = match &context.tx_dep_provider {
+            Some(provider) => provider.as_ref(),
+            None => environment.tx_dep_provider(),
+        };
+        for DaoPrepareItem { input, lock_script } in &context.items {
+            let out_point = input.previous_output();
+            let tx_hash = out_point.tx_hash();
+            let deposit_header = header_dep_resolver
+                .resolve_by_tx(&tx_hash)
+                .map_err(TxBuilderError::Other)?
+                .ok_or_else(|| TxBuilderError::ResolveHeaderDepByTxHashFailed(tx_hash.clone()))?;
+            let input_cell = tx_dep_provider.get_cell(&out_point)?;
+            if input_cell.type_().to_opt().as_ref() != Some(&dao_type_script) {
+                return Err(TxBuilderError::InvalidParameter(anyhow!(
+                    "the input cell has invalid type script"
+                )));
+            }
+            let input_data = tx_dep_provider.get_cell_data(&out_point)?;
+            let output = {
+                let mut builder = input_cell.clone().as_builder();
+                if let Some(script) = lock_script {
+                    builder = builder.lock(script.clone());
+                }
+                builder.build()
+            };
+            let output_data = Bytes::from(deposit_header.number().to_le_bytes().to_vec());
+
+            // the prepared cell must have the same index as the deposited cell
+            let index = transaction_inputs.len();
+            if index > tx_builder.outputs.len() {
+                return Err(TxBuilderError::InvalidParameter(anyhow!(
+                    "can not put the prepared cell at output index {}",
+                    index
+                )));
+            }
+            tx_builder.dedup_header_dep(deposit_header.hash());
+            tx_builder.outputs.insert(index, output);
+            tx_builder.outputs_data.insert(index, output_data.pack());
+
+            let live_cell = LiveCell {
+                output: input_cell,
+                output_data: input_data,
+                out_point,
+                block_number: deposit_header.number(),
+                tx_index: 0,
+            };
+            let since: u64 = input.since().unpack();
+            transaction_inputs.push(TransactionInput::new(live_cell, since));
+        }
+        Ok(())
+    }
+
+    fn prepare_withdraw_phase2(
+        &self,
+        transaction_inputs: &mut Vec<TransactionInput>,
+        tx_builder: &mut TransactionBuilder,
+        context: &DaoWithdrawContext,
+        environment: &BuildEnvironment,
+    ) -> Result<(), TxBuilderError> {
+        let dao_type_script = Self::dao_type_script();
+        let header_dep_resolver: &dyn HeaderDepResolver = match &context.header_dep_resolver {
+            Some(resolver) => resolver.as_ref(),
+            None => environment.header_dep_resolver(),
+        };
+        let tx_dep_provider: &dyn TransactionDependencyProvider = match &context.tx_dep_provider {
+            Some(provider) => provider.as_ref(),
+            None => environment.tx_dep_provider(),
+        };
+        for out_point in &context.out_points {
+            let tx_hash = out_point.tx_hash();
+            let prepare_header = header_dep_resolver
+                .resolve_by_tx(&tx_hash)
+                .map_err(TxBuilderError::Other)?
+                .ok_or_else(|| TxBuilderError::ResolveHeaderDepByTxHashFailed(tx_hash.clone()))?;
+            let input_cell = tx_dep_provider.get_cell(out_point)?;
+            if input_cell.type_().to_opt().as_ref() != Some(&dao_type_script) {
+                return Err(TxBuilderError::InvalidParameter(anyhow!(
+                    "the input cell has invalid type script"
+                )));
+            }
+            let data = tx_dep_provider.get_cell_data(out_point)?;
+            if data.len() != 8 {
+                return Err(TxBuilderError::InvalidParameter(anyhow!(
+                    "the input cell has invalid data length, expected: 8, got: {}",
+                    data.len()
+                )));
+            }
+            let deposit_number = {
+                let mut number_bytes = [0u8; 8];
+                number_bytes.copy_from_slice(data.as_ref());
+                u64::from_le_bytes(number_bytes)
+            };
+            let deposit_header = header_dep_resolver
+                .resolve_by_number(deposit_number)
+                .or_else(|_err| {
+                    // for light client
+                    let prepare_tx = tx_dep_provider.get_transaction(&tx_hash)?;
+                    for input in prepare_tx.inputs() {
+                        let _ = header_dep_resolver
+                            .resolve_by_tx(&input.previous_output().tx_hash())?;
+                    }
+                    header_dep_resolver.resolve_by_number(deposit_number)
+                })
+                .map_err(TxBuilderError::Other)?
+                .ok_or(TxBuilderError::ResolveHeaderDepByNumberFailed(
+                    deposit_number,
+                ))?;
+
+            let since = {
+                let unlock_point = minimal_unlock_point(&deposit_header, &prepare_header);
+                Since::new(
+                    SinceType::EpochNumberWithFraction,
+                    unlock_point.full_value(),
+                    false,
+                )
+            };
+
+            let deposit_block_hash = deposit_header.hash();
+            tx_builder.dedup_header_dep(deposit_block_hash.clone());
+            tx_builder.dedup_header_dep(prepare_header.hash());
+            let header_idx = tx_builder
+                .header_deps
+                .iter()
+                .position(|hash| *hash == deposit_block_hash)
+                .ok_or_else(|| {
+                    TxBuilderError::Other(anyhow!(
+                        "the deposit header {} is not in the header deps",
+                        deposit_block_hash
+                    ))
+                })?;
+
+            // put the deposit header index into the witness of the input
+            let index = transaction_inputs.len();
+            if tx_builder.witnesses.len() <= index {
+                tx_builder
+                    .witnesses
+                    .resize(index + 1, packed::Bytes::default());
+            }
+            let witness_data = tx_builder.witnesses[index].raw_data();
+            let witness = if witness_data.is_empty() {
+                WitnessArgs::new_builder()
+            } else {
+                WitnessArgs::from_slice(witness_data.as_ref())
+                    .map_err(|_| UnlockError::InvalidWitnessArgs(index))?
+                    .as_builder()
+            }
+            .input_type(Some(Bytes::from((header_idx as u64).to_le_bytes().to_vec())).pack())
+            .build();
+            tx_builder.set_witness(index, witness.as_bytes().pack());
+
+            let occupied_capacity = input_cell
+                .occupied_capacity(Capacity::bytes(data.len()).unwrap())
+                .unwrap();
+            let withdraw_capacity = calculate_dao_maximum_withdraw4(
+                &deposit_header,
+                &prepare_header,
+                &input_cell,
+                occupied_capacity.as_u64(),
+            );
+            let live_cell = LiveCell {
+                output: input_cell,
+                output_data: data,
+                out_point: out_point.clone(),
+                block_number: prepare_header.number(),
+                tx_index: 0,
+            };
+            // the change builder balances the DAO compensation into the change output
+            let mut input = TransactionInput::new(live_cell, since.value());
+            input.set_extra_capacity(withdraw_capacity.saturating_sub(input.capacity()));
+            transaction_inputs.push(input);
+        }
+        Ok(())
+    }
+}
+
+impl ScriptHandler for DaoScriptHandler {
+    fn prepare_transaction(
+        &self,
+        transaction_inputs: &mut Vec<TransactionInput>,
+        tx_builder: &mut TransactionBuilder,
+        context: &dyn HandlerContext,
+    ) -> Result<bool, TxBuilderError> {
+        self.prepare_transaction_with_environment(
+            transaction_inputs,
+            tx_builder,
+            context,
+            &BuildEnvironment::default(),
+        )
+    }
+
+    fn prepare_transaction_with_environment(
+        &self,
+        transaction_inputs: &mut Vec<TransactionInput>,
+        tx_builder: &mut TransactionBuilder,
+        context: &dyn HandlerContext,
+        environment: &BuildEnvironment,
+    ) -> Result<bool, TxBuilderError> {
+        if let Some(args) = context.as_any().downcast_ref::<DaoDepositContext>() {
+            self.prepare_deposit(tx_builder, args)?;
+            Ok(true)
+        } else if let Some(args) = context.as_any().downcast_ref::<DaoPrepareContext>() {
+            self.prepare_withdraw_phase1(transaction_inputs, tx_builder, args, environment)?;
+            Ok(true)
+        } else if let Some(args) = context.as_any().downcast_ref::<DaoWithdrawContext>() {
+            self.prepare_withdraw_phase2(transaction_inputs, tx_builder, args, environment)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn build_transaction(
+        &self,
+        tx_builder: &mut TransactionBuilder,
+        script_group: &mut ScriptGroup,
+        context: &dyn HandlerContext,
+    ) -> Result<bool, TxBuilderError> {
+        if !self.is_match(&script_group.script) || !is_dao_context(context) {
+            return Ok(false);
+        }
+        tx_builder.dedup_cell_deps(self.cell_deps.clone());
+        Ok(true)
+    }
+
+    fn init(&mut self, network: &NetworkInfo) -> Result<(), TxBuilderError> {
+        let out_point = if network.network_type == NetworkType::Mainnet {
+            OutPoint::new_builder()
+                .tx_hash(
+                    h256!("0xe2fb199810d49a4d8beec56718ba2593b665db9d52299a0f9e6e75416d73ff5c")
+                        .pack(),
+                )
+                .index(2u32.pack())
+                .build()
+        } else if network.network_type == NetworkType::Testnet {
+            OutPoint::new_builder()
+                .tx_hash(
+                    h256!("0x8f8c79eb6671709633fe6a46de93c0fedc9c1b8a6527a18d3983879542635c9f")
+                        .pack(),
+                )
+                .index(2u32.pack())
+                .build()
+        } else {
+            return Err(TxBuilderError::UnsupportedNetworkType(network.network_type));
+        };
+
+        let cell_dep = CellDep::new_builder()
+            .out_point(out_point)
+            .dep_type(DepType::Code.into())
+            .build();
+        self.cell_deps.push(cell_dep);
+        Ok(())
+    }
+}
@@ -2,19 +2,37 @@ use std::any::Any;
 
 use crate::{
     core::TransactionBuilder, tx_builder::TxBuilderError, unlock::MultisigConfig, NetworkInfo,
-    ScriptGroup,
+    ScriptGroup, ScriptId,
 };
 
+use super::{environment::BuildEnvironment, input::TransactionInput, signer::CKBScriptSigner};
+
 use self::{
     sighash::Secp256k1Blake160SighashAllScriptContext, sudt::SudtContext, typeid::TypeIdContext,
 };
 
+pub mod dao;
 pub mod multisig;
+pub mod omnilock;
 pub mod sighash;
 pub mod sudt;
 pub mod typeid;
 
 pub trait ScriptHandler {
+    /// Try to prepare the transaction with the given context before collecting inputs,
+    /// e.g. add the required inputs, outputs and header deps.
+    ///
+    /// The pushed `transaction_inputs` will be placed in front of the collected inputs in order.
+    /// Return true if the context is matched, otherwise return false.
+    fn prepare_transaction(
+        &self,
+        _transaction_inputs: &mut Vec<TransactionInput>,
+        _tx_builder: &mut TransactionBuilder,
+        _context: &dyn HandlerContext,
+    ) -> Result<bool, TxBuilderError> {
+        Ok(false)
+    }
+
     /// Try to build transaction with the given script_group and context.
     ///
     /// Return true if script_group and context are matched, otherwise return false.
@@ -25,7 +43,69 @@ pub trait ScriptHandler {
         context: &dyn HandlerContext,
     ) -> Result<bool, TxBuilderError>;
 
+    /// Same as [`prepare_transaction`](ScriptHandler::prepare_transaction), but the chain data
+    /// can be resolved with the build environment, the transaction builder calls this method.
+    ///
+    /// The default implementation ignores the environment.
+    fn prepare_transaction_with_environment(
+        &self,
+        transaction_inputs: &mut Vec<TransactionInput>,
+        tx_builder: &mut TransactionBuilder,
+        context: &dyn HandlerContext,
+        _environment: &BuildEnvironment,
+    ) -> Result<bool, TxBuilderError> {
+        self.prepare_transaction(transaction_inputs, tx_builder, context)
+    }
+
+    /// Same as [`build_transaction`](ScriptHandler::build_transaction), but the chain data
+    /// can be resolved with the build environment, the transaction builder calls this method.
+    ///
+    /// The default implementation ignores the environment.
+    fn build_transaction_with_environment(
+        &self,
+        tx_builder: &mut TransactionBuilder,
+        script_group: &mut ScriptGroup,
+        context: &dyn HandlerContext,
+        _environment: &BuildEnvironment,
+    ) -> Result<bool, TxBuilderError> {
+        self.build_transaction(tx_builder, script_group, context)
+    }
+
     fn init(&mut self, network: &NetworkInfo) -> Result<(), TxBuilderError>;
+
+    /// Estimate the size of the placeholder witness lock which will be set by
+    /// [`build_transaction`](ScriptHandler::build_transaction) for the lock script group.
+    ///
+    /// The transaction builder uses it to calculate the fee before the witnesses are filled,
+    /// return None if script_group and context are not matched or the size can not be estimated.
+    fn estimate_witness_size(
+        &self,
+        _script_group: &ScriptGroup,
+        _context: &dyn HandlerContext,
+    ) -> Option<usize> {
+        None
+    }
+
+    /// Estimate the cycles of the script group after the transaction is signed, the transaction
+    /// builder uses it when the script group can not be verified with the placeholder witnesses,
+    /// see [`TransactionBuilderConfiguration::check_cycle_fee`](super::TransactionBuilderConfiguration::check_cycle_fee).
+    ///
+    /// Return None if script_group and context are not matched or the cycles can not be estimated.
+    fn estimate_cycles(
+        &self,
+        _script_group: &ScriptGroup,
+        _context: &dyn HandlerContext,
+    ) -> Option<u64> {
+        None
+    }
+
+    /// The signers of the scripts handled by this handler, they are used by
+    /// [`TransactionSigner::new_with_configuration`](super::signer::TransactionSigner::new_with_configuration).
+    ///
+    /// The default implementation returns no signer.
+    fn signers(&self) -> Vec<(ScriptId, Box<dyn CKBScriptSigner>)> {
+        Vec::new()
+    }
 }
 
 pub trait Type2Any: 'static {
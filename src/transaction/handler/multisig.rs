@@ -1,17 +1,33 @@
 use ckb_types::{
     core::DepType,
     h256,
-    packed::{CellDep, OutPoint, Script},
+    packed::{CellDep, OutPoint, Script, WitnessArgs},
     prelude::{Builder, Entity, Pack},
 };
 
 use crate::{
-    constants, core::TransactionBuilder, tx_builder::TxBuilderError, unlock::MultisigConfig,
-    NetworkInfo, NetworkType, ScriptGroup,
+    constants,
+    core::TransactionBuilder,
+    transaction::signer::{multisig::Secp256k1Blake160MultisigAllSigner, CKBScriptSigner},
+    tx_builder::TxBuilderError,
+    unlock::{MultisigConfig, UnlockError},
+    NetworkInfo, NetworkType, ScriptGroup, ScriptId,
 };
 
 use super::{HandlerContext, ScriptHandler};
 
+/// The cycles of a multisig lock group, the base cycles are for hashing the transaction and checking
+/// the multisig config, and each signature is recovered once. A signed 3/3 group in the cycle fee tests
+/// takes about 4_330_000 cycles, below the estimated 4_600_000.
+const MULTISIG_BASE_CYCLES: u64 = 400_000;
+const MULTISIG_SIGNATURE_CYCLES: u64 = 1_400_000;
+
+/// Estimate the cycles to verify the signatures of a multisig config, it's also used by the
+/// OmniLock multisig mode.
+pub(crate) fn estimate_multisig_cycles(config: &MultisigConfig) -> u64 {
+    MULTISIG_BASE_CYCLES + MULTISIG_SIGNATURE_CYCLES * config.threshold() as u64
+}
+
 pub struct Secp256k1Blake160MultisigAllScriptHandler {
     cell_deps: Vec<CellDep>,
 }
@@ -37,6 +53,11 @@ impl Secp256k1Blake160MultisigAllScriptHandler {
         ret.init(network)?;
         Ok(ret)
     }
+
+    /// Create a multisig script handler with custom cell deps, e.g. for the dev chain.
+    pub fn new_with_cell_deps(cell_deps: Vec<CellDep>) -> Self {
+        Self { cell_deps }
+    }
 }
 
 impl ScriptHandler for Secp256k1Blake160MultisigAllScriptHandler {
@@ -54,15 +75,58 @@ impl ScriptHandler for Secp256k1Blake160MultisigAllScriptHandler {
             .downcast_ref::<Secp256k1Blake160MultisigAllScriptContext>()
         {
             tx_builder.dedup_cell_deps(self.cell_deps.clone());
-            let index = script_group.input_indices.first().unwrap();
-            let witness = args.multisig_config.placeholder_witness();
-            tx_builder.set_witness(*index, witness.as_bytes().pack());
+            let index = *script_group.input_indices.first().unwrap();
+            let placeholder_lock = args.multisig_config.placeholder_witness().lock();
+            let witness = if let Some(witness) = tx_builder.get_witnesses().get(index) {
+                let witness_data = witness.raw_data();
+                if witness_data.is_empty() {
+                    WitnessArgs::new_builder()
+                } else {
+                    WitnessArgs::from_slice(witness_data.as_ref())
+                        .map_err(|_| UnlockError::InvalidWitnessArgs(index))?
+                        .as_builder()
+                }
+            } else {
+                WitnessArgs::new_builder()
+            }
+            .lock(placeholder_lock)
+            .build();
+            tx_builder.set_witness(index, witness.as_bytes().pack());
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
+    fn estimate_witness_size(
+        &self,
+        script_group: &ScriptGroup,
+        context: &dyn HandlerContext,
+    ) -> Option<usize> {
+        if !self.is_match(&script_group.script) {
+            return None;
+        }
+        let args = context
+            .as_any()
+            .downcast_ref::<Secp256k1Blake160MultisigAllScriptContext>()?;
+        let placeholder_lock = args.multisig_config.placeholder_witness().lock().to_opt()?;
+        Some(placeholder_lock.raw_data().len())
+    }
+
+    fn estimate_cycles(
+        &self,
+        script_group: &ScriptGroup,
+        context: &dyn HandlerContext,
+    ) -> Option<u64> {
+        if !self.is_match(&script_group.script) {
+            return None;
+        }
+        let args = context
+            .as_any()
+            .downcast_ref::<Secp256k1Blake160MultisigAllScriptContext>()?;
+        Some(estimate_multisig_cycles(&args.multisig_config))
+    }
+
     fn init(&mut self, network: &NetworkInfo) -> Result<(), TxBuilderError> {
         let out_point = if network.network_type == NetworkType::Mainnet {
             OutPoint::new_builder()
@@ -91,4 +155,11 @@ impl ScriptHandler for Secp256k1Blake160MultisigAllScriptHandler {
         self.cell_deps.push(cell_dep);
         Ok(())
     }
+
+    fn signers(&self) -> Vec<(ScriptId, Box<dyn CKBScriptSigner>)> {
+        vec![(
+            ScriptId::new_type(constants::MULTISIG_TYPE_HASH),
+            Box::new(Secp256k1Blake160MultisigAllSigner {}) as Box<_>,
+        )]
+    }
 }
@@ -0,0 +1,277 @@
+use anyhow::anyhow;
+use ckb_types::{
+    core::DepType,
+    h256,
+    packed::{CellDep, OutPoint, Script, WitnessArgs},
+    prelude::*,
+};
+
+use crate::{
+    constants,
+    core::TransactionBuilder,
+    transaction::signer::{omnilock::OmniLockSigner, CKBScriptSigner},
+    tx_builder::{SinceSource, TxBuilderError},
+    unlock::{IdentityFlag, OmniLockConfig, OmniUnlockMode, UnlockError},
+    NetworkInfo, NetworkType, ScriptGroup, ScriptId,
+};
+
+use super::{multisig::estimate_multisig_cycles, HandlerContext, ScriptHandler};
+
+/// The cycles of an OmniLock group when the auth is verified by a secp256k1 signature, e.g. the
+/// pubkey hash and Ethereum modes. A signed pubkey hash group in the transfer tests takes about
+/// 1_440_000 cycles.
+const OMNILOCK_SECP256K1_CYCLES: u64 = 1_600_000;
+
+/// OmniLock script handler, it will setup the [OmniLock](https://github.com/nervosnetwork/rfcs/blob/master/rfcs/0042-omnilock/0042-omnilock.md) related data automatically.
+pub struct OmniLockScriptHandler {
+    cell_deps: Vec<CellDep>,
+    lock_script_id: ScriptId,
+}
+
+/// The context of an OmniLock script group, the script args must match the `omni_lock_config`.
+pub struct OmniLockScriptContext {
+    omni_lock_config: OmniLockConfig,
+    unlock_mode: OmniUnlockMode,
+    /// The RCE cells, only used in the admin mode when the `rce_in_input` of the admin config is false.
+    rce_cells: Option<Vec<OutPoint>>,
+}
+
+impl HandlerContext for OmniLockScriptContext {}
+
+impl OmniLockScriptContext {
+    pub fn new(omni_lock_config: OmniLockConfig, unlock_mode: OmniUnlockMode) -> Self {
+        Self {
+            omni_lock_config,
+            unlock_mode,
+            rce_cells: None,
+        }
+    }
+
+    /// Set the RCE cells, they will be added as the cell deps of the transaction.
+    pub fn set_rce_cells(&mut self, rce_cells: Vec<OutPoint>) {
+        self.rce_cells = Some(rce_cells);
+    }
+
+    pub fn omni_lock_config(&self) -> &OmniLockConfig {
+        &self.omni_lock_config
+    }
+
+    pub fn unlock_mode(&self) -> OmniUnlockMode {
+        self.unlock_mode
+    }
+}
+
+impl OmniLockScriptHandler {
+    pub fn is_match(&self, script: &Script) -> bool {
+        ScriptId::from(script) == self.lock_script_id
+    }
+
+    pub fn new_with_network(network: &NetworkInfo) -> Result<Self, TxBuilderError> {
+        let mut ret = Self {
+            cell_deps: vec![],
+            lock_script_id: ScriptId::default(),
+        };
+        ret.init(network)?;
+        Ok(ret)
+    }
+
+    /// Create an OmniLock script handler with custom cell deps, e.g. for the dev chain.
+    ///
+    /// The cell deps must include the OmniLock script cell and the secp256k1 data cell.
+    pub fn new_with_cell_deps(cell_deps: Vec<CellDep>, lock_script_id: ScriptId) -> Self {
+        Self {
+            cell_deps,
+            lock_script_id,
+        }
+    }
+
+    pub fn lock_script_id(&self) -> &ScriptId {
+        &self.lock_script_id
+    }
+}
+
+impl ScriptHandler for OmniLockScriptHandler {
+    fn build_transaction(
+        &self,
+        tx_builder: &mut TransactionBuilder,
+        script_group: &mut ScriptGroup,
+        context: &dyn HandlerContext,
+    ) -> Result<bool, TxBuilderError> {
+        if !self.is_match(&script_group.script) {
+            return Ok(false);
+        }
+        let args = if let Some(args) = context.as_any().downcast_ref::<OmniLockScriptContext>() {
+            args
+        } else {
+            return Ok(false);
+        };
+        let config = &args.omni_lock_config;
+        let lock_args = script_group.script.args().raw_data();
+        if lock_args != config.build_args() {
+            return Ok(false);
+        }
+
+        tx_builder.dedup_cell_deps(self.cell_deps.clone());
+        // the RCE cells are only used by the admin mode when the admin flag is set in the args
+        if let Some(rce_cells) = args.rce_cells.as_ref() {
+            let admin_config = config
+                .get_admin_config()
+                .filter(|_| config.use_rc() && args.unlock_mode == OmniUnlockMode::Admin)
+                .ok_or_else(|| {
+                    TxBuilderError::InvalidParameter(anyhow!(
+                        "the RCE cells are only used in the admin mode of an OmniLock with the admin flag"
+                    ))
+                })?;
+            if admin_config.rce_in_input() {
+                return Err(TxBuilderError::InvalidParameter(anyhow!(
+                    "RCE cells in inputs are not supported by OmniLockScriptHandler"
+                )));
+            }
+            tx_builder.dedup_cell_deps(rce_cells.iter().map(|cell| {
+                CellDep::new_builder()
+                    .out_point(cell.clone())
+                    .dep_type(DepType::Code.into())
+                    .build()
+            }));
+        }
+
+        let index = *script_group.input_indices.first().unwrap();
+        let placeholder_lock = config
+            .placeholder_witness(args.unlock_mode)
+            .map_err(UnlockError::from)?
+            .lock();
+        let witness = if let Some(witness) = tx_builder.get_witnesses().get(index) {
+            let witness_data = witness.raw_data();
+            if witness_data.is_empty() {
+                WitnessArgs::new_builder()
+            } else {
+                WitnessArgs::from_slice(witness_data.as_ref())
+                    .map_err(|_| UnlockError::InvalidWitnessArgs(index))?
+                    .as_builder()
+            }
+        } else {
+            WitnessArgs::new_builder()
+        }
+        .lock(placeholder_lock)
+        .build();
+        tx_builder.set_witness(index, witness.as_bytes().pack());
+
+        // the inputs of a time-locked OmniLock must carry the since value in the lock args
+        if let SinceSource::LockArgs(offset) = config.get_since_source() {
+            let mut since_bytes = [0u8; 8];
+            since_bytes.copy_from_slice(lock_args.get(offset..offset + 8).ok_or_else(|| {
+                TxBuilderError::InvalidParameter(anyhow!(
+                    "the lock args length {} is too short for the since at offset {}",
+                    lock_args.len(),
+                    offset
+                ))
+            })?);
+            let since = u64::from_le_bytes(since_bytes);
+            for idx in &script_group.input_indices {
+                let input = tx_builder.inputs[*idx]
+                    .clone()
+                    .as_builder()
+                    .since(since.pack())
+                    .build();
+                tx_builder.inputs[*idx] = input;
+            }
+        }
+        Ok(true)
+    }
+
+    fn estimate_witness_size(
+        &self,
+        script_group: &ScriptGroup,
+        context: &dyn HandlerContext,
+    ) -> Option<usize> {
+        if !self.is_match(&script_group.script) {
+            return None;
+        }
+        let args = context.as_any().downcast_ref::<OmniLockScriptContext>()?;
+        if script_group.script.args().raw_data() != args.omni_lock_config.build_args() {
+            return None;
+        }
+        let placeholder_lock = args
+            .omni_lock_config
+            .placeholder_witness(args.unlock_mode)
+            .ok()?
+            .lock()
+            .to_opt()?;
+        Some(placeholder_lock.raw_data().len())
+    }
+
+    fn estimate_cycles(
+        &self,
+        script_group: &ScriptGroup,
+        context: &dyn HandlerContext,
+    ) -> Option<u64> {
+        if !self.is_match(&script_group.script) {
+            return None;
+        }
+        let args = context.as_any().downcast_ref::<OmniLockScriptContext>()?;
+        let config = &args.omni_lock_config;
+        if script_group.script.args().raw_data() != config.build_args() {
+            return None;
+        }
+        let (auth, multisig_config) = match args.unlock_mode {
+            OmniUnlockMode::Normal => (config.id(), config.multisig_config()),
+            OmniUnlockMode::Admin => {
+                let admin_config = config.get_admin_config()?;
+                (admin_config.get_auth(), admin_config.get_multisig_config())
+            }
+        };
+        match auth.flag() {
+            IdentityFlag::PubkeyHash
+            | IdentityFlag::Ethereum
+            | IdentityFlag::Eos
+            | IdentityFlag::Tron
+            | IdentityFlag::Bitcoin
+            | IdentityFlag::Dogecoin => Some(OMNILOCK_SECP256K1_CYCLES),
+            IdentityFlag::Multisig => multisig_config.map(estimate_multisig_cycles),
+            // the cycles of the owner lock and the delegated scripts are unknown, the builder fails
+            // rather than paying the fee without them
+            IdentityFlag::OwnerLock | IdentityFlag::Exec | IdentityFlag::Dl => None,
+        }
+    }
+
+    fn init(&mut self, network: &NetworkInfo) -> Result<(), TxBuilderError> {
+        let (tx_hash, secp_data_tx_hash, code_hash) =
+            if network.network_type == NetworkType::Mainnet {
+                (
+                    h256!("0xc76edf469816aa22f416503c38d0b533d2a018e253e379f134c3985b3472c842"),
+                    h256!("0xe2fb199810d49a4d8beec56718ba2593b665db9d52299a0f9e6e75416d73ff5c"),
+                    constants::OMNILOCK_TYPE_HASH_LINA,
+                )
+            } else if network.network_type == NetworkType::Testnet {
+                (
+                    h256!("0xec18bf0d857c981c3d1f4e17999b9b90c484b303378e94de1a57b0872f5d4602"),
+                    h256!("0x8f8c79eb6671709633fe6a46de93c0fedc9c1b8a6527a18d3983879542635c9f"),
+                    constants::OMNILOCK_TYPE_HASH_AGGRON,
+                )
+            } else {
+                return Err(TxBuilderError::UnsupportedNetworkType(network.network_type));
+            };
+        let out_point = OutPoint::new(tx_hash.pack(), 0);
+        let secp_data_out_point = OutPoint::new(secp_data_tx_hash.pack(), 3);
+
+        self.cell_deps = vec![
+            CellDep::new_builder()
+                .out_point(out_point)
+                .dep_type(DepType::Code.into())
+                .build(),
+            CellDep::new_builder()
+                .out_point(secp_data_out_point)
+                .dep_type(DepType::Code.into())
+                .build(),
+        ];
+        self.lock_script_id = ScriptId::new_type(code_hash);
+        Ok(())
+    }
+
+    fn signers(&self) -> Vec<(ScriptId, Box<dyn CKBScriptSigner>)> {
+        vec![(
+            self.lock_script_id.clone(),
+            Box::new(OmniLockSigner {}) as Box<_>,
+        )]
+    }
+}
@@ -6,12 +6,20 @@ use ckb_types::{
 };
 
 use crate::{
-    constants, core::TransactionBuilder, tx_builder::TxBuilderError, unlock::UnlockError,
-    NetworkInfo, NetworkType, ScriptGroup,
+    constants,
+    core::TransactionBuilder,
+    transaction::signer::{sighash::Secp256k1Blake160SighashAllSigner, CKBScriptSigner},
+    tx_builder::TxBuilderError,
+    unlock::UnlockError,
+    NetworkInfo, NetworkType, ScriptGroup, ScriptId,
 };
 
 use super::{HandlerContext, ScriptHandler};
 
+/// The cycles of a sighash lock group, a signed two-input group in the transfer tests takes about
+/// 1_650_000 cycles, rounded up for the larger witnesses.
+const SIGHASH_ALL_CYCLES: u64 = 1_800_000;
+
 pub struct Secp256k1Blake160SighashAllScriptHandler {
     cell_deps: Vec<CellDep>,
 }
@@ -29,6 +37,11 @@ impl Secp256k1Blake160SighashAllScriptHandler {
         ret.init(network)?;
         Ok(ret)
     }
+
+    /// Create a sighash script handler with custom cell deps, e.g. for the dev chain.
+    pub fn new_with_cell_deps(cell_deps: Vec<CellDep>) -> Self {
+        Self { cell_deps }
+    }
 }
 
 impl ScriptHandler for Secp256k1Blake160SighashAllScriptHandler {
@@ -68,6 +81,38 @@ impl ScriptHandler for Secp256k1Blake160SighashAllScriptHandler {
         }
     }
 
+    fn estimate_witness_size(
+        &self,
+        script_group: &ScriptGroup,
+        context: &dyn HandlerContext,
+    ) -> Option<usize> {
+        if self.is_match(&script_group.script)
+            && context
+                .as_any()
+                .is::<Secp256k1Blake160SighashAllScriptContext>()
+        {
+            Some(65)
+        } else {
+            None
+        }
+    }
+
+    fn estimate_cycles(
+        &self,
+        script_group: &ScriptGroup,
+        context: &dyn HandlerContext,
+    ) -> Option<u64> {
+        if self.is_match(&script_group.script)
+            && context
+                .as_any()
+                .is::<Secp256k1Blake160SighashAllScriptContext>()
+        {
+            Some(SIGHASH_ALL_CYCLES)
+        } else {
+            None
+        }
+    }
+
     fn init(&mut self, network: &NetworkInfo) -> Result<(), TxBuilderError> {
         let out_point = if network.network_type == NetworkType::Mainnet {
             OutPoint::new_builder()
@@ -96,4 +141,11 @@ impl ScriptHandler for Secp256k1Blake160SighashAllScriptHandler {
         self.cell_deps.push(cell_dep);
         Ok(())
     }
+
+    fn signers(&self) -> Vec<(ScriptId, Box<dyn CKBScriptSigner>)> {
+        vec![(
+            ScriptId::new_type(constants::SIGHASH_TYPE_HASH),
+            Box::new(Secp256k1Blake160SighashAllSigner {}) as Box<_>,
+        )]
+    }
 }
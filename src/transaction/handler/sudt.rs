@@ -23,6 +23,14 @@ pub struct SudtContext;
 impl HandlerContext for SudtContext {}
 
 impl SudtHandler {
+    /// Create a sUDT script handler with custom cell deps and script id, e.g. for the dev chain.
+    pub fn new_with_cell_deps(cell_deps: Vec<CellDep>, sudt_script_id: ScriptId) -> Self {
+        Self {
+            cell_deps,
+            sudt_script_id,
+        }
+    }
+
     pub fn new_with_network(network: &NetworkInfo) -> Result<Self, TxBuilderError> {
         let (out_point, sudt_script_id) = if network.network_type == NetworkType::Mainnet {
             (
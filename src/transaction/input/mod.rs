@@ -1,13 +1,27 @@
+pub mod selector;
+pub mod since;
 pub mod transaction_input;
-use ckb_types::packed::Script;
+use anyhow::anyhow;
+use ckb_types::{
+    core::TransactionView,
+    packed::{OutPoint, Script, Transaction},
+};
+pub use selector::{
+    BranchAndBoundSelector, InputSelector, LargestFirstSelector, MaxInputCountSelector,
+    RandomImproveSelector, SmallestFirstSelector,
+};
+pub use since::SinceChecker;
 pub use transaction_input::TransactionInput;
 
 use crate::{
     rpc::ckb_indexer::SearchMode,
     traits::{
-        CellCollector, CellCollectorError, CellQueryOptions, DefaultCellCollector, ValueRangeOption,
+        CellCollector, CellCollectorError, CellQueryOptions, DefaultCellCollector,
+        ValueRangeOption, WalletCellCache,
     },
+    tx_builder::SinceSource,
     types::NetworkInfo,
+    unlock::MultisigConfig,
     Address,
 };
 
@@ -16,6 +30,16 @@ pub struct InputIterator {
     lock_scripts: Vec<Script>,
     cell_collector: Box<dyn CellCollector>,
     type_script: Option<Script>,
+    /// The since source of the inputs locked by the lock script, the default since is 0
+    since_sources: Vec<(Script, SinceSource)>,
+    /// Used for skipping the cells which can not be spent yet at the tip
+    since_checker: Option<SinceChecker>,
+    /// The number of the inputs returned by the iterator
+    returned_count: usize,
+    /// The number of the cells skipped since they are time-locked
+    time_locked_count: usize,
+    /// The shared wallet cell cache, the returned inputs are reserved in it
+    cell_cache: Option<WalletCellCache>,
 }
 
 impl Clone for InputIterator {
@@ -25,6 +49,11 @@ impl Clone for InputIterator {
             lock_scripts: self.lock_scripts.clone(),
             cell_collector: dyn_clone::clone_box(&*self.cell_collector),
             type_script: self.type_script.clone(),
+            since_sources: self.since_sources.clone(),
+            since_checker: self.since_checker.clone(),
+            returned_count: self.returned_count,
+            time_locked_count: self.time_locked_count,
+            cell_cache: self.cell_cache.clone(),
         }
     }
 }
@@ -38,6 +67,11 @@ impl InputIterator {
             lock_scripts,
             cell_collector: Box::new(DefaultCellCollector::new(&network_info.url)),
             type_script: None,
+            since_sources: vec![],
+            since_checker: None,
+            returned_count: 0,
+            time_locked_count: 0,
+            cell_cache: None,
         }
     }
 
@@ -52,9 +86,26 @@ impl InputIterator {
             lock_scripts,
             cell_collector,
             type_script: None,
+            since_sources: vec![],
+            since_checker: None,
+            returned_count: 0,
+            time_locked_count: 0,
+            cell_cache: None,
         }
     }
 
+    /// Create an input iterator with a clone of the shared wallet cell cache, the cells spent by the
+    /// pending transactions are skipped and their change outputs can be spent.
+    ///
+    /// The returned inputs are reserved until the built transaction is added to the cache by
+    /// [`InputIterator::add_pending_tx`], or the iterator is dropped.
+    pub fn new_with_cell_cache(lock_scripts: Vec<Script>, cell_cache: &WalletCellCache) -> Self {
+        let mut iterator =
+            Self::new_with_cell_collector(lock_scripts, Box::new(cell_cache.clone()));
+        iterator.cell_cache = Some(cell_cache.clone());
+        iterator
+    }
+
     pub fn new_with_address(address: &[Address], network_info: &NetworkInfo) -> Self {
         let lock_scripts = address.iter().map(|addr| addr.into()).collect::<Vec<_>>();
         Self::new(lock_scripts, network_info)
@@ -72,6 +123,77 @@ impl InputIterator {
         self.buffer_inputs.push(input);
     }
 
+    /// Set the since source of the inputs locked by the lock script.
+    pub fn set_since_source(&mut self, lock_script: Script, since_source: SinceSource) {
+        self.since_sources
+            .retain(|(script, _)| script != &lock_script);
+        self.since_sources.push((lock_script, since_source));
+    }
+
+    /// Add a multisig lock script after the other lock scripts, if `since_absolute_epoch` is
+    /// set, the since is stored in the lock args and the cells can not be spent before the epoch.
+    pub fn add_multisig_lock(
+        &mut self,
+        multisig_config: &MultisigConfig,
+        since_absolute_epoch: Option<u64>,
+    ) -> Script {
+        let lock_script = Script::from(&multisig_config.to_address_payload(since_absolute_epoch));
+        if since_absolute_epoch.is_some() {
+            // the since follows the 20 bytes blake160 hash of the multisig config
+            self.set_since_source(lock_script.clone(), SinceSource::LockArgs(20));
+        }
+        // the lock scripts are consumed from the end
+        self.lock_scripts.insert(0, lock_script.clone());
+        lock_script
+    }
+
+    /// Skip the cells which can not be spent yet in the next block of the since checker tip.
+    pub fn set_since_checker(&mut self, since_checker: Option<SinceChecker>) {
+        self.since_checker = since_checker;
+    }
+
+    /// Apply the transaction to the cell collector, its inputs are treated as dead cells and its
+    /// outputs as live cells, e.g. to spend the outputs of a transaction in the tx-pool.
+    pub fn apply_tx(
+        &mut self,
+        tx: Transaction,
+        tip_block_number: u64,
+    ) -> Result<(), CellCollectorError> {
+        self.cell_collector.apply_tx(tx, tip_block_number)
+    }
+
+    /// Add the built transaction to the wallet cell cache as a pending transaction, and release the
+    /// other reserved cells. It does nothing if the iterator is not created with a wallet cell cache.
+    pub fn add_pending_tx(&mut self, tx: &TransactionView) -> Result<(), CellCollectorError> {
+        match self.cell_cache.as_mut() {
+            Some(cell_cache) => cell_cache.apply_tx(tx.data(), 0),
+            None => Ok(()),
+        }
+    }
+
+    /// Release the reservations of the returned cells which are not used, e.g. the candidates not
+    /// taken by the input selector.
+    pub fn release_cells(&mut self, out_points: &[OutPoint]) {
+        if let Some(cell_cache) = self.cell_cache.as_mut() {
+            for out_point in out_points {
+                cell_cache.release_cell(out_point);
+            }
+        }
+    }
+
+    fn get_since(&self, lock_script: &Script) -> Result<u64, CellCollectorError> {
+        match self
+            .since_sources
+            .iter()
+            .find(|(script, _)| script == lock_script)
+        {
+            Some((_, since_source)) => since_source
+                .get_since(lock_script)
+                .map_err(|err| CellCollectorError::Other(anyhow!(err))),
+            None => Ok(0),
+        }
+    }
+
     fn collect_live_cells(&mut self) -> Result<(), CellCollectorError> {
         loop {
             if self.lock_scripts.is_empty() {
@@ -92,12 +214,23 @@ impl InputIterator {
                 if live_cells.is_empty() {
                     self.lock_scripts.pop();
                 } else {
-                    self.buffer_inputs = live_cells
-                        .into_iter()
-                        .rev() // reverse the iter, so that the first cell will be consumed while pop
-                        .map(|live_cell| TransactionInput::new(live_cell, 0))
-                        .collect();
-                    break;
+                    let since = self.get_since(lock_script)?;
+                    let mut inputs = Vec::with_capacity(live_cells.len());
+                    for live_cell in live_cells {
+                        if let Some(checker) = &self.since_checker {
+                            if !checker.is_spendable(since, &live_cell)? {
+                                self.time_locked_count += 1;
+                                continue;
+                            }
+                        }
+                        inputs.push(TransactionInput::new(live_cell, since));
+                    }
+                    // reverse the inputs, so that the first cell will be consumed while pop
+                    inputs.reverse();
+                    self.buffer_inputs = inputs;
+                    if !self.buffer_inputs.is_empty() {
+                        break;
+                    }
                 }
             }
         }
@@ -109,15 +242,38 @@ impl Iterator for InputIterator {
     type Item = Result<TransactionInput, CellCollectorError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(input) = self.buffer_inputs.pop() {
+        loop {
+            if self.buffer_inputs.is_empty() {
+                if let Err(status) = self.collect_live_cells() {
+                    return Some(Err(status));
+                }
+            }
+            let Some(input) = self.buffer_inputs.pop() else {
+                break;
+            };
+            // the cell may be taken by a concurrent builder after it's collected
+            if let Some(cell_cache) = self.cell_cache.as_mut() {
+                if !cell_cache.reserve_cell(&input.live_cell.out_point) {
+                    continue;
+                }
+            }
+            self.returned_count += 1;
             return Some(Ok(input));
         }
 
-        let status = self.collect_live_cells();
-        if let Err(status) = status {
-            Some(Err(status))
-        } else {
-            self.buffer_inputs.pop().map(Ok)
+        if self.returned_count == 0 && self.time_locked_count > 0 {
+            let tip_number = self
+                .since_checker
+                .as_ref()
+                .map(|checker| checker.tip_header().number())
+                .unwrap_or_default();
+            let count = std::mem::take(&mut self.time_locked_count);
+            return Some(Err(CellCollectorError::Other(anyhow!(
+                "all the {} candidate cells are time-locked at the tip block {}",
+                count,
+                tip_number
+            ))));
         }
+        None
     }
 }
@@ -0,0 +1,316 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::TransactionInput;
+
+/// Input selection strategy trait.
+///
+/// The transaction builder collects the candidate inputs from the input iterator, up to
+/// [`max_candidates`](InputSelector::max_candidates), then uses the input selector to choose and
+/// order them, the selected inputs are consumed in order until the transaction is balanced.
+/// The inputs not returned by the selector will never be used.
+pub trait InputSelector {
+    /// Select and order the inputs from the candidates, `target_capacity` is the capacity
+    /// (in shannons) of the outputs which should be paid by the candidates, the fee and the
+    /// change output are not included.
+    fn select(
+        &mut self,
+        candidates: Vec<TransactionInput>,
+        target_capacity: u64,
+    ) -> Vec<TransactionInput>;
+
+    /// The maximum number of the candidates collected from the input iterator.
+    ///
+    /// The default implementation returns None, all the live cells of the input iterator are collected.
+    fn max_candidates(&self) -> Option<usize> {
+        None
+    }
+
+    /// Set the fee of the transaction without a change output before selecting, `base_fee` is the fee
+    /// without the selected candidates and `input_fee` is the fee added by each selected candidate.
+    ///
+    /// The default implementation ignores the fee.
+    fn set_fee(&mut self, _base_fee: u64, _input_fee: u64) {}
+
+    /// The change up to this capacity is merged into the fee instead of a change output, see
+    /// [`ChangeBuilder::set_max_extra_fee`](crate::transaction::builder::ChangeBuilder::set_max_extra_fee).
+    ///
+    /// The default implementation returns None, the change is handled by the small change policy.
+    fn max_extra_fee(&self) -> Option<u64> {
+        None
+    }
+}
+
+fn capacity(input: &TransactionInput) -> u64 {
+    input.capacity()
+}
+
+fn sort_by_capacity_desc(inputs: &mut [TransactionInput]) {
+    inputs.sort_by_key(|input| std::cmp::Reverse(capacity(input)));
+}
+
+/// Select the largest cells first, it minimizes the number of inputs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LargestFirstSelector;
+
+impl InputSelector for LargestFirstSelector {
+    fn select(
+        &mut self,
+        mut candidates: Vec<TransactionInput>,
+        _target_capacity: u64,
+    ) -> Vec<TransactionInput> {
+        sort_by_capacity_desc(&mut candidates);
+        candidates
+    }
+}
+
+/// Select the smallest cells first, it consolidates the dust cells of the wallet.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SmallestFirstSelector;
+
+impl InputSelector for SmallestFirstSelector {
+    fn select(
+        &mut self,
+        mut candidates: Vec<TransactionInput>,
+        _target_capacity: u64,
+    ) -> Vec<TransactionInput> {
+        candidates.sort_by_key(capacity);
+        candidates
+    }
+}
+
+/// Search for a set of cells whose total capacity is in `[target, target + tolerance]` with
+/// branch and bound, the target is the outputs capacity plus the fee of the transaction without a
+/// change output. The matched cells are placed in front and the other cells follow in the largest
+/// first order.
+///
+/// The transaction builder merges the change up to the tolerance into the fee, so the transaction
+/// of the matched cells has no change output.
+#[derive(Clone, Copy, Debug)]
+pub struct BranchAndBoundSelector {
+    /// The capacity allowed to exceed the target, it's paid as extra fee.
+    pub tolerance: u64,
+    /// The maximum number of the searched branches.
+    pub max_tries: usize,
+    /// The maximum number of the candidates collected from the input iterator, None to collect
+    /// all the live cells.
+    pub max_candidates: Option<usize>,
+    /// The fee of the transaction without a change output, see [`InputSelector::set_fee`].
+    base_fee: u64,
+    input_fee: u64,
+}
+
+impl BranchAndBoundSelector {
+    pub fn new(tolerance: u64) -> Self {
+        Self {
+            tolerance,
+            max_tries: 100_000,
+            max_candidates: Some(1000),
+            base_fee: 0,
+            input_fee: 0,
+        }
+    }
+
+    /// Returns the indices of the matched candidates, the candidates must be sorted by capacity desc.
+    fn search(&self, capacities: &[u64], target: u64) -> Option<Vec<usize>> {
+        // the fee grows with the selected candidates, adding a candidate never fixes an excess
+        // since a cell capacity is much larger than the fee of an input
+        let target_with_fee = |count: usize| {
+            target
+                .saturating_add(self.base_fee)
+                .saturating_add(self.input_fee.saturating_mul(count as u64))
+        };
+        // the sum of capacities[i..]
+        let mut remaining = vec![0u64; capacities.len() + 1];
+        for idx in (0..capacities.len()).rev() {
+            remaining[idx] = remaining[idx + 1].saturating_add(capacities[idx]);
+        }
+
+        let mut selected: Vec<usize> = Vec::new();
+        let mut selected_sum = 0u64;
+        let mut idx = 0;
+        let mut tries = 0;
+        loop {
+            tries += 1;
+            if tries > self.max_tries {
+                return None;
+            }
+            let required = target_with_fee(selected.len());
+            let backtrack = if selected_sum > required.saturating_add(self.tolerance)
+                || selected_sum.saturating_add(remaining[idx]) < required
+            {
+                true
+            } else if selected_sum >= required {
+                return Some(selected);
+            } else if idx >= capacities.len() {
+                true
+            } else {
+                // include the candidate at idx
+                selected.push(idx);
+                selected_sum += capacities[idx];
+                idx += 1;
+                false
+            };
+
+            if backtrack {
+                // exclude the last included candidate and try the next one
+                let last = selected.pop()?;
+                selected_sum -= capacities[last];
+                idx = last + 1;
+            }
+        }
+    }
+}
+
+impl InputSelector for BranchAndBoundSelector {
+    fn select(
+        &mut self,
+        mut candidates: Vec<TransactionInput>,
+        target_capacity: u64,
+    ) -> Vec<TransactionInput> {
+        sort_by_capacity_desc(&mut candidates);
+        let capacities: Vec<u64> = candidates.iter().map(capacity).collect();
+        if let Some(indices) = self.search(&capacities, target_capacity) {
+            let mut matched = Vec::with_capacity(indices.len());
+            let mut others = Vec::with_capacity(candidates.len() - indices.len());
+            for (idx, input) in candidates.into_iter().enumerate() {
+                if indices.contains(&idx) {
+                    matched.push(input);
+                } else {
+                    others.push(input);
+                }
+            }
+            matched.extend(others);
+            matched
+        } else {
+            candidates
+        }
+    }
+
+    fn max_candidates(&self) -> Option<usize> {
+        self.max_candidates
+    }
+
+    fn set_fee(&mut self, base_fee: u64, input_fee: u64) {
+        self.base_fee = base_fee;
+        self.input_fee = input_fee;
+    }
+
+    fn max_extra_fee(&self) -> Option<u64> {
+        Some(self.tolerance)
+    }
+}
+
+/// The random-improve strategy, it selects random cells until the target is reached, then keeps
+/// adding random cells while the total capacity gets closer to twice the target without exceeding
+/// three times the target, so the change output is about the same size as the payment.
+///
+/// The cells which are not selected follow in the largest first order.
+#[derive(Clone, Copy, Debug)]
+pub struct RandomImproveSelector {
+    state: u64,
+}
+
+impl RandomImproveSelector {
+    /// Creates a selector seeded with the current system time.
+    pub fn new() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+        Self::new_with_seed(seed)
+    }
+
+    /// Creates a selector with the given seed, the selection is deterministic for the same seed.
+    pub fn new_with_seed(seed: u64) -> Self {
+        // xorshift can not start from zero
+        Self { state: seed | 1 }
+    }
+
+    fn next_index(&mut self, len: usize) -> usize {
+        // xorshift64
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state % len as u64) as usize
+    }
+}
+
+impl Default for RandomImproveSelector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputSelector for RandomImproveSelector {
+    fn select(
+        &mut self,
+        mut candidates: Vec<TransactionInput>,
+        target_capacity: u64,
+    ) -> Vec<TransactionInput> {
+        let mut selected = Vec::new();
+        let mut selected_sum = 0u64;
+
+        // select random cells until the target is reached
+        while selected_sum < target_capacity && !candidates.is_empty() {
+            let input = candidates.swap_remove(self.next_index(candidates.len()));
+            selected_sum += capacity(&input);
+            selected.push(input);
+        }
+
+        // improve: move the total capacity towards the ideal one
+        let ideal = target_capacity.saturating_mul(2);
+        let upper_bound = target_capacity.saturating_mul(3);
+        while selected_sum >= target_capacity && !candidates.is_empty() {
+            let idx = self.next_index(candidates.len());
+            let new_sum = selected_sum + capacity(&candidates[idx]);
+            if new_sum > upper_bound || ideal.abs_diff(new_sum) >= ideal.abs_diff(selected_sum) {
+                break;
+            }
+            selected_sum = new_sum;
+            selected.push(candidates.swap_remove(idx));
+        }
+
+        sort_by_capacity_desc(&mut candidates);
+        selected.extend(candidates);
+        selected
+    }
+}
+
+/// Limit the number of inputs selected by the inner selector.
+pub struct MaxInputCountSelector {
+    inner: Box<dyn InputSelector>,
+    max_input_count: usize,
+}
+
+impl MaxInputCountSelector {
+    pub fn new(inner: Box<dyn InputSelector>, max_input_count: usize) -> Self {
+        Self {
+            inner,
+            max_input_count,
+        }
+    }
+}
+
+impl InputSelector for MaxInputCountSelector {
+    fn select(
+        &mut self,
+        candidates: Vec<TransactionInput>,
+        target_capacity: u64,
+    ) -> Vec<TransactionInput> {
+        let mut selected = self.inner.select(candidates, target_capacity);
+        selected.truncate(self.max_input_count);
+        selected
+    }
+
+    fn max_candidates(&self) -> Option<usize> {
+        self.inner.max_candidates()
+    }
+
+    fn set_fee(&mut self, base_fee: u64, input_fee: u64) {
+        self.inner.set_fee(base_fee, input_fee);
+    }
+
+    fn max_extra_fee(&self) -> Option<u64> {
+        self.inner.max_extra_fee()
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::{Arc, OnceLock};
+
+use anyhow::anyhow;
+use ckb_types::core::{EpochNumberWithFraction, HeaderView};
+
+use crate::{
+    traits::{CellCollectorError, HeaderDepResolver, LiveCell},
+    types::{Since, SinceType},
+};
+
+/// The number of the blocks to calculate the median time, see
+/// [RFC 0017](https://github.com/nervosnetwork/rfcs/blob/master/rfcs/0017-tx-valid-since/0017-tx-valid-since.md).
+const MEDIAN_TIME_BLOCK_COUNT: u64 = 37;
+
+/// Check whether the `since` of an input is satisfied in the next block of the tip, it's used by the
+/// [`InputIterator`](super::InputIterator) to skip the cells which can not be spent yet.
+///
+/// The epoch of the next block is unknown, so the epoch `since` is compared with the tip epoch.
+/// The timestamp `since` is compared with the median time of the past 37 blocks like the consensus,
+/// and the relative epoch and timestamp `since` require the header of the block containing the cell,
+/// the headers are resolved by the header dep resolver. Without a header dep resolver, the cells
+/// locked by a timestamp or a relative epoch `since` are treated as not spendable.
+#[derive(Clone)]
+pub struct SinceChecker {
+    tip_header: HeaderView,
+    header_dep_resolver: Option<Arc<dyn HeaderDepResolver>>,
+    /// The median time of the past 37 blocks up to the tip, it's resolved on demand
+    tip_median_time: OnceLock<Option<u64>>,
+}
+
+impl SinceChecker {
+    pub fn new(tip_header: HeaderView) -> Self {
+        Self {
+            tip_header,
+            header_dep_resolver: None,
+            tip_median_time: OnceLock::new(),
+        }
+    }
+
+    pub fn new_with_header_dep_resolver(
+        tip_header: HeaderView,
+        header_dep_resolver: Arc<dyn HeaderDepResolver>,
+    ) -> Self {
+        Self {
+            tip_header,
+            header_dep_resolver: Some(header_dep_resolver),
+            tip_median_time: OnceLock::new(),
+        }
+    }
+
+    pub fn tip_header(&self) -> &HeaderView {
+        &self.tip_header
+    }
+
+    /// Returns true if the cell can be spent with the `since` in the next block of the tip.
+    pub fn is_spendable(
+        &self,
+        since: u64,
+        live_cell: &LiveCell,
+    ) -> Result<bool, CellCollectorError> {
+        if since == 0 {
+            return Ok(true);
+        }
+        let since = Since::from_raw_value(since);
+        let (metric, value) = since
+            .extract_metric()
+            .filter(|_| since.flags_is_valid())
+            .ok_or_else(|| {
+                CellCollectorError::Other(anyhow!("invalid since value: {:#x}", since.value()))
+            })?;
+
+        // the since is checked against the block containing the transaction
+        let next_number = self.tip_header.number().saturating_add(1);
+        let tip_epoch = self.tip_header.epoch();
+        if since.is_absolute() {
+            let spendable = match metric {
+                SinceType::BlockNumber => next_number >= value,
+                SinceType::EpochNumberWithFraction => {
+                    let epoch = EpochNumberWithFraction::from_full_value(value);
+                    tip_epoch.to_rational() >= epoch.to_rational()
+                }
+                // the timestamp of since is in seconds
+                SinceType::Timestamp => match self.tip_median_time()? {
+                    Some(median_time) => median_time >= value.saturating_mul(1000),
+                    None => false,
+                },
+            };
+            return Ok(spendable);
+        }
+
+        if metric == SinceType::BlockNumber {
+            return Ok(next_number >= live_cell.block_number.saturating_add(value));
+        }
+        let spendable = match metric {
+            SinceType::EpochNumberWithFraction => {
+                let cell_header = match self.resolve_header(live_cell.block_number)? {
+                    Some(header) => header,
+                    None => return Ok(false),
+                };
+                let epoch = EpochNumberWithFraction::from_full_value(value);
+                tip_epoch.to_rational() >= cell_header.epoch().to_rational() + epoch.to_rational()
+            }
+            // the relative timestamp starts from the median time before the block containing the cell
+            SinceType::Timestamp => {
+                let base_time = match live_cell.block_number.checked_sub(1) {
+                    Some(parent_number) => self.median_time(parent_number)?,
+                    None => self.resolve_header(0)?.map(|header| header.timestamp()),
+                };
+                match (self.tip_median_time()?, base_time) {
+                    (Some(median_time), Some(base_time)) => {
+                        median_time >= base_time.saturating_add(value.saturating_mul(1000))
+                    }
+                    _ => false,
+                }
+            }
+            SinceType::BlockNumber => unreachable!(),
+        };
+        Ok(spendable)
+    }
+
+    fn tip_median_time(&self) -> Result<Option<u64>, CellCollectorError> {
+        if let Some(median_time) = self.tip_median_time.get() {
+            return Ok(*median_time);
+        }
+        let median_time = self.median_time(self.tip_header.number())?;
+        Ok(*self.tip_median_time.get_or_init(|| median_time))
+    }
+
+    /// The median time of the past 37 blocks up to the block, or fewer blocks near the genesis block.
+    fn median_time(&self, block_number: u64) -> Result<Option<u64>, CellCollectorError> {
+        let start = block_number.saturating_sub(MEDIAN_TIME_BLOCK_COUNT - 1);
+        let mut timestamps = Vec::with_capacity(MEDIAN_TIME_BLOCK_COUNT as usize);
+        for number in start..=block_number {
+            let header = if number == self.tip_header.number() {
+                self.tip_header.clone()
+            } else {
+                match self.resolve_header(number)? {
+                    Some(header) => header,
+                    None => return Ok(None),
+                }
+            };
+            timestamps.push(header.timestamp());
+        }
+        timestamps.sort_unstable();
+        Ok(Some(timestamps[timestamps.len() / 2]))
+    }
+
+    fn resolve_header(&self, block_number: u64) -> Result<Option<HeaderView>, CellCollectorError> {
+        match &self.header_dep_resolver {
+            Some(resolver) => resolver
+                .resolve_by_number(block_number)
+                .map_err(CellCollectorError::Other),
+            None => Ok(None),
+        }
+    }
+}
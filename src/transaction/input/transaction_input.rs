@@ -1,19 +1,57 @@
 use ckb_types::{
     packed,
-    prelude::{Builder, Entity, Pack},
+    prelude::{Builder, Entity, Pack, Unpack},
 };
 
-use crate::traits::LiveCell;
+use crate::traits::{
+    HeaderDepResolver, LiveCell, TransactionDependencyError, TransactionDependencyProvider,
+};
 
 #[derive(Clone, Debug)]
 pub struct TransactionInput {
     pub live_cell: LiveCell,
     pub since: u64,
+    /// The capacity the input provides besides the cell capacity, e.g. the compensation of a
+    /// withdrawing Nervos DAO cell, it's counted into the inputs capacity by the change builders.
+    pub extra_capacity: u64,
 }
 
 impl TransactionInput {
     pub fn new(live_cell: LiveCell, since: u64) -> Self {
-        Self { live_cell, since }
+        Self {
+            live_cell,
+            since,
+            extra_capacity: 0,
+        }
+    }
+
+    /// Resolve the cell of the out point with the transaction dependency provider, and the
+    /// `block_number` of the live cell with the header of the transaction resolved by the header
+    /// dep resolver.
+    ///
+    /// The `tx_index` is unknown and set to 0. The `block_number` is 0 if the header is not found,
+    /// e.g. by an offline resolver without the header, then the relative since and the cellbase
+    /// maturity of the input can not be checked by the block number.
+    pub fn new_with_out_point(
+        out_point: packed::OutPoint,
+        since: u64,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+        header_dep_resolver: &dyn HeaderDepResolver,
+    ) -> Result<Self, TransactionDependencyError> {
+        let output = tx_dep_provider.get_cell(&out_point)?;
+        let output_data = tx_dep_provider.get_cell_data(&out_point)?;
+        let block_number = header_dep_resolver
+            .resolve_by_tx(&out_point.tx_hash())?
+            .map(|header| header.number())
+            .unwrap_or_default();
+        let live_cell = LiveCell {
+            output,
+            output_data,
+            out_point,
+            block_number,
+            tx_index: 0,
+        };
+        Ok(Self::new(live_cell, since))
     }
 
     #[inline]
@@ -21,6 +59,17 @@ impl TransactionInput {
         self.since = since;
     }
 
+    #[inline]
+    pub fn set_extra_capacity(&mut self, extra_capacity: u64) {
+        self.extra_capacity = extra_capacity;
+    }
+
+    /// The capacity the input provides to the transaction, the cell capacity plus the extra capacity.
+    pub fn capacity(&self) -> u64 {
+        let capacity: u64 = self.live_cell.output.capacity().unpack();
+        capacity.saturating_add(self.extra_capacity)
+    }
+
     pub fn cell_input(&self) -> packed::CellInput {
         packed::CellInput::new_builder()
             .since(self.since.pack())
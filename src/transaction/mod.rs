@@ -1,10 +1,23 @@
+use anyhow::anyhow;
+use ckb_types::core::BlockView;
+
 use crate::{tx_builder::TxBuilderError, NetworkInfo};
 
-use self::{builder::FeeCalculator, handler::ScriptHandler};
+use self::{
+    builder::{FeeCalculator, SmallChangePolicy},
+    environment::BuildEnvironment,
+    fee_rate::FeeRatePolicy,
+    handler::ScriptHandler,
+    script_registry::{to_packed_cell_deps, ScriptRegistry},
+};
 
 pub mod builder;
+pub mod environment;
+pub mod fee_bumper;
+pub mod fee_rate;
 pub mod handler;
 pub mod input;
+pub mod script_registry;
 pub mod signer;
 
 pub struct TransactionBuilderConfiguration {
@@ -13,11 +26,31 @@ pub struct TransactionBuilderConfiguration {
     /// The script handlers for transaction builder, user can add their own script handlers.
     pub script_handlers: Vec<Box<dyn ScriptHandler>>,
     /// The fee rate for transaction builder, the default value is 1000 shannons/KB.
+    ///
+    /// It's overwritten by the fee rate policy at build time if the policy is set.
     pub fee_rate: u64,
+    /// The policy to decide the fee rate at build time, e.g. from the fee rate statistics of the node.
+    pub fee_rate_policy: Option<Box<dyn FeeRatePolicy>>,
     /// The estimate tx size in bytes, the maximum size of the tx-pool to accept transactions is 512000,
     /// a typical TWO_IN_TWO_OUT secp256k1-sig-hash-all transaction size is about 597 bytes,
     /// we set the default value to 128000, it's enough for most cases, and user can change it if needed.
+    ///
+    /// It's only used to calculate the fee when the witness size of a lock script group
+    /// can not be estimated by the script handlers, see [`ScriptHandler::estimate_witness_size`].
     pub estimate_tx_size: u64,
+    /// The policy to handle the change which is too small for a change output,
+    /// the default policy is collecting more inputs.
+    pub small_change_policy: SmallChangePolicy,
+    /// The chain data resolvers for the script handlers, the default environment is offline
+    /// without any chain data.
+    pub environment: BuildEnvironment,
+    /// Whether to run the scripts with the environment after the placeholder witnesses are filled,
+    /// and pay the fee by the larger one of the transaction size and the cycles converted with
+    /// [`bytes_per_cycle`](crate::tx_builder::bytes_per_cycle), the default value is false.
+    ///
+    /// The lock scripts usually fail with the placeholder signatures, the cycles of such script
+    /// groups are estimated by [`ScriptHandler::estimate_cycles`], the build fails if they can not be estimated.
+    pub check_cycle_fee: bool,
 }
 
 impl TransactionBuilderConfiguration {
@@ -29,14 +62,81 @@ impl TransactionBuilderConfiguration {
         Self::new_with_network(NetworkInfo::testnet())
     }
 
-    pub fn new_with_network(network: NetworkInfo) -> Result<Self, TxBuilderError> {
-        let script_handlers = Self::generate_system_handlers(&network)?;
-        Ok(Self {
+    // The default settings with the given script handlers.
+    fn new_with_script_handlers(
+        network: NetworkInfo,
+        script_handlers: Vec<Box<dyn ScriptHandler>>,
+    ) -> Self {
+        Self {
             network,
             script_handlers,
             fee_rate: 1000,
+            fee_rate_policy: None,
             estimate_tx_size: 128000,
-        })
+            small_change_policy: SmallChangePolicy::default(),
+            environment: BuildEnvironment::default(),
+            check_cycle_fee: false,
+        }
+    }
+
+    pub fn new_with_network(network: NetworkInfo) -> Result<Self, TxBuilderError> {
+        let script_handlers = Self::generate_system_handlers(&network)?;
+        Ok(Self::new_with_script_handlers(network, script_handlers))
+    }
+
+    /// Create a configuration with the script handlers of the scripts in the registry,
+    /// it works for any network, e.g. the staging network, the dev chain or a fork.
+    pub fn new_with_script_registry(
+        network: NetworkInfo,
+        registry: &ScriptRegistry,
+    ) -> Result<Self, TxBuilderError> {
+        let mut script_handlers: Vec<Box<dyn ScriptHandler>> = Vec::new();
+        if let Some(cell_deps) = registry.sighash_cell_deps.as_ref() {
+            script_handlers.push(Box::new(
+                handler::sighash::Secp256k1Blake160SighashAllScriptHandler::new_with_cell_deps(
+                    to_packed_cell_deps(cell_deps),
+                ),
+            ));
+        }
+        if let Some(cell_deps) = registry.multisig_cell_deps.as_ref() {
+            script_handlers.push(Box::new(
+                handler::multisig::Secp256k1Blake160MultisigAllScriptHandler::new_with_cell_deps(
+                    to_packed_cell_deps(cell_deps),
+                ),
+            ));
+        }
+        if let Some(sudt) = registry.sudt.as_ref() {
+            script_handlers.push(Box::new(handler::sudt::SudtHandler::new_with_cell_deps(
+                sudt.packed_cell_deps(),
+                sudt.script_id(),
+            )));
+        }
+        script_handlers.push(Box::new(handler::typeid::TypeIdHandler));
+        if let Some(cell_deps) = registry.dao_cell_deps.as_ref() {
+            script_handlers.push(Box::new(
+                handler::dao::DaoScriptHandler::new_with_cell_deps(to_packed_cell_deps(cell_deps)),
+            ));
+        }
+        if let Some(omnilock) = registry.omnilock.as_ref() {
+            script_handlers.push(Box::new(
+                handler::omnilock::OmniLockScriptHandler::new_with_cell_deps(
+                    omnilock.packed_cell_deps(),
+                    omnilock.script_id(),
+                ),
+            ));
+        }
+        Ok(Self::new_with_script_handlers(network, script_handlers))
+    }
+
+    /// Create a configuration with the system script handlers discovered from the genesis block,
+    /// see [`ScriptRegistry::from_genesis`].
+    pub fn new_with_genesis(
+        network: NetworkInfo,
+        genesis_block: &BlockView,
+    ) -> Result<Self, TxBuilderError> {
+        let registry = ScriptRegistry::from_genesis(genesis_block)
+            .map_err(|err| TxBuilderError::Other(anyhow!(err)))?;
+        Self::new_with_script_registry(network, &registry)
     }
 
     fn generate_system_handlers(
@@ -55,6 +155,10 @@ impl TransactionBuilderConfiguration {
             ) as Box<_>,
             Box::new(handler::sudt::SudtHandler::new_with_network(network)?) as Box<_>,
             Box::new(handler::typeid::TypeIdHandler) as Box<_>,
+            Box::new(handler::dao::DaoScriptHandler::new_with_network(network)?) as Box<_>,
+            Box::new(handler::omnilock::OmniLockScriptHandler::new_with_network(
+                network,
+            )?) as Box<_>,
         ];
         Ok(ret)
     }
@@ -71,10 +175,33 @@ impl TransactionBuilderConfiguration {
         &self.script_handlers
     }
 
+    /// Set the chain data resolvers for the script handlers, e.g. [`BuildEnvironment::new_with_rpc`].
+    pub fn set_environment(&mut self, environment: BuildEnvironment) {
+        self.environment = environment;
+    }
+
+    pub fn get_environment(&self) -> &BuildEnvironment {
+        &self.environment
+    }
+
     pub fn get_fee_rate(&self) -> u64 {
         self.fee_rate
     }
 
+    /// Set the policy to decide the fee rate at build time, it takes precedence over `fee_rate`.
+    pub fn set_fee_rate_policy(&mut self, fee_rate_policy: Box<dyn FeeRatePolicy>) {
+        self.fee_rate_policy = Some(fee_rate_policy);
+    }
+
+    /// Update `fee_rate` with the fee rate policy if any, and return the fee rate to use.
+    pub fn update_fee_rate(&mut self) -> Result<u64, TxBuilderError> {
+        if let Some(policy) = &self.fee_rate_policy {
+            self.fee_rate = policy.fee_rate()?;
+            log::debug!("fee rate from the fee rate policy: {}", self.fee_rate);
+        }
+        Ok(self.fee_rate)
+    }
+
     pub fn fee_calculator(&self) -> FeeCalculator {
         FeeCalculator::new(self.fee_rate)
     }
@@ -0,0 +1,96 @@
+use ckb_jsonrpc_types as json_types;
+use ckb_types::{core::BlockView, packed::CellDep, H256};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    traits::{default_impls::ParseGenesisInfoError, DefaultCellDepResolver},
+    ScriptId,
+};
+
+/// A deployed script, it is identified by `code_hash` and `hash_type`, and referenced by `cell_deps`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ScriptInfo {
+    pub code_hash: H256,
+    pub hash_type: json_types::ScriptHashType,
+    pub cell_deps: Vec<json_types::CellDep>,
+}
+
+impl ScriptInfo {
+    pub fn new(script_id: ScriptId, cell_deps: Vec<CellDep>) -> Self {
+        Self {
+            code_hash: script_id.code_hash,
+            hash_type: script_id.hash_type.into(),
+            cell_deps: cell_deps.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn script_id(&self) -> ScriptId {
+        ScriptId::new(self.code_hash.clone(), self.hash_type.clone().into())
+    }
+
+    pub fn packed_cell_deps(&self) -> Vec<CellDep> {
+        to_packed_cell_deps(&self.cell_deps)
+    }
+}
+
+/// The cell deps of the scripts on a network, it is used to build the system script handlers of
+/// the networks other than mainnet and testnet, e.g. the staging network, the dev chain or a fork.
+///
+/// The script ids of the sighash, multisig and Nervos DAO scripts are the same on all networks,
+/// so only their cell deps are required. The missing scripts are not supported by the configuration.
+///
+/// A registry file looks like:
+/// ```json
+/// {
+///   "sighash_cell_deps": [
+///     {
+///       "out_point": { "tx_hash": "0x...", "index": "0x0" },
+///       "dep_type": "dep_group"
+///     }
+///   ],
+///   "sudt": {
+///     "code_hash": "0x...",
+///     "hash_type": "data1",
+///     "cell_deps": [{ "out_point": { "tx_hash": "0x...", "index": "0x0" }, "dep_type": "code" }]
+///   }
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScriptRegistry {
+    #[serde(default)]
+    pub sighash_cell_deps: Option<Vec<json_types::CellDep>>,
+    #[serde(default)]
+    pub multisig_cell_deps: Option<Vec<json_types::CellDep>>,
+    #[serde(default)]
+    pub dao_cell_deps: Option<Vec<json_types::CellDep>>,
+    #[serde(default)]
+    pub sudt: Option<ScriptInfo>,
+    #[serde(default)]
+    pub omnilock: Option<ScriptInfo>,
+}
+
+impl ScriptRegistry {
+    /// Discover the cell deps of the system scripts (sighash, multisig and Nervos DAO) from the genesis block.
+    pub fn from_genesis(genesis_block: &BlockView) -> Result<Self, ParseGenesisInfoError> {
+        let resolver = DefaultCellDepResolver::from_genesis(genesis_block)?;
+        let cell_deps = |dep: Option<&(CellDep, String)>| {
+            dep.map(|(cell_dep, _)| vec![json_types::CellDep::from(cell_dep.clone())])
+        };
+        Ok(Self {
+            sighash_cell_deps: cell_deps(resolver.sighash_dep()),
+            multisig_cell_deps: cell_deps(resolver.multisig_dep()),
+            dao_cell_deps: cell_deps(resolver.dao_dep()),
+            sudt: None,
+            omnilock: None,
+        })
+    }
+
+    /// Load the registry from a JSON string.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+pub(crate) fn to_packed_cell_deps(cell_deps: &[json_types::CellDep]) -> Vec<CellDep> {
+    cell_deps.iter().cloned().map(Into::into).collect()
+}
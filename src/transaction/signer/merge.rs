@@ -0,0 +1,264 @@
+//! Merge the copies of the same transaction signed by different signers, e.g. the cosigners of a multisig script.
+use ckb_types::{
+    bytes::Bytes,
+    core::TransactionView,
+    packed::{self, Byte32, WitnessArgs},
+    prelude::*,
+    H160,
+};
+use thiserror::Error;
+
+use super::multisig::{normalize_signatures, select_signatures};
+use crate::{
+    types::omni_lock::OmniLockWitnessLock,
+    unlock::{generate_message, MultisigConfig},
+    PortableTransaction, ScriptGroup, ScriptGroupType, TransactionWithScriptGroups,
+};
+
+#[derive(Error, Debug)]
+pub enum TransactionMergeError {
+    #[error("can not merge different transactions: {0:#x} and {1:#x}")]
+    DifferentTransaction(Byte32, Byte32),
+
+    #[error("the witnesses at index {0} conflict with each other")]
+    ConflictWitness(usize),
+}
+
+/// Merge the witnesses of another copy of the same transaction into `tx`, e.g. the copies signed by the
+/// cosigners of a multisig script.
+///
+/// The witnesses are merged one by one: the empty or placeholder lock (all zeros) is replaced by the
+/// signed one, and the signatures in the multisig locks (secp256k1 multisig or OmniLock multisig) are
+/// combined and sorted by the cosigners' order in the multisig config. Any other difference is a conflict.
+pub fn merge_transaction(
+    tx: &mut TransactionWithScriptGroups,
+    other: &TransactionWithScriptGroups,
+) -> Result<(), TransactionMergeError> {
+    let tx_hash = tx.tx_view.hash();
+    if tx_hash != other.tx_view.hash() {
+        return Err(TransactionMergeError::DifferentTransaction(
+            tx_hash,
+            other.tx_view.hash(),
+        ));
+    }
+    let mut witnesses: Vec<packed::Bytes> = tx.tx_view.witnesses().into_iter().collect();
+    for (index, other_witness) in other.tx_view.witnesses().into_iter().enumerate() {
+        if index >= witnesses.len() {
+            witnesses.push(other_witness);
+            continue;
+        }
+        let witness = merge_witness(&witnesses[index].raw_data(), &other_witness.raw_data())
+            .ok_or(TransactionMergeError::ConflictWitness(index))?;
+        witnesses[index] = witness.pack();
+    }
+    tx.tx_view = tx
+        .tx_view
+        .as_advanced_builder()
+        .set_witnesses(witnesses)
+        .build();
+    for script_group in &other.script_groups {
+        if !tx.script_groups.contains(script_group) {
+            tx.script_groups.push(script_group.clone());
+        }
+    }
+    for script_group in &tx.script_groups {
+        if script_group.group_type != ScriptGroupType::Lock {
+            continue;
+        }
+        if let Some(tx_view) = sort_multisig_signatures(&tx.tx_view, script_group) {
+            tx.tx_view = tx_view;
+        }
+    }
+    if tx.change_decision.is_none() {
+        tx.change_decision = other.change_decision;
+    }
+    if tx.fee_rate.is_none() {
+        tx.fee_rate = other.fee_rate;
+    }
+    Ok(())
+}
+
+/// Merge the witnesses of another copy of the same portable transaction into `tx` by
+/// [`merge_transaction`], the cells and headers missing in `tx` are taken from the other one.
+pub fn merge_portable_transaction(
+    tx: &mut PortableTransaction,
+    other: &PortableTransaction,
+) -> Result<(), TransactionMergeError> {
+    merge_transaction(tx.tx_with_groups_mut(), other.tx_with_groups())?;
+    tx.add_resolved_from(other);
+    Ok(())
+}
+
+fn merge_witness(witness: &Bytes, other: &Bytes) -> Option<Bytes> {
+    if witness == other || other.is_empty() {
+        return Some(witness.clone());
+    } else if witness.is_empty() {
+        return Some(other.clone());
+    }
+    let witness_args = WitnessArgs::from_slice(witness).ok()?;
+    let other_args = WitnessArgs::from_slice(other).ok()?;
+    if witness_args.input_type().as_slice() != other_args.input_type().as_slice()
+        || witness_args.output_type().as_slice() != other_args.output_type().as_slice()
+    {
+        return None;
+    }
+    let lock = match (
+        witness_args.lock().to_opt().map(|lock| lock.raw_data()),
+        other_args.lock().to_opt().map(|lock| lock.raw_data()),
+    ) {
+        (Some(lock), Some(other_lock)) => Some(merge_lock(&lock, &other_lock)?),
+        (lock, other_lock) => lock.or(other_lock),
+    };
+    Some(
+        witness_args
+            .as_builder()
+            .lock(lock.pack())
+            .build()
+            .as_bytes(),
+    )
+}
+
+fn merge_lock(lock: &Bytes, other: &Bytes) -> Option<Bytes> {
+    if lock == other {
+        return Some(lock.clone());
+    }
+    if lock.len() == other.len() {
+        if lock.iter().all(|byte| *byte == 0) {
+            return Some(other.clone());
+        } else if other.iter().all(|byte| *byte == 0) {
+            return Some(lock.clone());
+        }
+    }
+    if let Some(lock) = merge_multisig_signatures(lock, other) {
+        return Some(lock);
+    }
+    // the multisig signatures of OmniLock are in the signature field of the witness lock
+    let witness_lock = OmniLockWitnessLock::from_slice(lock).ok()?;
+    let other_witness_lock = OmniLockWitnessLock::from_slice(other).ok()?;
+    if witness_lock.omni_identity().as_slice() != other_witness_lock.omni_identity().as_slice()
+        || witness_lock.preimage().as_slice() != other_witness_lock.preimage().as_slice()
+    {
+        return None;
+    }
+    let signature = match (
+        witness_lock
+            .signature()
+            .to_opt()
+            .map(|signature| signature.raw_data()),
+        other_witness_lock
+            .signature()
+            .to_opt()
+            .map(|signature| signature.raw_data()),
+    ) {
+        (Some(signature), Some(other_signature)) => {
+            Some(merge_multisig_signatures(&signature, &other_signature)?)
+        }
+        (signature, other_signature) => signature.or(other_signature),
+    };
+    Some(
+        witness_lock
+            .as_builder()
+            .signature(signature.pack())
+            .build()
+            .as_bytes(),
+    )
+}
+
+/// Merge the signatures of two multisig locks with the same multisig config, the layout is:
+/// `0u8 | require_first_n | threshold | pubkeys_count | pubkey_hashes | signatures`, the unsigned
+/// signatures are all zeros.
+fn merge_multisig_signatures(lock: &Bytes, other: &Bytes) -> Option<Bytes> {
+    if lock.len() != other.len() || lock.len() < 4 || lock[0] != 0 {
+        return None;
+    }
+    let threshold = lock[2] as usize;
+    let config_len = 4 + 20 * lock[3] as usize;
+    if lock.len() != config_len + 65 * threshold || lock[..config_len] != other[..config_len] {
+        return None;
+    }
+    let mut signatures: Vec<&[u8]> = Vec::with_capacity(threshold);
+    for signature in lock[config_len..]
+        .chunks_exact(65)
+        .chain(other[config_len..].chunks_exact(65))
+    {
+        if signature.iter().any(|byte| *byte != 0) && !signatures.contains(&signature) {
+            signatures.push(signature);
+        }
+    }
+    if signatures.len() > threshold {
+        return None;
+    }
+    let mut merged = lock[..config_len].to_vec();
+    for signature in signatures {
+        merged.extend_from_slice(signature);
+    }
+    merged.resize(lock.len(), 0);
+    Some(Bytes::from(merged))
+}
+
+/// Sort the signatures of the multisig lock by the cosigners' order in the multisig config, which is
+/// required by the multisig scripts, None if the lock of the script group is not a multisig lock.
+fn sort_multisig_signatures(
+    tx: &TransactionView,
+    script_group: &ScriptGroup,
+) -> Option<TransactionView> {
+    let witness_idx = *script_group.input_indices.first()?;
+    let witness = WitnessArgs::from_slice(&tx.witnesses().get(witness_idx)?.raw_data()).ok()?;
+    let lock = witness.lock().to_opt()?.raw_data();
+    if let Some(config) = parse_multisig_config(&lock) {
+        let args = script_group.script.args().raw_data();
+        if args.get(..20) != Some(config.hash160().as_bytes()) {
+            return None;
+        }
+        return normalize_signatures(tx, script_group, &config).ok();
+    }
+
+    // the OmniLock multisig signs the message with the whole witness lock zeroed
+    let witness_lock = OmniLockWitnessLock::from_slice(&lock).ok()?;
+    let signature = witness_lock.signature().to_opt()?.raw_data();
+    let config = parse_multisig_config(&signature)?;
+    let message = generate_message(tx, script_group, Bytes::from(vec![0u8; lock.len()])).ok()?;
+    let config_len = config.to_witness_data().len();
+    let signatures: Vec<Bytes> = signature[config_len..]
+        .chunks_exact(65)
+        .filter(|signature| signature.iter().any(|byte| *byte != 0))
+        .map(Bytes::copy_from_slice)
+        .collect();
+    let sorted = select_signatures(&config, &message, signatures.clone());
+    // keep the lock as it is if any signature is not signed by the cosigners
+    if sorted.len() != signatures.len() {
+        return None;
+    }
+    let mut sorted_signature = signature[..config_len].to_vec();
+    for signature in sorted {
+        sorted_signature.extend_from_slice(&signature);
+    }
+    sorted_signature.resize(signature.len(), 0);
+    let witness_lock = witness_lock
+        .as_builder()
+        .signature(Some(Bytes::from(sorted_signature)).pack())
+        .build();
+    let witness = witness
+        .as_builder()
+        .lock(Some(witness_lock.as_bytes()).pack())
+        .build();
+    let mut witnesses: Vec<packed::Bytes> = tx.witnesses().into_iter().collect();
+    witnesses[witness_idx] = witness.as_bytes().pack();
+    Some(tx.as_advanced_builder().set_witnesses(witnesses).build())
+}
+
+/// Parse the multisig config of the multisig lock `multisig_config | signatures`.
+fn parse_multisig_config(lock: &[u8]) -> Option<MultisigConfig> {
+    if lock.len() < 4 || lock[0] != 0 {
+        return None;
+    }
+    let config_len = 4 + 20 * lock[3] as usize;
+    if lock.len() != config_len + 65 * lock[2] as usize {
+        return None;
+    }
+    let sighash_addresses = lock[4..config_len]
+        .chunks_exact(20)
+        .map(|hash| H160::from_slice(hash).expect("20 bytes"))
+        .collect();
+    MultisigConfig::new_with(sighash_addresses, lock[1], lock[2]).ok()
+}
@@ -3,14 +3,16 @@ use std::collections::HashMap;
 
 use crate::{
     constants,
-    unlock::{MultisigConfig, UnlockError},
-    NetworkInfo, ScriptGroup, ScriptId, TransactionWithScriptGroups,
+    unlock::{MultisigConfig, OmniLockConfig, OmniUnlockMode, UnlockError},
+    NetworkInfo, NetworkType, ScriptGroup, ScriptId, TransactionWithScriptGroups,
 };
 
 use self::sighash::Secp256k1Blake160SighashAllSigner;
 
-use super::handler::Type2Any;
+use super::{handler::Type2Any, TransactionBuilderConfiguration};
+pub mod merge;
 pub mod multisig;
+pub mod omnilock;
 pub mod sighash;
 
 pub trait CKBScriptSigner {
@@ -68,32 +70,80 @@ impl SignContexts {
         Ok(Self::new_multisig(key, multisig_config))
     }
 
+    pub fn new_omnilock(
+        keys: Vec<secp256k1::SecretKey>,
+        omnilock_config: OmniLockConfig,
+        unlock_mode: OmniUnlockMode,
+    ) -> Self {
+        let omnilock_context =
+            omnilock::OmniLockSignerContext::new(keys, omnilock_config, unlock_mode);
+        Self {
+            contexts: vec![Box::new(omnilock_context)],
+        }
+    }
+
     #[inline]
     pub fn add_context(&mut self, context: Box<dyn SignContext>) {
         self.contexts.push(context);
     }
 }
 
+/// The transaction signer, it signs the script groups of a transaction with the registered
+/// script signers.
+///
+/// Several signers can be registered for the same [`ScriptId`], they are tried in the order
+/// of registration until one of them matches a sign context.
 pub struct TransactionSigner {
-    unlockers: HashMap<ScriptId, Box<dyn CKBScriptSigner>>,
+    unlockers: HashMap<ScriptId, Vec<Box<dyn CKBScriptSigner>>>,
 }
 
 impl TransactionSigner {
-    pub fn new(_network: &NetworkInfo) -> Self {
-        let mut unlockers = HashMap::default();
-
-        let sighash_script_id = ScriptId::new_type(constants::SIGHASH_TYPE_HASH.clone());
-        unlockers.insert(
-            sighash_script_id,
-            Box::new(Secp256k1Blake160SighashAllSigner {}) as Box<_>,
+    pub fn new(network: &NetworkInfo) -> Self {
+        let mut ret = Self {
+            unlockers: HashMap::default(),
+        };
+
+        ret.register_signer(
+            ScriptId::new_type(constants::SIGHASH_TYPE_HASH.clone()),
+            Box::new(Secp256k1Blake160SighashAllSigner {}),
         );
-
-        unlockers.insert(
+        ret.register_signer(
             ScriptId::new_type(constants::MULTISIG_TYPE_HASH.clone()),
-            Box::new(multisig::Secp256k1Blake160MultisigAllSigner {}) as Box<_>,
+            Box::new(multisig::Secp256k1Blake160MultisigAllSigner {}),
         );
 
-        Self { unlockers }
+        let omnilock_code_hash = match network.network_type {
+            NetworkType::Mainnet => Some(constants::OMNILOCK_TYPE_HASH_LINA),
+            NetworkType::Testnet => Some(constants::OMNILOCK_TYPE_HASH_AGGRON),
+            _ => None,
+        };
+        if let Some(code_hash) = omnilock_code_hash {
+            ret.register_signer(
+                ScriptId::new_type(code_hash),
+                Box::new(omnilock::OmniLockSigner {}),
+            );
+        }
+
+        ret
+    }
+
+    /// Create a transaction signer with the signers of the script handlers in the configuration.
+    pub fn new_with_configuration(configuration: &TransactionBuilderConfiguration) -> Self {
+        let mut ret = Self {
+            unlockers: HashMap::default(),
+        };
+        for handler in configuration.get_script_handlers() {
+            for (script_id, signer) in handler.signers() {
+                ret.register_signer(script_id, signer);
+            }
+        }
+        ret
+    }
+
+    /// Register a signer for the script id, it will be tried after the signers
+    /// registered before for the same script id.
+    pub fn register_signer(&mut self, script_id: ScriptId, signer: Box<dyn CKBScriptSigner>) {
+        self.unlockers.entry(script_id).or_default().push(signer);
     }
 
     pub fn sign_transaction(
@@ -108,14 +158,19 @@ impl TransactionSigner {
         let mut tx = transaction.get_tx_view().clone();
         for (idx, script_group) in transaction.get_script_groups().iter().enumerate() {
             let script_id = ScriptId::from(&script_group.script);
-            if let Some(unlocker) = self.unlockers.get(&script_id) {
+            let unlockers = if let Some(unlockers) = self.unlockers.get(&script_id) {
+                unlockers
+            } else {
+                continue;
+            };
+            'unlockers: for unlocker in unlockers {
                 for context in &contexts.contexts {
                     if !unlocker.match_context(context.as_ref()) {
                         continue;
                     }
                     tx = unlocker.sign_transaction(&tx, script_group, context.as_ref())?;
                     signed_groups_indices.push(idx);
-                    break;
+                    break 'unlockers;
                 }
             }
         }
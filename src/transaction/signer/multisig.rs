@@ -1,10 +1,20 @@
-use ckb_types::core;
+use anyhow::anyhow;
+use ckb_types::{
+    bytes::Bytes,
+    core::{self, TransactionView},
+    packed::{self, WitnessArgs},
+    prelude::*,
+};
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
 
 use crate::{
     traits::{dummy_impls::DummyTransactionDependencyProvider, SecpCkbRawKeySigner},
     unlock::{
-        MultisigConfig, ScriptUnlocker, SecpMultisigScriptSigner, SecpMultisigUnlocker, UnlockError,
+        generate_message, MultisigConfig, ScriptSignError, ScriptUnlocker,
+        SecpMultisigScriptSigner, SecpMultisigUnlocker, UnlockError,
     },
+    util::blake160,
+    ScriptGroup, SECP256K1,
 };
 
 use super::{CKBScriptSigner, SignContext};
@@ -62,3 +72,205 @@ impl CKBScriptSigner for Secp256k1Blake160MultisigAllSigner {
         }
     }
 }
+
+// The functions below aggregate the signatures of the cosigners who sign independently, e.g. on
+// different machines. The witness lock layout of the secp256k1 multisig script is
+// `multisig_config | signature_0 | .. | signature_(threshold-1)`, the empty slots are all zeros, which is
+// the same for `Secp256k1Blake160MultisigAllSigner` and the legacy `SecpMultisigScriptSigner`.
+
+/// The message signed by the cosigners of the multisig script group.
+pub fn multisig_message(
+    tx: &TransactionView,
+    script_group: &ScriptGroup,
+    config: &MultisigConfig,
+) -> Result<Bytes, ScriptSignError> {
+    let tx = pad_witnesses(tx, script_group);
+    generate_message(&tx, script_group, placeholder_lock(config))
+}
+
+/// Extract the signatures in the witness lock of the multisig script group, the empty slots are skipped.
+pub fn get_signatures(
+    tx: &TransactionView,
+    script_group: &ScriptGroup,
+    config: &MultisigConfig,
+) -> Result<Vec<Bytes>, ScriptSignError> {
+    let lock = witness_lock(tx, script_group, config)?;
+    let config_len = config.to_witness_data().len();
+    Ok(lock[config_len..]
+        .chunks_exact(65)
+        .filter(|signature| signature.iter().any(|byte| *byte != 0))
+        .map(Bytes::copy_from_slice)
+        .collect())
+}
+
+/// Add a signature of a cosigner to the witness lock, the signatures are normalized,
+/// see [`normalize_signatures`].
+pub fn add_signature(
+    tx: &TransactionView,
+    script_group: &ScriptGroup,
+    config: &MultisigConfig,
+    signature: Bytes,
+) -> Result<TransactionView, ScriptSignError> {
+    let message = multisig_message(tx, script_group, config)?;
+    if recover_cosigner(config, &message, &signature).is_none() {
+        return Err(ScriptSignError::Other(anyhow!(
+            "the signature is not signed by any cosigner of the multisig config"
+        )));
+    }
+    let mut signatures = get_signatures(tx, script_group, config)?;
+    signatures.push(signature);
+    let signatures = select_signatures(config, &message, signatures);
+    Ok(set_signatures(tx, script_group, config, &signatures))
+}
+
+/// Check whether the witness lock has enough valid signatures: `threshold` signatures of different
+/// cosigners, and the first `require_first_n` cosigners are all included.
+pub fn is_threshold_reached(
+    tx: &TransactionView,
+    script_group: &ScriptGroup,
+    config: &MultisigConfig,
+) -> Result<bool, ScriptSignError> {
+    let message = multisig_message(tx, script_group, config)?;
+    let signatures = get_signatures(tx, script_group, config)?;
+    let mut cosigners: Vec<usize> = signatures
+        .iter()
+        .filter_map(|signature| recover_cosigner(config, &message, signature))
+        .collect();
+    cosigners.sort_unstable();
+    cosigners.dedup();
+    // the script rejects the invalid or duplicated signatures
+    Ok(cosigners.len() == signatures.len()
+        && cosigners.len() >= config.threshold() as usize
+        && (0..config.require_first_n() as usize).all(|index| cosigners.contains(&index)))
+}
+
+/// Normalize the signatures in the witness lock: the invalid and duplicated signatures are removed, the
+/// signatures are sorted by the cosigners' order in the multisig config, and if there are more than
+/// `threshold` signatures, the ones of the first `require_first_n` cosigners are kept first.
+pub fn normalize_signatures(
+    tx: &TransactionView,
+    script_group: &ScriptGroup,
+    config: &MultisigConfig,
+) -> Result<TransactionView, ScriptSignError> {
+    let message = multisig_message(tx, script_group, config)?;
+    let signatures = get_signatures(tx, script_group, config)?;
+    let signatures = select_signatures(config, &message, signatures);
+    Ok(set_signatures(tx, script_group, config, &signatures))
+}
+
+fn placeholder_lock(config: &MultisigConfig) -> Bytes {
+    let mut lock = config.to_witness_data();
+    lock.resize(lock.len() + 65 * config.threshold() as usize, 0);
+    Bytes::from(lock)
+}
+
+fn pad_witnesses(tx: &TransactionView, script_group: &ScriptGroup) -> TransactionView {
+    let witness_idx = script_group.input_indices[0];
+    let mut witnesses: Vec<packed::Bytes> = tx.witnesses().into_iter().collect();
+    if witnesses.len() > witness_idx {
+        return tx.clone();
+    }
+    witnesses.resize(witness_idx + 1, Default::default());
+    tx.as_advanced_builder().set_witnesses(witnesses).build()
+}
+
+fn witness_args(
+    tx: &TransactionView,
+    script_group: &ScriptGroup,
+) -> Result<WitnessArgs, ScriptSignError> {
+    let witness_data = tx
+        .witnesses()
+        .get(script_group.input_indices[0])
+        .map(|witness| witness.raw_data())
+        .unwrap_or_default();
+    if witness_data.is_empty() {
+        Ok(WitnessArgs::default())
+    } else {
+        Ok(WitnessArgs::from_slice(witness_data.as_ref())?)
+    }
+}
+
+fn witness_lock(
+    tx: &TransactionView,
+    script_group: &ScriptGroup,
+    config: &MultisigConfig,
+) -> Result<Bytes, ScriptSignError> {
+    let placeholder = placeholder_lock(config);
+    let lock = match witness_args(tx, script_group)?.lock().to_opt() {
+        Some(lock) => lock.raw_data(),
+        None => return Ok(placeholder),
+    };
+    let config_len = config.to_witness_data().len();
+    if lock.len() != placeholder.len() || lock[..config_len] != placeholder[..config_len] {
+        return Err(ScriptSignError::InvalidMultisigConfig(
+            "the witness lock doesn't match the multisig config".to_string(),
+        ));
+    }
+    Ok(lock)
+}
+
+/// Return the index of the cosigner in the multisig config who signed the signature.
+fn recover_cosigner(config: &MultisigConfig, message: &[u8], signature: &[u8]) -> Option<usize> {
+    if signature.len() != 65 {
+        return None;
+    }
+    let recovery_id = RecoveryId::from_i32(signature[64] as i32).ok()?;
+    let signature = RecoverableSignature::from_compact(&signature[0..64], recovery_id).ok()?;
+    let message = secp256k1::Message::from_slice(message).ok()?;
+    let pubkey = SECP256K1.recover_ecdsa(&message, &signature).ok()?;
+    let hash160 = blake160(&pubkey.serialize());
+    config
+        .sighash_addresses()
+        .iter()
+        .position(|address| address == &hash160)
+}
+
+/// Keep the valid signatures of different cosigners and sort them by the cosigners' order, at most
+/// `threshold` signatures are kept.
+pub(crate) fn select_signatures(
+    config: &MultisigConfig,
+    message: &[u8],
+    signatures: Vec<Bytes>,
+) -> Vec<Bytes> {
+    let mut signed: Vec<(usize, Bytes)> = signatures
+        .into_iter()
+        .filter_map(|signature| {
+            recover_cosigner(config, message, &signature).map(|index| (index, signature))
+        })
+        .collect();
+    signed.sort_by_key(|(index, _)| *index);
+    signed.dedup_by_key(|(index, _)| *index);
+    let threshold = config.threshold() as usize;
+    if signed.len() > threshold {
+        // the required cosigners go first, the sort is stable
+        let require_first_n = config.require_first_n() as usize;
+        signed.sort_by_key(|(index, _)| *index >= require_first_n);
+        signed.truncate(threshold);
+        signed.sort_by_key(|(index, _)| *index);
+    }
+    signed.into_iter().map(|(_, signature)| signature).collect()
+}
+
+fn set_signatures(
+    tx: &TransactionView,
+    script_group: &ScriptGroup,
+    config: &MultisigConfig,
+    signatures: &[Bytes],
+) -> TransactionView {
+    let mut lock = config.to_witness_data();
+    for signature in signatures {
+        lock.extend_from_slice(signature);
+    }
+    lock.resize(placeholder_lock(config).len(), 0);
+
+    let tx = pad_witnesses(tx, script_group);
+    let witness_idx = script_group.input_indices[0];
+    let witness = witness_args(&tx, script_group)
+        .unwrap_or_default()
+        .as_builder()
+        .lock(Some(Bytes::from(lock)).pack())
+        .build();
+    let mut witnesses: Vec<packed::Bytes> = tx.witnesses().into_iter().collect();
+    witnesses[witness_idx] = witness.as_bytes().pack();
+    tx.as_advanced_builder().set_witnesses(witnesses).build()
+}
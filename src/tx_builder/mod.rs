@@ -255,6 +255,7 @@ pub fn tx_fee(
     header_dep_resolver: &dyn HeaderDepResolver,
 ) -> Result<u64, TransactionFeeError> {
     let mut input_total: u64 = 0;
+    tx_dep_provider.prefetch_inputs(&tx)?;
     for input in tx.inputs() {
         let mut is_withdraw = false;
         let since: u64 = input.since().unpack();
@@ -322,6 +323,27 @@ pub enum SinceSource {
     Value(u64),
 }
 
+impl SinceSource {
+    /// Get the `since` value of the inputs locked by the lock script.
+    pub fn get_since(&self, lock_script: &Script) -> Result<u64, BalanceTxCapacityError> {
+        match self {
+            SinceSource::LockArgs(offset) => {
+                let lock_arg = lock_script.args().raw_data();
+                if lock_arg.len() < offset + 8 {
+                    return Err(BalanceTxCapacityError::InvalidSinceValue(
+                        *offset,
+                        lock_arg.len(),
+                    ));
+                }
+                let mut since_bytes = [0u8; 8];
+                since_bytes.copy_from_slice(&lock_arg[*offset..*offset + 8]);
+                Ok(u64::from_le_bytes(since_bytes))
+            }
+            SinceSource::Value(since_value) => Ok(*since_value),
+        }
+    }
+}
+
 impl Default for SinceSource {
     fn default() -> SinceSource {
         SinceSource::Value(0)
@@ -938,21 +960,7 @@ fn rebalance_tx_capacity(
                     witnesses.push(placeholder_witness.as_bytes().pack());
                 }
             }
-            let since = match since_source {
-                SinceSource::LockArgs(offset) => {
-                    let lock_arg = lock_script.args().raw_data();
-                    if lock_arg.len() < offset + 8 {
-                        return Err(BalanceTxCapacityError::InvalidSinceValue(
-                            *offset,
-                            lock_arg.len(),
-                        ));
-                    }
-                    let mut since_bytes = [0u8; 8];
-                    since_bytes.copy_from_slice(&lock_arg[*offset..*offset + 8]);
-                    u64::from_le_bytes(since_bytes)
-                }
-                SinceSource::Value(since_value) => *since_value,
-            };
+            let since = since_source.get_since(lock_script)?;
             inputs.extend(
                 more_cells
                     .into_iter()
@@ -975,6 +983,7 @@ pub fn gen_script_groups(
     let mut lock_groups: HashMap<Byte32, ScriptGroup> = HashMap::default();
     #[allow(clippy::mutable_key_type)]
     let mut type_groups: HashMap<Byte32, ScriptGroup> = HashMap::default();
+    tx_dep_provider.prefetch_inputs(tx)?;
     for (i, input) in tx.inputs().into_iter().enumerate() {
         let output = tx_dep_provider.get_cell(&input.previous_output())?;
         let lock_group_entry = lock_groups
@@ -4,6 +4,9 @@ mod human_capacity;
 mod network_type;
 #[allow(clippy::all)]
 pub mod omni_lock;
+pub mod portable_transaction;
+#[allow(clippy::all)]
+pub mod portable_tx_mol;
 mod script_group;
 mod script_id;
 mod since;
@@ -16,6 +19,7 @@ pub use address::{
 };
 pub use human_capacity::HumanCapacity;
 pub use network_type::{NetworkInfo, NetworkType};
+pub use portable_transaction::{PortableTransaction, PortableTxError, ResolvedCell};
 pub use script_group::{ScriptGroup, ScriptGroupType};
 pub use script_id::ScriptId;
 pub use since::{Since, SinceType};
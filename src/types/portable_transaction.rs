@@ -0,0 +1,503 @@
+use std::convert::TryFrom;
+
+use ckb_jsonrpc_types as json_types;
+use ckb_mock_tx_types::{MockCellDep, MockInfo, MockInput, MockTransaction};
+use ckb_types::{
+    bytes::Bytes,
+    core::{DepType, HeaderView, TransactionView},
+    error::VerificationError,
+    packed::{self, Byte32, CellDep, CellOutput, OutPoint, OutPointVec},
+    prelude::*,
+    H256,
+};
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    portable_tx_mol, transaction_with_groups::TransactionWithScriptGroups, ScriptGroup,
+    ScriptGroupType,
+};
+use crate::traits::{TransactionDependencyError, TransactionDependencyProvider};
+
+/// The current version of the portable transaction format.
+pub const PORTABLE_TX_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum PortableTxError {
+    #[error("unsupported portable transaction version: {0}")]
+    UnsupportedVersion(u32),
+
+    #[error("invalid portable transaction: {0}")]
+    Invalid(String),
+
+    #[error("json error: `{0}`")]
+    Json(#[from] serde_json::Error),
+
+    #[error("molecule error: `{0}`")]
+    Molecule(#[from] VerificationError),
+
+    #[error("transaction dependency error: `{0}`")]
+    TxDep(#[from] TransactionDependencyError),
+}
+
+/// A cell resolved from an input or a cell dep of the transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedCell {
+    pub out_point: OutPoint,
+    pub output: CellOutput,
+    pub data: Bytes,
+    /// The hash of the block which contains the cell, only needed by the scripts loading the header of the cell.
+    pub header: Option<Byte32>,
+}
+
+/// A transaction with its script groups and everything needed to sign and verify it offline:
+/// the resolved input cells, the resolved cell deps (the members of the dep groups included)
+/// and the header deps.
+///
+/// It can be exchanged as versioned JSON or molecule binary between the builder, the offline signers
+/// and the multisig cosigners, and the partially signed copies are combined by
+/// [`merge_portable_transaction`](crate::transaction::signer::merge::merge_portable_transaction).
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(into = "ReprPortableTransaction", try_from = "ReprPortableTransaction")]
+pub struct PortableTransaction {
+    tx_with_groups: TransactionWithScriptGroups,
+    inputs: Vec<ResolvedCell>,
+    cell_deps: Vec<ResolvedCell>,
+    header_deps: Vec<HeaderView>,
+}
+
+impl PortableTransaction {
+    /// Resolve the inputs, cell deps and header deps of the transaction with the provider.
+    pub fn new(
+        tx_with_groups: TransactionWithScriptGroups,
+        tx_dep_provider: &dyn TransactionDependencyProvider,
+    ) -> Result<Self, PortableTxError> {
+        let tx = tx_with_groups.get_tx_view();
+        let inputs = tx
+            .input_pts_iter()
+            .map(|out_point| resolve_cell(out_point, tx_dep_provider))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut cell_deps = Vec::new();
+        for cell_dep in tx.cell_deps_iter() {
+            let cell = resolve_cell(cell_dep.out_point(), tx_dep_provider)?;
+            let members = if cell_dep.dep_type() == DepType::DepGroup.into() {
+                OutPointVec::from_slice(&cell.data)?.into_iter().collect()
+            } else {
+                Vec::new()
+            };
+            cell_deps.push(cell);
+            for out_point in members {
+                cell_deps.push(resolve_cell(out_point, tx_dep_provider)?);
+            }
+        }
+        let header_deps = tx
+            .header_deps_iter()
+            .map(|block_hash| tx_dep_provider.get_header(&block_hash))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            tx_with_groups,
+            inputs,
+            cell_deps,
+            header_deps,
+        })
+    }
+
+    pub fn new_with_resolved(
+        tx_with_groups: TransactionWithScriptGroups,
+        inputs: Vec<ResolvedCell>,
+        cell_deps: Vec<ResolvedCell>,
+        header_deps: Vec<HeaderView>,
+    ) -> Self {
+        Self {
+            tx_with_groups,
+            inputs,
+            cell_deps,
+            header_deps,
+        }
+    }
+
+    pub fn tx_with_groups(&self) -> &TransactionWithScriptGroups {
+        &self.tx_with_groups
+    }
+
+    pub fn tx_with_groups_mut(&mut self) -> &mut TransactionWithScriptGroups {
+        &mut self.tx_with_groups
+    }
+
+    pub fn into_tx_with_groups(self) -> TransactionWithScriptGroups {
+        self.tx_with_groups
+    }
+
+    pub fn inputs(&self) -> &[ResolvedCell] {
+        &self.inputs
+    }
+
+    pub fn cell_deps(&self) -> &[ResolvedCell] {
+        &self.cell_deps
+    }
+
+    pub fn header_deps(&self) -> &[HeaderView] {
+        &self.header_deps
+    }
+
+    /// Add the resolved cells and headers of another copy of the same transaction which are missing in this copy.
+    pub fn add_resolved_from(&mut self, other: &PortableTransaction) {
+        for cell in &other.inputs {
+            if !self.inputs.iter().any(|c| c.out_point == cell.out_point) {
+                self.inputs.push(cell.clone());
+            }
+        }
+        for cell in &other.cell_deps {
+            if !self.cell_deps.iter().any(|c| c.out_point == cell.out_point) {
+                self.cell_deps.push(cell.clone());
+            }
+        }
+        for header in &other.header_deps {
+            if !self.header_deps.iter().any(|h| h.hash() == header.hash()) {
+                self.header_deps.push(header.clone());
+            }
+        }
+    }
+
+    /// Convert to the mock transaction, which can be verified by `ckb-debugger` or the `ckb-script` verifier.
+    pub fn to_mock_tx(&self) -> MockTransaction {
+        let tx = self.tx_with_groups.get_tx_view();
+        let inputs = tx
+            .inputs()
+            .into_iter()
+            .filter_map(|input| {
+                let cell = self
+                    .inputs
+                    .iter()
+                    .find(|cell| cell.out_point == input.previous_output())?;
+                Some(MockInput {
+                    input,
+                    output: cell.output.clone(),
+                    data: cell.data.clone(),
+                    header: cell.header.clone(),
+                })
+            })
+            .collect();
+        // the members of the dep groups are not in the transaction, they are loaded as code
+        let cell_deps = self
+            .cell_deps
+            .iter()
+            .map(|cell| MockCellDep {
+                cell_dep: tx
+                    .cell_deps_iter()
+                    .find(|cell_dep| cell_dep.out_point() == cell.out_point)
+                    .unwrap_or_else(|| {
+                        CellDep::new_builder()
+                            .out_point(cell.out_point.clone())
+                            .dep_type(DepType::Code.into())
+                            .build()
+                    }),
+                output: cell.output.clone(),
+                data: cell.data.clone(),
+                header: cell.header.clone(),
+            })
+            .collect();
+        MockTransaction {
+            mock_info: MockInfo {
+                inputs,
+                cell_deps,
+                header_deps: self.header_deps.clone(),
+                extensions: vec![],
+            },
+            tx: tx.data(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, PortableTxError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, PortableTxError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Serialize to the molecule binary format, see `schemas/portable_tx.mol`.
+    pub fn to_molecule_bytes(&self) -> Bytes {
+        let tx = self.tx_with_groups.get_tx_view();
+        let script_groups = self
+            .tx_with_groups
+            .get_script_groups()
+            .iter()
+            .map(|script_group| {
+                let group_type: u8 = match script_group.group_type {
+                    ScriptGroupType::Lock => 0,
+                    ScriptGroupType::Type => 1,
+                };
+                portable_tx_mol::ScriptGroupInfo::new_builder()
+                    .script(script_group.script.clone())
+                    .group_type(group_type.into())
+                    .input_indices(pack_indices(&script_group.input_indices))
+                    .output_indices(pack_indices(&script_group.output_indices))
+                    .build()
+            });
+        portable_tx_mol::PortableTx::new_builder()
+            .version(PORTABLE_TX_VERSION.pack())
+            .transaction(tx.data())
+            .script_groups(
+                portable_tx_mol::ScriptGroupInfoVec::new_builder()
+                    .extend(script_groups)
+                    .build(),
+            )
+            .inputs(pack_cells(&self.inputs))
+            .cell_deps(pack_cells(&self.cell_deps))
+            .header_deps(
+                portable_tx_mol::HeaderVec::new_builder()
+                    .extend(self.header_deps.iter().map(|header| header.data()))
+                    .build(),
+            )
+            .build()
+            .as_bytes()
+    }
+
+    pub fn from_molecule_bytes(slice: &[u8]) -> Result<Self, PortableTxError> {
+        // the data of a newer version may have more fields, it's rejected by the version
+        let data = portable_tx_mol::PortableTx::from_compatible_slice(slice)?;
+        let version: u32 = data.version().unpack();
+        if version != PORTABLE_TX_VERSION {
+            return Err(PortableTxError::UnsupportedVersion(version));
+        }
+        let mut script_groups = Vec::new();
+        for script_group in data.script_groups() {
+            let group_type = match u8::from(script_group.group_type()) {
+                0 => ScriptGroupType::Lock,
+                1 => ScriptGroupType::Type,
+                group_type => {
+                    return Err(PortableTxError::Invalid(format!(
+                        "unknown script group type: {}",
+                        group_type
+                    )))
+                }
+            };
+            script_groups.push(ScriptGroup {
+                script: script_group.script(),
+                group_type,
+                input_indices: unpack_indices(&script_group.input_indices()),
+                output_indices: unpack_indices(&script_group.output_indices()),
+            });
+        }
+        let tx_with_groups =
+            TransactionWithScriptGroups::new(data.transaction().into_view(), script_groups);
+        Ok(Self {
+            tx_with_groups,
+            inputs: unpack_cells(&data.inputs()),
+            cell_deps: unpack_cells(&data.cell_deps()),
+            header_deps: data
+                .header_deps()
+                .into_iter()
+                .map(|header| header.into_view())
+                .collect(),
+        })
+    }
+
+    fn find_cell(&self, out_point: &OutPoint) -> Result<&ResolvedCell, TransactionDependencyError> {
+        self.inputs
+            .iter()
+            .chain(self.cell_deps.iter())
+            .find(|cell| &cell.out_point == out_point)
+            .ok_or_else(|| TransactionDependencyError::NotFound(format!("cell: {}", out_point)))
+    }
+}
+
+/// The resolved cells can be used by the unlockers and signers offline.
+impl TransactionDependencyProvider for PortableTransaction {
+    fn get_transaction(
+        &self,
+        tx_hash: &Byte32,
+    ) -> Result<TransactionView, TransactionDependencyError> {
+        if &self.tx_with_groups.get_tx_view().hash() == tx_hash {
+            Ok(self.tx_with_groups.get_tx_view().clone())
+        } else {
+            Err(TransactionDependencyError::NotFound(format!(
+                "transaction: {:#x}",
+                tx_hash
+            )))
+        }
+    }
+    fn get_cell(&self, out_point: &OutPoint) -> Result<CellOutput, TransactionDependencyError> {
+        self.find_cell(out_point).map(|cell| cell.output.clone())
+    }
+    fn get_cell_data(&self, out_point: &OutPoint) -> Result<Bytes, TransactionDependencyError> {
+        self.find_cell(out_point).map(|cell| cell.data.clone())
+    }
+    fn get_header(&self, block_hash: &Byte32) -> Result<HeaderView, TransactionDependencyError> {
+        self.header_deps
+            .iter()
+            .find(|header| &header.hash() == block_hash)
+            .cloned()
+            .ok_or_else(|| {
+                TransactionDependencyError::NotFound(format!("header: {:#x}", block_hash))
+            })
+    }
+    fn get_block_extension(
+        &self,
+        _block_hash: &Byte32,
+    ) -> Result<Option<packed::Bytes>, TransactionDependencyError> {
+        Ok(None)
+    }
+}
+
+fn resolve_cell(
+    out_point: OutPoint,
+    tx_dep_provider: &dyn TransactionDependencyProvider,
+) -> Result<ResolvedCell, TransactionDependencyError> {
+    Ok(ResolvedCell {
+        output: tx_dep_provider.get_cell(&out_point)?,
+        data: tx_dep_provider.get_cell_data(&out_point)?,
+        out_point,
+        header: None,
+    })
+}
+
+fn pack_indices(indices: &[usize]) -> portable_tx_mol::Uint32Vec {
+    portable_tx_mol::Uint32Vec::new_builder()
+        .extend(indices.iter().map(|index| (*index as u32).pack()))
+        .build()
+}
+
+fn unpack_indices(indices: &portable_tx_mol::Uint32Vec) -> Vec<usize> {
+    indices
+        .clone()
+        .into_iter()
+        .map(|index| Unpack::<u32>::unpack(&index) as usize)
+        .collect()
+}
+
+fn pack_cells(cells: &[ResolvedCell]) -> portable_tx_mol::ResolvedCellVec {
+    let cells = cells.iter().map(|cell| {
+        portable_tx_mol::ResolvedCell::new_builder()
+            .out_point(cell.out_point.clone())
+            .output(cell.output.clone())
+            .data(cell.data.pack())
+            .header(
+                portable_tx_mol::Byte32Opt::new_builder()
+                    .set(cell.header.clone())
+                    .build(),
+            )
+            .build()
+    });
+    portable_tx_mol::ResolvedCellVec::new_builder()
+        .extend(cells)
+        .build()
+}
+
+fn unpack_cells(cells: &portable_tx_mol::ResolvedCellVec) -> Vec<ResolvedCell> {
+    cells
+        .clone()
+        .into_iter()
+        .map(|cell| ResolvedCell {
+            out_point: cell.out_point(),
+            output: cell.output(),
+            data: cell.data().raw_data(),
+            header: cell.header().to_opt(),
+        })
+        .collect()
+}
+
+/// The JSON representation of [`PortableTransaction`].
+#[derive(Serialize, Deserialize)]
+struct ReprPortableTransaction {
+    version: u32,
+    transaction: json_types::Transaction,
+    script_groups: Vec<ReprScriptGroup>,
+    inputs: Vec<ReprResolvedCell>,
+    cell_deps: Vec<ReprResolvedCell>,
+    header_deps: Vec<json_types::HeaderView>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ReprScriptGroup {
+    script: json_types::Script,
+    group_type: ScriptGroupType,
+    input_indices: Vec<json_types::Uint32>,
+    output_indices: Vec<json_types::Uint32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ReprResolvedCell {
+    out_point: json_types::OutPoint,
+    output: json_types::CellOutput,
+    data: json_types::JsonBytes,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    header: Option<H256>,
+}
+
+impl From<PortableTransaction> for ReprPortableTransaction {
+    fn from(tx: PortableTransaction) -> Self {
+        let to_repr_cell = |cell: ResolvedCell| ReprResolvedCell {
+            out_point: cell.out_point.into(),
+            output: cell.output.into(),
+            data: json_types::JsonBytes::from_bytes(cell.data),
+            header: cell.header.map(|hash| hash.unpack()),
+        };
+        let to_repr_indices = |indices: Vec<usize>| {
+            indices
+                .into_iter()
+                .map(|index| (index as u32).into())
+                .collect()
+        };
+        let script_groups = tx
+            .tx_with_groups
+            .get_script_groups()
+            .iter()
+            .cloned()
+            .map(|script_group| ReprScriptGroup {
+                script: script_group.script.into(),
+                group_type: script_group.group_type,
+                input_indices: to_repr_indices(script_group.input_indices),
+                output_indices: to_repr_indices(script_group.output_indices),
+            })
+            .collect();
+        ReprPortableTransaction {
+            version: PORTABLE_TX_VERSION,
+            transaction: tx.tx_with_groups.get_tx_view().data().into(),
+            script_groups,
+            inputs: tx.inputs.into_iter().map(to_repr_cell).collect(),
+            cell_deps: tx.cell_deps.into_iter().map(to_repr_cell).collect(),
+            header_deps: tx.header_deps.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<ReprPortableTransaction> for PortableTransaction {
+    type Error = PortableTxError;
+
+    fn try_from(repr: ReprPortableTransaction) -> Result<Self, Self::Error> {
+        if repr.version != PORTABLE_TX_VERSION {
+            return Err(PortableTxError::UnsupportedVersion(repr.version));
+        }
+        let from_repr_cell = |cell: ReprResolvedCell| ResolvedCell {
+            out_point: cell.out_point.into(),
+            output: cell.output.into(),
+            data: cell.data.into_bytes(),
+            header: cell.header.map(|hash| hash.pack()),
+        };
+        let from_repr_indices = |indices: Vec<json_types::Uint32>| {
+            indices
+                .into_iter()
+                .map(|index| index.value() as usize)
+                .collect()
+        };
+        let script_groups = repr
+            .script_groups
+            .into_iter()
+            .map(|script_group| ScriptGroup {
+                script: script_group.script.into(),
+                group_type: script_group.group_type,
+                input_indices: from_repr_indices(script_group.input_indices),
+                output_indices: from_repr_indices(script_group.output_indices),
+            })
+            .collect();
+        let tx = packed::Transaction::from(repr.transaction).into_view();
+        Ok(Self {
+            tx_with_groups: TransactionWithScriptGroups::new(tx, script_groups),
+            inputs: repr.inputs.into_iter().map(from_repr_cell).collect(),
+            cell_deps: repr.cell_deps.into_iter().map(from_repr_cell).collect(),
+            header_deps: repr.header_deps.into_iter().map(HeaderView::from).collect(),
+        })
+    }
+}
@@ -4,11 +4,16 @@ use ckb_types::{
     prelude::*,
 };
 
-use crate::ScriptGroup;
+use crate::{transaction::builder::ChangeDecision, ScriptGroup};
 
+#[derive(Clone)]
 pub struct TransactionWithScriptGroups {
     pub(crate) tx_view: TransactionView,
     pub(crate) script_groups: Vec<ScriptGroup>,
+    /// How the change is handled, only available for the transactions built by the transaction builders.
+    pub(crate) change_decision: Option<ChangeDecision>,
+    /// The fee rate (shannons/KB) used to build the transaction, only available for the transactions built by the transaction builders.
+    pub(crate) fee_rate: Option<u64>,
 }
 
 impl TransactionWithScriptGroups {
@@ -16,6 +21,8 @@ impl TransactionWithScriptGroups {
         Self {
             tx_view,
             script_groups,
+            change_decision: None,
+            fee_rate: None,
         }
     }
     pub fn get_tx_view(&self) -> &TransactionView {
@@ -33,6 +40,22 @@ impl TransactionWithScriptGroups {
     pub fn set_script_groups(&mut self, script_groups: Vec<ScriptGroup>) {
         self.script_groups = script_groups;
     }
+
+    pub fn get_change_decision(&self) -> Option<&ChangeDecision> {
+        self.change_decision.as_ref()
+    }
+
+    pub fn set_change_decision(&mut self, change_decision: Option<ChangeDecision>) {
+        self.change_decision = change_decision;
+    }
+
+    pub fn get_fee_rate(&self) -> Option<u64> {
+        self.fee_rate
+    }
+
+    pub fn set_fee_rate(&mut self, fee_rate: Option<u64>) {
+        self.fee_rate = fee_rate;
+    }
 }
 
 #[derive(Default, Clone)]
@@ -82,6 +105,8 @@ impl TransactionWithScriptGroupsBuilder {
         TransactionWithScriptGroups {
             tx_view: self.tx_view.unwrap(),
             script_groups: self.script_groups,
+            change_decision: None,
+            fee_rate: None,
         }
     }
 }
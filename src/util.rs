@@ -2,14 +2,14 @@ use std::{convert::TryInto, ptr, sync::atomic};
 
 use ckb_dao_utils::extract_dao_data;
 use ckb_types::{
-    core::{Capacity, EpochNumber, EpochNumberWithFraction, HeaderView},
+    core::{Capacity, EpochNumber, EpochNumberWithFraction, HeaderView, RationalU256},
     packed::CellOutput,
     prelude::*,
     H160, H256, U256,
 };
 use sha3::{Digest, Keccak256};
 
-use crate::rpc::CkbRpcClient;
+use crate::rpc::{AsyncCkbRpcClient, CkbRpcClient};
 use crate::traits::LiveCell;
 
 pub fn zeroize_privkey(key: &mut secp256k1::SecretKey) {
@@ -28,24 +28,65 @@ pub fn zeroize_slice(data: &mut [u8]) {
 }
 
 pub fn get_max_mature_number(rpc_client: &CkbRpcClient) -> Result<u64, String> {
-    let cellbase_maturity = EpochNumberWithFraction::from_full_value(
-        rpc_client
-            .get_consensus()
-            .map_err(|err| err.to_string())?
-            .cellbase_maturity
-            .value(),
-    );
+    let cellbase_maturity = rpc_client
+        .get_consensus()
+        .map_err(|err| err.to_string())?
+        .cellbase_maturity;
     let tip_epoch = rpc_client
         .get_tip_header()
-        .map(|header| EpochNumberWithFraction::from_full_value(header.inner.epoch.value()))
-        .map_err(|err| err.to_string())?;
+        .map_err(|err| err.to_string())?
+        .inner
+        .epoch;
+    match max_mature_epoch(cellbase_maturity.value(), tip_epoch.value()) {
+        Some((epoch_number, difference_delta)) => {
+            let max_mature_epoch = rpc_client
+                .get_epoch_by_number(epoch_number.into())
+                .map_err(|err| err.to_string())?
+                .ok_or_else(|| "Can not get epoch less than current epoch number".to_string())?;
+            Ok(max_mature_block_number(difference_delta, &max_mature_epoch))
+        }
+        // No cellbase live cell is mature
+        None => Ok(0),
+    }
+}
+
+/// Same as [`get_max_mature_number`] but use the async rpc client.
+pub async fn get_max_mature_number_async(rpc_client: &AsyncCkbRpcClient) -> Result<u64, String> {
+    let cellbase_maturity = rpc_client
+        .get_consensus()
+        .await
+        .map_err(|err| err.to_string())?
+        .cellbase_maturity;
+    let tip_epoch = rpc_client
+        .get_tip_header()
+        .await
+        .map_err(|err| err.to_string())?
+        .inner
+        .epoch;
+    match max_mature_epoch(cellbase_maturity.value(), tip_epoch.value()) {
+        Some((epoch_number, difference_delta)) => {
+            let max_mature_epoch = rpc_client
+                .get_epoch_by_number(epoch_number.into())
+                .await
+                .map_err(|err| err.to_string())?
+                .ok_or_else(|| "Can not get epoch less than current epoch number".to_string())?;
+            Ok(max_mature_block_number(difference_delta, &max_mature_epoch))
+        }
+        None => Ok(0),
+    }
+}
+
+// Return the epoch number of the max mature block and the fraction in that epoch,
+// or None if no cellbase live cell is mature.
+fn max_mature_epoch(cellbase_maturity: u64, tip_epoch: u64) -> Option<(EpochNumber, RationalU256)> {
+    let cellbase_maturity = EpochNumberWithFraction::from_full_value(cellbase_maturity);
+    let tip_epoch = EpochNumberWithFraction::from_full_value(tip_epoch);
 
     let tip_epoch_rational = tip_epoch.to_rational();
     let cellbase_maturity_rational = cellbase_maturity.to_rational();
 
     if tip_epoch_rational < cellbase_maturity_rational {
-        // No cellbase live cell is mature
-        Ok(0)
+        None
     } else {
         let difference = tip_epoch_rational - cellbase_maturity_rational;
         let rounds_down_difference = difference.clone().into_u256();
@@ -55,26 +96,26 @@ pub fn get_max_mature_number(rpc_client: &CkbRpcClient) -> Result<u64, String> {
             rounds_down_difference.to_le_bytes()[..8]
                 .try_into()
                 .expect("should be u64"),
-        )
-        .into();
-        let max_mature_epoch = rpc_client
-            .get_epoch_by_number(epoch_number)
-            .map_err(|err| err.to_string())?
-            .ok_or_else(|| "Can not get epoch less than current epoch number".to_string())?;
-
-        let max_mature_block_number = (difference_delta
-            * U256::from(max_mature_epoch.length.value())
-            + U256::from(max_mature_epoch.start_number.value()))
-        .into_u256();
-
-        Ok(u64::from_le_bytes(
-            max_mature_block_number.to_le_bytes()[..8]
-                .try_into()
-                .expect("should be u64"),
-        ))
+        );
+        Some((epoch_number, difference_delta))
     }
 }
 
+fn max_mature_block_number(
+    difference_delta: RationalU256,
+    max_mature_epoch: &ckb_jsonrpc_types::EpochView,
+) -> u64 {
+    let max_mature_block_number = (difference_delta * U256::from(max_mature_epoch.length.value())
+        + U256::from(max_mature_epoch.start_number.value()))
+    .into_u256();
+
+    u64::from_le_bytes(
+        max_mature_block_number.to_le_bytes()[..8]
+            .try_into()
+            .expect("should be u64"),
+    )
+}
+
 pub fn is_mature(info: &LiveCell, max_mature_number: u64) -> bool {
     // Not cellbase cell
     info.tx_index > 0
@@ -379,4 +420,55 @@ mod tests {
             assert_eq!(151500, get_max_mature_number(&rpc_client).unwrap());
         }
     }
+
+    #[test]
+    fn test_get_max_mature_number_async() {
+        // cellbase maturity is 3(1/3), tip epoch is 3(300/600), epoch 3 starts at block 1800
+        // so the max mature block number is 1800 + (600 * 1 / 6) = 1900
+        let server = MockServer::start();
+        let consensus: Consensus = ConsensusBuilder::default()
+            .cellbase_maturity(EpochNumberWithFraction::new(3, 1, 3))
+            .build()
+            .into();
+        server.mock(|when, then| {
+            when.method(POST).path("/").body_contains("get_consensus");
+            then.status(200)
+                .body(MockRpcResult::new(consensus).to_json());
+        });
+        let tip_header: HeaderView = HeaderBuilder::default()
+            .epoch(
+                EpochNumberWithFraction::new(3, 300, 600)
+                    .full_value()
+                    .pack(),
+            )
+            .build()
+            .into();
+        server.mock(|when, then| {
+            when.method(POST).path("/").body_contains("get_tip_header");
+            then.status(200)
+                .body(MockRpcResult::new(tip_header).to_json());
+        });
+        let epoch3: EpochView = EpochView {
+            number: 3.into(),
+            start_number: 1800.into(),
+            length: 600.into(),
+            compact_target: 0.into(),
+        };
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .body_contains("get_epoch_by_number");
+            then.status(200).body(MockRpcResult::new(epoch3).to_json());
+        });
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let rpc_client = AsyncCkbRpcClient::new(server.base_url().as_str());
+        let max_mature_number = runtime
+            .block_on(get_max_mature_number_async(&rpc_client))
+            .unwrap();
+        assert_eq!(1900, max_mature_number);
+    }
 }